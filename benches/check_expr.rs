@@ -0,0 +1,49 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use permission_check::{CompiledExpr, check_expr};
+use permission_parser::{PermissionGroup, expr_parse, parse};
+use std::hint::black_box;
+
+const EXPR: &str = "((org.1047.role.admin.enact | org.1047.role.owner.enact) | (org.1047.user.write && (org.1047.user.read | org.1047.user.read_one)) | (org.1047.user.243.read && org.1047.user.243.write)) & !(user.blacklist.enact & !user.blacklist.*)";
+// Two of three approvals, every leaf appears twice
+const REPEATED_EXPR: &str = "(org.1047.role.admin.enact & org.1047.role.owner.enact) | (org.1047.role.admin.enact & org.1047.role.billing.enact) | (org.1047.role.owner.enact & org.1047.role.billing.enact)";
+
+fn group() -> PermissionGroup {
+    let mut perms: Vec<_> = (0..200)
+        .map(|i| parse(&format!("org.{}.user.{}.read", 2000 + i, i)).unwrap())
+        .collect();
+    perms.push(parse("org.1047.user.243.read").unwrap());
+    perms.push(parse("org.1047.user.243.write").unwrap());
+    perms.push(parse("user.blacklist.enact").unwrap());
+    perms.push(parse("org.1047.role.billing.enact").unwrap());
+    perms.into()
+}
+
+fn bench_check_expr(c: &mut Criterion) {
+    let group = group();
+    let groups = vec![group.clone(); 32];
+    for (name, expr) in [("readme", EXPR), ("repeated", REPEATED_EXPR)] {
+        let expr = expr_parse(expr).unwrap();
+        let program = CompiledExpr::compile(&expr);
+
+        c.bench_function(&format!("{}/tree_walk", name), |b| {
+            b.iter(|| check_expr(black_box(&expr), black_box(&group)))
+        });
+        c.bench_function(&format!("{}/compiled", name), |b| {
+            b.iter(|| program.eval(black_box(&group)))
+        });
+        c.bench_function(&format!("{}/tree_walk_batch_32", name), |b| {
+            b.iter(|| {
+                groups
+                    .iter()
+                    .map(|g| check_expr(black_box(&expr), g))
+                    .collect::<Vec<_>>()
+            })
+        });
+        c.bench_function(&format!("{}/compiled_batch_32", name), |b| {
+            b.iter(|| program.eval_many(black_box(&groups)))
+        });
+    }
+}
+
+criterion_group!(benches, bench_check_expr);
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use permission_check::{PatternAlgebra, check_one};
+use permission_parser::parse;
+
+// Input is a required permission and a grant, one per line
+fuzz_target!(|data: &str| {
+    let Some((require, grant)) = data.split_once('\n') else {
+        return;
+    };
+    let (Ok(require), Ok(grant)) = (parse(require), parse(grant)) else {
+        return;
+    };
+    let _ = check_one(&require, &grant);
+    let _ = check_one(&grant, &require);
+    for item in require.intersect(&grant) {
+        assert!(require.subsumes(&item) && grant.subsumes(&item));
+    }
+    for item in require.difference(&grant).perms {
+        assert!(require.subsumes(&item) && !grant.overlaps(&item));
+    }
+});
@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use permission_parser::{ItemExpr, PermissionGroup, PermissionItem};
+
+fuzz_target!(|data: &[u8]| {
+    // Whatever decodes must survive another encode/decode cycle unchanged
+    if let Ok(item) = PermissionItem::decode(data) {
+        let encoded = item.encode().unwrap();
+        assert_eq!(
+            PermissionItem::decode(&encoded).unwrap().encode().unwrap(),
+            encoded
+        );
+    }
+    if let Ok(group) = PermissionGroup::decode(data) {
+        let encoded = group.encode().unwrap();
+        assert_eq!(
+            PermissionGroup::decode(&encoded).unwrap().encode().unwrap(),
+            encoded
+        );
+    }
+    if let Ok(expr) = ItemExpr::decode(data) {
+        let encoded = expr.encode().unwrap();
+        assert_eq!(
+            ItemExpr::decode(&encoded).unwrap().encode().unwrap(),
+            encoded
+        );
+    }
+});
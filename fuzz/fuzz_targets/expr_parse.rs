@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use permission_check::ComplexCheck;
+use permission_parser::{PermissionGroup, expr_parse, expr_parse_pattern};
+
+fuzz_target!(|data: &str| {
+    if let Ok(expr) = expr_parse(data) {
+        let group = PermissionGroup { perms: vec![] };
+        let _ = ComplexCheck::from(&expr).with_perm(&group);
+        let _ = expr.encode();
+    }
+    let _ = expr_parse_pattern(data);
+});
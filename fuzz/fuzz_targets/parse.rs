@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use permission_parser::{parse, parse_pattern};
+
+fuzz_target!(|data: &str| {
+    // Parsing must fail with an error, never panic, and what parses must be a valid item which
+    // displays as text parsing back to the same item
+    if let Ok(item) = parse(data) {
+        item.validate().unwrap();
+        assert!(parse(&item.to_string()).unwrap() == item);
+    }
+    if let Ok(item) = parse_pattern(data) {
+        let _ = item.to_string();
+    }
+});
@@ -1,11 +1,18 @@
 use permission_parser::{
-    Expr, Permissions, parser,
-    tokenizer::{Field, ListSpecifier, Specifier},
+    Condition, Expr, ExprDefinition, PermissionItem, Permissions, Value,
+    condition::{CmpOp, Operand},
+    expr::definition_order,
+    parser,
+    tokenizer::{Field, ListSpecifier, Specifier, VarType},
 };
 use proc_macro::TokenStream;
 use proc_macro2::Span;
-use quote::{ToTokens, quote, quote_spanned};
-use syn::parse_macro_input;
+use quote::{ToTokens, format_ident, quote, quote_spanned};
+use syn::{
+    Visibility,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+};
 
 #[derive(Clone)]
 #[cfg_attr(debug_assertions, derive(Debug))]
@@ -29,6 +36,12 @@ fn enum_to_token(parsed_enum: Token) -> impl ToTokens {
                 quote! { ::permission_parser::tokenizer::Field::Name { name : (#name).to_string()}}
             }
             Field::ID { id } => quote! { ::permission_parser::tokenizer::Field::ID {id: #id} },
+            Field::LargeID { id } => {
+                quote! { ::permission_parser::tokenizer::Field::LargeID {id: #id} }
+            }
+            Field::Uuid { uuid } => {
+                quote! { ::permission_parser::tokenizer::Field::Uuid {uuid: #uuid} }
+            }
             Field::Specifier { specifier } => {
                 let inner = enum_to_token(Token::Specifier(specifier));
                 quote! {::permission_parser::tokenizer::Field::Specifier {specifier: #inner } }
@@ -36,20 +49,43 @@ fn enum_to_token(parsed_enum: Token) -> impl ToTokens {
             Field::Glob => quote! { ::permission_parser::tokenizer::Field::Glob },
             Field::DoubleGlob => quote! { ::permission_parser::tokenizer::Field::DoubleGlob },
             Field::TripleGlob => quote! { ::permission_parser::tokenizer::Field::TripleGlob },
-            Field::VarKind(span, ident) => {
-                quote_spanned! {
-                    span =>
+            Field::Prefix { prefix } => {
+                let prefix = syn::LitStr::new(&prefix, Span::call_site());
+                quote! { ::permission_parser::tokenizer::Field::Prefix { prefix: (#prefix).to_string() } }
+            }
+            Field::Range { start, end } => {
+                quote! { ::permission_parser::tokenizer::Field::Range { start: #start, end: #end } }
+            }
+            Field::Alternatives { fields } => {
+                let fields = fields
+                    .into_iter()
+                    .map(|field| enum_to_token(Token::Field(field)));
+                quote! { ::permission_parser::tokenizer::Field::Alternatives { fields: vec![#(#fields),*] } }
+            }
+            // A value that could fail to convert, like a signed integer, does not compile, as the
+            // permission would silently match nothing; a name never fails
+            Field::VarKind(span, ident, ty) => {
+                let name = ident.to_string();
+                let value = placeholder_value(span, &ident, ty);
+                let check = match ty {
+                    VarType::Name => quote! {},
+                    _ => {
+                        quote_spanned! { span => ::permission_parser::tokenizer::infallible(&#ident); }
+                    }
+                };
+                let ty = match ty {
+                    VarType::Any => format_ident!("Any"),
+                    VarType::Id => format_ident!("Id"),
+                    VarType::Name => format_ident!("Name"),
+                };
+                quote_spanned! { span =>
                     {
-                        #[inline(always)]
-                        fn converter<T: ::std::string::ToString>(v: &T) -> ::permission_parser::tokenizer::Field {
-                            let content =  v.to_string();
-                            match content.parse::<u64>() {
-                                Ok(value) => ::permission_parser::tokenizer::Field::ID {id: value},
-                                Err(_) => ::permission_parser::tokenizer::Field::Name {name: content}
-                            }
-                        }
-
-                        converter(&#ident)
+                        #check
+                        ::permission_parser::tokenizer::placeholder(
+                            #value,
+                            #name,
+                            ::permission_parser::tokenizer::VarType::#ty,
+                        )
                     }
                 }
             }
@@ -79,6 +115,29 @@ fn enum_to_token(parsed_enum: Token) -> impl ToTokens {
     }
 }
 
+// The conversion of a placeholder's value into a `Result<Field, PermissionParseError>`. The
+// type of the value picks the conversion, so unsupported types fail to compile at the placeholder
+fn placeholder_value(span: Span, ident: &syn::Ident, ty: VarType) -> proc_macro2::TokenStream {
+    let name = ident.to_string();
+    match ty {
+        VarType::Any => quote_spanned! { span =>
+            ::permission_parser::tokenizer::IntoField::to_field(&#ident)
+        },
+        VarType::Id => quote_spanned! { span =>
+            {
+                use ::permission_parser::tokenizer::IdPlaceholder as _;
+                (&#ident).id_placeholder(#name)
+            }
+        },
+        VarType::Name => quote_spanned! { span =>
+            {
+                use ::permission_parser::tokenizer::NamePlaceholder as _;
+                (&#ident).name_placeholder(#name)
+            }
+        },
+    }
+}
+
 fn to_internal_token(permissions: &Permissions) -> Vec<Token> {
     parser::token_converter(permissions.clone())
         .unwrap()
@@ -90,25 +149,172 @@ fn to_internal_token(permissions: &Permissions) -> Vec<Token> {
 fn perm_reconstructor(input: Vec<Token>) -> impl ToTokens {
     let code_token: Vec<_> = input
         .iter()
-        .map(|token| enum_to_token((*token).clone()))
+        .map(|token| enum_to_token((*token).clone()).into_token_stream())
         .collect();
+    item_constructor(code_token)
+}
+
+fn item_constructor(code_token: Vec<proc_macro2::TokenStream>) -> proc_macro2::TokenStream {
     quote! {
         ::permission_parser::parser::PermissionItem {
             perm: vec![
                 #(#code_token),*
-            ]
+            ],
+            validity: ::permission_parser::validity::Validity {
+                not_before: ::core::option::Option::None,
+                expires_at: ::core::option::Option::None,
+            },
+            condition: ::core::option::Option::None,
         }
     }
 }
 
+// Each permission is handed to `permission_check`, which registers it from where the macro is
+// used when its `registry` feature is on
+fn registration(permissions: Vec<&Permissions>, source: &str) -> proc_macro2::TokenStream {
+    let permissions = permissions.into_iter().map(|permissions| {
+        PermissionItem::from(parser::token_converter(permissions.clone()).unwrap()).to_string()
+    });
+    quote! {
+        #(
+            ::permission_check::__register_permission!(#permissions, #source);
+        )*
+    }
+}
+
+/// Builds a `PermissionItem` from a permission written in place, e.g.
+/// `perm_parser!(org.{org_id}.user.{user_id}.read)`. It cannot fail, so the values of `{var}` and
+/// `{var:id}` placeholders must be `InfallibleField`s: a signed integer, which could be a negative
+/// ID, does not compile, and neither does any other value whose conversion may fail. Use
+/// `try_perm_parser!` for those and for values from untrusted input.
 #[proc_macro]
 pub fn perm_parser(input: TokenStream) -> TokenStream {
-    let input: Vec<Token> = to_internal_token(&parse_macro_input!(input as parser::Permissions));
-    let v = perm_reconstructor(input);
-    quote! {#v}.into()
+    let permissions = parse_macro_input!(input as parser::Permissions);
+    let registration = registration(vec![&permissions], "perm_parser!");
+    let v = perm_reconstructor(to_internal_token(&permissions));
+    quote! {
+        {
+            #registration
+            #v
+        }
+    }
+    .into()
+}
+
+/// Like `perm_parser!`, but for values from untrusted input: every `{var}` placeholder is
+/// converted, so a negative ID is an error, then checked with `Field::interpolated`, and the item
+/// is checked with `validate`, and the first failure is
+/// returned as the `Err` of a `Result<PermissionItem, PermissionParseError>`. A value like `*`,
+/// `a.b` or `read` is rejected instead of becoming a name that looks like a glob, a deeper path
+/// or a specifier.
+#[proc_macro]
+pub fn try_perm_parser(input: TokenStream) -> TokenStream {
+    let permissions = parse_macro_input!(input as parser::Permissions);
+    let registration = registration(vec![&permissions], "try_perm_parser!");
+    let input: Vec<Token> = to_internal_token(&permissions);
+    let code_token: Vec<_> = input
+        .into_iter()
+        .map(|token| match token {
+            Token::Field(Field::VarKind(span, ident, ty)) => {
+                let value = placeholder_value(span, &ident, ty);
+                quote! { ::permission_parser::tokenizer::Field::interpolated(#value?)? }
+            }
+            token => enum_to_token(token).into_token_stream(),
+        })
+        .collect();
+    let item = item_constructor(code_token);
+    quote! {
+        {
+        #registration
+        (|| -> ::core::result::Result<
+            ::permission_parser::parser::PermissionItem,
+            ::permission_parser::PermissionParseError,
+        > {
+            let item = #item;
+            item.validate()?;
+            ::core::result::Result::Ok(item)
+        })()
+        }
+    }
+    .into()
+}
+
+fn value_to_token(value: &Value) -> proc_macro2::TokenStream {
+    match value {
+        Value::Int(i) => quote! { ::permission_parser::Value::Int(#i) },
+        Value::Str(s) => {
+            let s = syn::LitStr::new(s, Span::call_site());
+            quote! { ::permission_parser::Value::Str((#s).to_string()) }
+        }
+        Value::Bool(b) => quote! { ::permission_parser::Value::Bool(#b) },
+    }
+}
+
+fn operand_to_token(operand: &Operand) -> proc_macro2::TokenStream {
+    match operand {
+        Operand::Attr(key) => {
+            let key = syn::LitStr::new(key, Span::call_site());
+            quote! { ::permission_parser::condition::Operand::Attr((#key).to_string()) }
+        }
+        Operand::Literal(value) => {
+            let value = value_to_token(value);
+            quote! { ::permission_parser::condition::Operand::Literal(#value) }
+        }
+    }
+}
+
+fn condition_to_token(condition: &Condition) -> proc_macro2::TokenStream {
+    match condition {
+        Condition::Compare(l, op, r) => {
+            let l = operand_to_token(l);
+            let r = operand_to_token(r);
+            let op = match op {
+                CmpOp::Eq => format_ident!("Eq"),
+                CmpOp::Ne => format_ident!("Ne"),
+                CmpOp::Lt => format_ident!("Lt"),
+                CmpOp::Le => format_ident!("Le"),
+                CmpOp::Gt => format_ident!("Gt"),
+                CmpOp::Ge => format_ident!("Ge"),
+            };
+            quote! {
+                ::permission_parser::Condition::Compare(#l, ::permission_parser::condition::CmpOp::#op, #r)
+            }
+        }
+        Condition::In(operand, values) => {
+            let operand = operand_to_token(operand);
+            let values: Vec<_> = values.iter().map(value_to_token).collect();
+            quote! { ::permission_parser::Condition::In(#operand, vec![#(#values),*]) }
+        }
+        Condition::Not(c) => {
+            let c = condition_to_token(c);
+            quote! { ::permission_parser::Condition::Not(::std::boxed::Box::new(#c)) }
+        }
+        Condition::And(operands) => {
+            let operands: Vec<_> = operands.iter().map(condition_to_token).collect();
+            quote! { ::permission_parser::Condition::And(vec![#(#operands),*]) }
+        }
+        Condition::Or(operands) => {
+            let operands: Vec<_> = operands.iter().map(condition_to_token).collect();
+            quote! { ::permission_parser::Condition::Or(vec![#(#operands),*]) }
+        }
+    }
+}
+
+fn operands_to_token(
+    operands: Vec<Expr>,
+    conditions: &mut Vec<proc_macro2::TokenStream>,
+) -> Vec<proc_macro2::TokenStream> {
+    operands
+        .into_iter()
+        .map(|operand| expr_to_token(operand, conditions))
+        .collect()
 }
 
-fn expr_to_token(expr: Expr) -> impl ToTokens {
+// Conditions are built once outside the generated closure and collected in `conditions`
+fn expr_to_token(
+    expr: Expr,
+    conditions: &mut Vec<proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
     match expr {
         Expr::Permission(permissions) => {
             let tokens = perm_reconstructor(to_internal_token(&permissions));
@@ -116,48 +322,172 @@ fn expr_to_token(expr: Expr) -> impl ToTokens {
                 ::permission_check::check(&#tokens, var)
             }
         }
-        Expr::And(left, right) => {
-            let left = expr_to_token(*left);
-            let right = expr_to_token(*right);
+        Expr::Predicate(condition) => {
+            let name = format_ident!("__permission_condition_{}", conditions.len());
+            let condition = condition_to_token(&condition);
+            conditions.push(quote! { let #name = #condition; });
             quote! {
-                (#left && #right)
+                #name.eval(ctx)
             }
         }
-        Expr::Or(left, right) => {
-            let left = expr_to_token(*left);
-            let right = expr_to_token(*right);
+        Expr::And(operands) => {
+            let operands = operands_to_token(operands, conditions);
             quote! {
-                (#left || #right)
+                (#(#operands)&&*)
             }
         }
-        Expr::Xor(left, right) => {
-            let left = expr_to_token(*left);
-            let right = expr_to_token(*right);
+        Expr::Or(operands) => {
+            let operands = operands_to_token(operands, conditions);
             quote! {
-                (#left ^ #right)
+                (#(#operands)||*)
+            }
+        }
+        Expr::Xor(operands) => {
+            let operands = operands_to_token(operands, conditions);
+            quote! {
+                (#(#operands)^*)
             }
         }
         Expr::Not(item) => {
-            let item = expr_to_token(*item);
+            let item = expr_to_token(*item, conditions);
             quote! {
                 (!(#item))
             }
         }
         Expr::Bracketed(item) => {
-            let item = expr_to_token(*item);
+            let item = expr_to_token(*item, conditions);
             quote! {
                 (#item)
             }
         }
+        // `any()` and `all()`
+        Expr::AtLeast(count, operands) if operands.is_empty() => {
+            let holds = count == 0;
+            quote! { #holds }
+        }
+        // Operands are not evaluated once the count is reached or too few are left to reach it
+        Expr::AtLeast(count, operands) => {
+            let left = (1..=operands.len()).rev();
+            let operands = operands_to_token(operands, conditions);
+            quote! {
+                {
+                    let mut __permission_missing: usize = #count;
+                    #(
+                        if __permission_missing > 0 && #left >= __permission_missing && #operands {
+                            __permission_missing -= 1;
+                        }
+                    )*
+                    __permission_missing == 0
+                }
+            }
+        }
+        // The placeholders are bound as local variables, shadowing any of the same name
+        Expr::Exists(vars, body) => {
+            let count = vars.len();
+            let idx = 0..count;
+            let body = expr_to_token(*body, conditions);
+            quote! {
+                ::permission_check::exists(var, #count, |__permission_values: &[::permission_parser::tokenizer::Field]| {
+                    #(let #vars = &__permission_values[#idx];)*
+                    #body
+                })
+            }
+        }
+        // A function defined with `perm_def!`
+        Expr::Call(name, args) => {
+            let args: Vec<_> = args
+                .iter()
+                .map(|arg| enum_to_token(to_internal_token(arg).remove(0)))
+                .collect();
+            quote! {
+                #name(var, ctx, #(#args),*)
+            }
+        }
     }
 }
 
+/// Builds a `ComplexCheck` from an expression written in place, e.g.
+/// `perm_expr!(org.{org_id}.doc.read & !user.blacklist.enact)`. Its placeholders take the same
+/// values as those of `perm_parser!`.
 #[proc_macro]
 pub fn perm_expr(input: TokenStream) -> TokenStream {
     let output = parse_macro_input!(input as Expr);
-    let token_content = expr_to_token(output);
-    let expanded = quote! {
-        ::permission_check::ComplexCheck::new(::std::boxed::Box::new(move |var| #token_content))
+    let registration = registration(output.permissions(), "perm_expr!");
+    let has_calls = !output.calls().is_empty();
+    let mut conditions = vec![];
+    let token_content = expr_to_token(output, &mut conditions);
+    let expanded = if conditions.is_empty() && !has_calls {
+        quote! {
+            ::permission_check::ComplexCheck::new(::std::boxed::Box::new(move |var| #token_content))
+        }
+    } else {
+        quote! {
+            {
+                #(#conditions)*
+                ::permission_check::ComplexCheck::new_with_context(::std::boxed::Box::new(move |var, ctx| #token_content))
+            }
+        }
     };
-    expanded.into()
+    quote! {
+        {
+            #registration
+            #expanded
+        }
+    }
+    .into()
+}
+
+struct Definitions(Vec<(Visibility, ExprDefinition)>);
+
+impl Parse for Definitions {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut definitions = vec![];
+        while !input.is_empty() {
+            definitions.push((input.parse()?, input.parse()?));
+        }
+        Ok(Self(definitions))
+    }
+}
+
+/// Defines named expressions for `perm_expr!`, e.g.
+/// `perm_def! { pub let is_org_admin(o) = org.{o}.role.admin.enact | org.{o}.role.owner.enact; }`
+/// makes `perm_expr!(is_org_admin({org_id}) & org.{org_id}.billing)` possible. Each definition
+/// becomes a function of the group, the request context and a `Field` per parameter, so the
+/// usual name resolution applies. Recursion is rejected between definitions of the same block.
+#[proc_macro]
+pub fn perm_def(input: TokenStream) -> TokenStream {
+    let Definitions(definitions) = parse_macro_input!(input as Definitions);
+    let (visibilities, definitions): (Vec<_>, Vec<_>) = definitions.into_iter().unzip();
+    if let Err(cycle) = definition_order(&definitions) {
+        let names: Vec<String> = cycle.iter().map(|name| name.to_string()).collect();
+        return syn::Error::new(
+            cycle[0].span(),
+            format!("Recursive expression: {}", names.join(" -> ")),
+        )
+        .to_compile_error()
+        .into();
+    }
+    let functions = visibilities
+        .into_iter()
+        .zip(definitions)
+        .map(|(vis, definition)| {
+            let name = definition.name;
+            let params = definition.params;
+            let mut conditions = vec![];
+            let registration = registration(definition.body.permissions(), "perm_def!");
+            let body = expr_to_token(definition.body, &mut conditions);
+            quote! {
+                #[allow(unused_variables)]
+                #vis fn #name(
+                    var: &::permission_parser::PermissionGroup,
+                    ctx: &dyn ::permission_parser::Context,
+                    #(#params: ::permission_parser::tokenizer::Field),*
+                ) -> bool {
+                    #registration
+                    #(#conditions)*
+                    #body
+                }
+            }
+        });
+    quote! { #(#functions)* }.into()
 }
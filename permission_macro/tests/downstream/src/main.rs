@@ -0,0 +1,20 @@
+use permission_macro::{perm_def, perm_expr, perm_parser};
+
+perm_def! {
+    let is_auditor(o) = org.{o}.role.auditor;
+}
+
+fn main() {
+    let org: u64 = 1047;
+    let checker = perm_expr!(org.{org}.doc.read & !is_auditor({ org }));
+    assert!(checker.with_perm(perm_parser!(org.1047.doc.read)));
+    #[cfg(feature = "signed_placeholder")]
+    {
+        let org = -1;
+        perm_parser!(org.{org});
+    }
+    #[cfg(feature = "registry")]
+    for registered in permission_check::registry::registered_permissions() {
+        println!("{}", registered);
+    }
+}
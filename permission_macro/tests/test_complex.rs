@@ -1,4 +1,4 @@
-use permission_macro::{perm_expr, perm_parser};
+use permission_macro::{perm_expr, perm_parser, try_perm_parser};
 
 #[test]
 fn always_false() {
@@ -73,8 +73,8 @@ fn test_1() {
         ]),
         true
     );
-    let org_id = 1047;
-    let user_id = 243;
+    let org_id: u64 = 1047;
+    let user_id: u64 = 243;
     assert_eq!(
         checker.with_perm(vec![
             perm_parser!(org.{org_id}.user.{user_id}.read),
@@ -82,8 +82,8 @@ fn test_1() {
         ]),
         true
     );
-    let org_id = 1047;
-    let user_id = 244;
+    let org_id: u64 = 1047;
+    let user_id: u64 = 244;
     assert_eq!(
         checker.with_perm(vec![
             perm_parser!(org.{org_id}.user.{user_id}.read),
@@ -92,3 +92,385 @@ fn test_1() {
         false
     );
 }
+
+#[test]
+fn context_predicate() {
+    use permission_parser::Value;
+    use std::collections::HashMap;
+
+    let o: u64 = 1047;
+    let d: u64 = 3;
+    let checker = perm_expr!(org.{o}.doc.{d}.write & ctx(owner_id == subject_id && !suspended));
+    let ctx = |owner_id: i64, subject_id: i64| {
+        HashMap::from([
+            ("owner_id".to_string(), Value::Int(owner_id)),
+            ("subject_id".to_string(), Value::Int(subject_id)),
+            ("suspended".to_string(), Value::Bool(false)),
+        ])
+    };
+    assert_eq!(
+        checker.with_perm_and_context(perm_parser!(org.1047.doc), &ctx(5, 5)),
+        true
+    );
+    assert_eq!(
+        checker.with_perm_and_context(perm_parser!(org.1047.doc), &ctx(5, 6)),
+        false
+    );
+    assert_eq!(
+        checker.with_perm_and_context(perm_parser!(org.1048.doc), &ctx(5, 5)),
+        false
+    );
+    assert_eq!(checker.with_perm(perm_parser!(org.1047.doc)), false);
+}
+
+#[test]
+fn quantifiers() {
+    let o: u64 = 1047;
+    let checker = perm_expr!(
+        atleast(2, org.{o}.role.admin, org.{o}.role.owner, org.{o}.role.auditor)
+            & !any(user.blacklist.enact, user.suspended)
+    );
+    assert_eq!(
+        checker.with_perm(vec![
+            perm_parser!(org.1047.role.admin),
+            perm_parser!(org.1047.role.auditor)
+        ]),
+        true
+    );
+    assert_eq!(checker.with_perm(perm_parser!(org.1047.role.admin)), false);
+    assert_eq!(
+        checker.with_perm(vec![
+            perm_parser!(org.1047.role.admin),
+            perm_parser!(org.1047.role.owner),
+            perm_parser!(user.suspended)
+        ]),
+        false
+    );
+    let all = perm_expr!(all(org.1047.doc.read, org.1047.doc.write));
+    assert_eq!(all.with_perm(perm_parser!(org.1047.doc)), true);
+    assert_eq!(all.with_perm(perm_parser!(org.1047.doc.read)), false);
+    assert_eq!(perm_expr!(all()).with_perm(vec![]), true);
+    // Evaluation stops once too few operands are left to reach the count
+    struct Recorded(std::cell::RefCell<Vec<String>>);
+    impl permission_parser::Context for Recorded {
+        fn get(&self, key: &str) -> Option<permission_parser::Value> {
+            self.0.borrow_mut().push(key.to_string());
+            Some(permission_parser::Value::Bool(key == "c"))
+        }
+    }
+    let ctx = Recorded(Default::default());
+    let checker = perm_expr!(atleast(2, ctx(a), ctx(b), ctx(c)));
+    assert_eq!(checker.with_perm_and_context(vec![], &ctx), false);
+    assert_eq!(ctx.0.into_inner(), vec!["a", "b"]);
+    assert_eq!(perm_expr!(any() | org.1047).with_perm(vec![]), false);
+}
+
+#[test]
+fn exists() {
+    let o: u64 = 1047;
+    let checker = perm_expr!(exists u: org.{o}.user.{u}.read & org.{o}.user.{u}.write);
+    assert_eq!(
+        checker.with_perm(vec![
+            perm_parser!(org.1047.user.5.read),
+            perm_parser!(org.1047.user.5.write)
+        ]),
+        true
+    );
+    assert_eq!(
+        checker.with_perm(vec![
+            perm_parser!(org.1047.user.5.read),
+            perm_parser!(org.1047.user.6.write)
+        ]),
+        false
+    );
+    assert_eq!(
+        checker.with_perm(vec![
+            perm_parser!(org.1047.user.*.read),
+            perm_parser!(org.1047.user.6.write)
+        ]),
+        true
+    );
+    let unlisted = perm_expr!(exists u: !org.1047.user.{u}.read);
+    assert_eq!(unlisted.with_perm(perm_parser!(org.1047.user.5.read)), true);
+    assert_eq!(
+        unlisted.with_perm(perm_parser!(org.1047.user.*.read)),
+        false
+    );
+}
+
+mod guards {
+    use permission_macro::perm_def;
+
+    perm_def! {
+        pub let is_org_admin(o) = org.{o}.role.admin.enact | org.{o}.role.owner.enact;
+        pub let can_edit(o, d) = is_org_admin({o}) | (org.{o}.doc.{d}.write & !ctx(suspended));
+    }
+}
+
+#[test]
+fn named_expressions() {
+    use guards::{can_edit, is_org_admin};
+    use permission_parser::{EmptyContext, Value};
+    use std::collections::HashMap;
+
+    let org_id: u64 = 1047;
+    let checker = perm_expr!(is_org_admin({ org_id }) & !user.blacklist.enact);
+    assert_eq!(checker.with_perm(perm_parser!(org.1047.role.owner)), true);
+    assert_eq!(checker.with_perm(perm_parser!(org.1048.role.owner)), false);
+
+    let checker = perm_expr!(can_edit({ org_id }, 3));
+    let suspended = HashMap::from([("suspended".to_string(), Value::Bool(true))]);
+    assert_eq!(checker.with_perm(perm_parser!(org.1047.doc.3.write)), true);
+    assert_eq!(
+        checker.with_perm_and_context(perm_parser!(org.1047.doc.3.write), &suspended),
+        false
+    );
+    assert_eq!(
+        checker.with_perm_and_context(perm_parser!(org.1047.role.admin), &suspended),
+        true
+    );
+    assert_eq!(
+        is_org_admin(
+            &perm_parser!(org.1047.role.admin).into(),
+            &EmptyContext,
+            permission_parser::tokenizer::Field::ID { id: 1047 }
+        ),
+        true
+    );
+    let _ = can_edit;
+}
+
+#[test]
+fn segment_patterns() {
+    let checker = perm_expr!(org.team_a.doc.100.read & org.team_b.doc.150.write);
+    assert_eq!(
+        checker.with_perm(perm_parser!(org.team_*.doc.*.{read,write})),
+        true
+    );
+    assert_eq!(
+        checker.with_perm(perm_parser!(org.team_*.doc.100..200)),
+        true
+    );
+    assert_eq!(
+        checker.with_perm(perm_parser!(org.team_*.doc.100..150)),
+        false
+    );
+    assert_eq!(checker.with_perm(perm_parser!(org.{team_a,team_b})), true);
+    assert_eq!(checker.with_perm(perm_parser!(org.team_a*)), false);
+    assert_eq!(
+        perm_parser!(org.team_*.100..200.doc.{read,write}).to_string(),
+        "org.team_*.100..200.doc.{read,write}"
+    );
+}
+
+#[test]
+fn quoted_names() {
+    let checker = perm_expr!(org.1047.project."my-project".read & !org.1047."blocked@example.com");
+    assert_eq!(
+        checker.with_perm(perm_parser!(org.1047.project."my-"*)),
+        true
+    );
+    assert_eq!(checker.with_perm(perm_parser!(org.1047.*)), false);
+    assert_eq!(checker.with_perm(perm_parser!(org.1047.**)), false);
+    assert_eq!(checker.with_perm(perm_parser!(org.1047.*)), false);
+    assert_eq!(checker.with_perm(perm_parser!(org.1047.**)), false);
+    assert_eq!(
+        checker.with_perm(vec![
+            perm_parser!(org.1047.project."my-project"),
+            perm_parser!(org.1047."blocked@example.com")
+        ]),
+        false
+    );
+    assert_eq!(
+        perm_parser!(org.1047.project."my-project".read).to_string(),
+        r#"org.1047.project."my-project".read"#
+    );
+}
+
+#[test]
+fn uuid_and_large_ids() {
+    let checker = perm_expr!(org."550e8400-e29b-41d4-a716-446655440000"uuid.doc.read);
+    assert_eq!(
+        checker.with_perm(perm_parser!(org."550E8400-E29B-41D4-A716-446655440000"uuid)),
+        true
+    );
+    assert_eq!(checker.with_perm(perm_parser!(org.*.doc)), true);
+    assert_eq!(
+        checker.with_perm(perm_parser!(org."550e8400-e29b-41d4-a716-446655440000")),
+        false
+    );
+    let snowflake = 340282366920938463463374607431768211455u128;
+    assert_eq!(
+        perm_parser!(org.{snowflake}.read)
+            == perm_parser!(org.340282366920938463463374607431768211455.read),
+        true
+    );
+    let small = 1047u128;
+    assert_eq!(perm_parser!(org.{small}) == perm_parser!(org.1047), true);
+    // A string is a name whatever it holds
+    let uuid = "550e8400-e29b-41d4-a716-446655440000";
+    assert_eq!(
+        perm_parser!(org.{uuid}) == perm_parser!(org."550e8400-e29b-41d4-a716-446655440000"),
+        true
+    );
+}
+
+struct OrgId(u64);
+
+impl permission_parser::tokenizer::InfallibleField for OrgId {}
+
+impl permission_parser::tokenizer::IntoField for OrgId {
+    fn to_field(
+        &self,
+    ) -> Result<permission_parser::tokenizer::Field, permission_parser::PermissionParseError> {
+        Ok(permission_parser::tokenizer::Field::ID { id: self.0 })
+    }
+}
+
+#[test]
+fn typed_placeholders() {
+    use permission_parser::tokenizer::Specifier;
+
+    // The type of the value decides, not what it displays as
+    let code = "42";
+    assert_eq!(perm_parser!(org.{code}) == perm_parser!(org."42"), true);
+    assert_eq!(perm_parser!(org.{code}) == perm_parser!(org.42), false);
+    let number: u64 = 42;
+    assert_eq!(
+        perm_parser!(org.{number:name}) == perm_parser!(org."42"),
+        true
+    );
+    assert_eq!(perm_parser!(org.{number:id}) == perm_parser!(org.42), true);
+    let specifier = Specifier::Read;
+    assert_eq!(
+        perm_parser!(org.1047.{specifier}) == perm_parser!(org.1047.read),
+        true
+    );
+    // Values of an `exists` are fields, of the wrong kind they do not fill the placeholder
+    let checker = perm_expr!(exists u: org.1.user.{u:id}.read);
+    assert_eq!(checker.with_perm(perm_parser!(org.1.user.5.read)), true);
+    assert_eq!(checker.with_perm(perm_parser!(org.1.user.*.read)), true);
+    assert_eq!(
+        checker.with_perm(perm_parser!(org.1.user.alice.read)),
+        false
+    );
+    let checker = perm_expr!(exists u: org.1.user.{u:name}.read);
+    assert_eq!(checker.with_perm(perm_parser!(org.1.user.5.read)), false);
+    assert_eq!(checker.with_perm(perm_parser!(org.1.user.alice.read)), true);
+    let org = OrgId(1047);
+    let doc = "report".to_string();
+    assert_eq!(
+        perm_parser!(org.{org}.doc.{doc}).to_string(),
+        "org.1047.doc.report"
+    );
+    let checker = perm_expr!(org.{org}.doc.{doc:name}.read);
+    assert_eq!(checker.with_perm(perm_parser!(org.1047.doc.report)), true);
+    assert_eq!(checker.with_perm(perm_parser!(org.1047.doc.rep*)), true);
+    assert_eq!(checker.with_perm(perm_parser!(org.1048.doc.report)), false);
+    // A signed integer only fills a name, see `test_downstream.rs` for the other placeholders
+    let org = -1;
+    assert_eq!(perm_parser!(org.{org:name}).to_string(), r#"org."-1""#);
+}
+
+#[test]
+fn try_perm_parser_rejects_injection() -> Result<(), permission_parser::PermissionParseError> {
+    use permission_parser::tokenizer::{Field, Specifier};
+
+    let user = "alice";
+    let doc: u64 = 3;
+    assert_eq!(
+        try_perm_parser!(org.1047.user.{user}.doc.{doc}.read)?
+            == perm_parser!(org.1047.user.alice.doc.3.read),
+        true
+    );
+    let uuid = Field::Uuid { uuid: 1 };
+    assert_eq!(try_perm_parser!(org.{uuid}.read).is_ok(), true);
+    let specifier = Specifier::Write;
+    assert_eq!(try_perm_parser!(org.1047.{specifier}).is_ok(), true);
+    for user in [
+        "*",
+        "**",
+        "***",
+        "team_*",
+        "a.b",
+        "1047.user",
+        "read",
+        "list_all",
+        "",
+    ] {
+        assert_eq!(
+            try_perm_parser!(org.1047.user.{user}.read).is_err(),
+            true,
+            "{:?}",
+            user
+        );
+        // The plain macro takes them as names
+        assert_eq!(
+            perm_parser!(org.1047.user.{user}.read).perm[3]
+                == Field::Name {
+                    name: user.to_string()
+                },
+            true
+        );
+    }
+    // Names that need quotes but cannot be mistaken for more are fine
+    let user = "alice@example";
+    assert_eq!(
+        try_perm_parser!(org.1047.user.{user}.read)?.to_string(),
+        r#"org.1047.user."alice@example".read"#
+    );
+    // Fields that match more than one segment are rejected, as is a specifier before the end
+    for value in [
+        Field::Glob,
+        Field::DoubleGlob,
+        Field::TripleGlob,
+        Field::Prefix {
+            prefix: "team_".to_string(),
+        },
+        Field::Range { start: 1, end: 5 },
+        Field::Alternatives {
+            fields: vec![Field::ID { id: 1 }, Field::ID { id: 2 }],
+        },
+        Specifier::Read.into(),
+    ] {
+        assert_eq!(
+            try_perm_parser!(org.{value}.read).is_err(),
+            true,
+            "{}",
+            value
+        );
+    }
+    let doc = Field::Name {
+        name: "report".to_string(),
+    };
+    assert_eq!(try_perm_parser!(org.{doc:id}.read).is_err(), true);
+    Ok(())
+}
+
+#[test]
+fn try_perm_parser_negative_ids() {
+    let org = -1i32;
+    let result = std::panic::catch_unwind(|| try_perm_parser!(org.{org}.read));
+    assert_eq!(
+        matches!(
+            result.unwrap(),
+            Err(permission_parser::PermissionParseError::InvalidOutput(message))
+                if message == "An ID cannot be negative, got `-1`"
+        ),
+        true
+    );
+    let user = i64::MIN;
+    assert_eq!(
+        try_perm_parser!(org.1047.user.{user:id}.read).is_err(),
+        true
+    );
+    let user = -3isize;
+    assert_eq!(try_perm_parser!(org.1047.user.{user:id}).is_err(), true);
+    // Zero and the names of negative numbers are fine
+    let org = 0i8;
+    assert_eq!(try_perm_parser!(org.{org}.read).is_ok(), true);
+    assert_eq!(
+        try_perm_parser!(org.{user:name}.read).unwrap().to_string(),
+        r#"org."-3".read"#
+    );
+}
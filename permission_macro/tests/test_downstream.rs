@@ -0,0 +1,75 @@
+use std::{
+    env,
+    path::Path,
+    process::{Command, Output},
+};
+
+// Builds and runs `tests/downstream`, which depends on the macros without any of their features
+// and on `permission_check` with or without `registry`, as a dependent crate would
+fn build_downstream(features: &[&str]) -> Output {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["run", "--quiet", "--offline", "--manifest-path"])
+        .arg(manifest_dir.join("tests/downstream/Cargo.toml"))
+        .arg("--features")
+        .arg(features.join(","))
+        .env(
+            "CARGO_TARGET_DIR",
+            manifest_dir.join("../target/downstream"),
+        )
+        .output()
+        .unwrap()
+}
+
+fn run_downstream(features: &[&str]) -> String {
+    let output = build_downstream(features);
+    assert_eq!(
+        output.status.success(),
+        true,
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn registry_feature_of_permission_check_alone() {
+    let registered = run_downstream(&["registry"]);
+    let permissions: Vec<&str> = registered
+        .lines()
+        .map(|line| line.split(' ').next().unwrap())
+        .collect();
+    assert_eq!(
+        permissions,
+        vec![
+            "org.{o}.role.auditor",
+            "org.{org}.doc.read",
+            "org.1047.doc.read",
+        ]
+    );
+    assert_eq!(
+        registered
+            .lines()
+            .all(|line| line.contains("at src/main.rs:")),
+        true
+    );
+}
+
+#[test]
+fn without_registry() {
+    assert_eq!(run_downstream(&[]), "");
+}
+
+#[test]
+fn signed_placeholder_does_not_compile() {
+    let output = build_downstream(&["signed_placeholder"]);
+    assert_eq!(output.status.success(), false);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr).contains(
+            "`i32` may fail to convert, so it cannot fill a placeholder of `perm_parser!`"
+        ),
+        true,
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,101 @@
+use permission_check::registry::{dead_permissions, registered_permissions};
+use permission_macro::{perm_def, perm_expr, perm_parser, try_perm_parser};
+use permission_parser::{PermissionGroup, PermissionParseError, parse};
+
+mod guards {
+    use permission_macro::perm_def;
+
+    perm_def! {
+        pub let is_billing_admin(o) = org.{o}.billing.admin.enact;
+    }
+}
+
+perm_def! {
+    let is_auditor(o) = org.{o}.role.auditor;
+}
+
+#[test]
+fn macros_register_permissions() {
+    let org: u64 = 1047;
+    let _ = perm_expr!(org.{org}.doc.read & !user.blacklist.enact);
+    let parser_line = line!() + 1;
+    let _ = perm_parser!(org.1047.user.*.read);
+    let user = "alice";
+    let _ = try_perm_parser!(org.1047.user.{user}.write);
+    let registered = registered_permissions();
+    for (permission, source) in [
+        ("org.{org}.doc.read", "perm_expr!"),
+        ("user.blacklist.enact", "perm_expr!"),
+        ("org.1047.user.*.read", "perm_parser!"),
+        ("org.1047.user.{user}.write", "try_perm_parser!"),
+        ("org.{o}.billing.admin.enact", "perm_def!"),
+        ("org.{o}.role.auditor", "perm_def!"),
+    ] {
+        let found = registered
+            .iter()
+            .find(|r| r.permission == permission)
+            .unwrap_or_else(|| panic!("{} is not registered", permission));
+        assert_eq!(found.source, source);
+        assert_eq!(found.file, file!());
+        assert_eq!(found.pattern().is_ok(), true);
+    }
+    // Registered even when the code never runs
+    assert_eq!(
+        registered
+            .iter()
+            .any(|r| r.permission == "org.{o}.never.called"),
+        true
+    );
+    let line = registered
+        .iter()
+        .find(|r| r.permission == "org.1047.user.*.read")
+        .unwrap();
+    assert_eq!(line.line, parser_line);
+    assert_eq!(
+        line.to_string(),
+        format!(
+            "org.1047.user.*.read (perm_parser! at {}:{}:{})",
+            file!(),
+            line.line,
+            line.column
+        )
+    );
+    // Ordered by location
+    assert_eq!(
+        registered
+            .windows(2)
+            .all(|w| (w[0].file, w[0].line, w[0].column) <= (w[1].file, w[1].line, w[1].column)),
+        true
+    );
+}
+
+#[allow(dead_code)]
+fn never_called() -> bool {
+    let o: u64 = 1;
+    perm_expr!(org.{o}.never.called).with_perm(vec![])
+}
+
+#[test]
+fn dead_grants() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = vec![
+        parse("org.5.doc")?,
+        parse("org.5.billing.admin")?,
+        parse("org.5.payroll.read")?,
+        parse("legacy.*")?,
+        parse("org.*.role.auditor")?,
+    ]
+    .into();
+    let registered: Vec<_> = registered_permissions()
+        .into_iter()
+        .filter(|r| r.file == file!() && r.source != "perm_parser!")
+        .collect();
+    let dead: Vec<String> = dead_permissions(&group, &registered)?
+        .iter()
+        .map(|p| p.to_string())
+        .collect();
+    assert_eq!(
+        dead,
+        vec!["org.5.payroll.read".to_string(), "legacy.*".to_string()]
+    );
+    Ok(())
+}
@@ -0,0 +1,422 @@
+use crate::{
+    PermissionItem, PermissionParseError,
+    limits::{ParseLimits, check_input},
+};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+use syn::{
+    Ident, LitBool, LitInt, LitStr, Token, bracketed,
+    ext::IdentExt,
+    parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    token::{Bracket, Paren},
+};
+
+/// Maximum nesting of brackets and `!` accepted when parsing a condition
+pub const MAX_CONDITION_DEPTH: usize = 64;
+
+/// Value of a request attribute or a literal in a condition
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum Value {
+    Int(i64),
+    Str(String),
+    Bool(bool),
+}
+
+impl Value {
+    // Values of different types are not comparable
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(l), Value::Int(r)) => Some(l.cmp(r)),
+            (Value::Str(l), Value::Str(r)) => Some(l.cmp(r)),
+            (Value::Bool(l), Value::Bool(r)) => Some(l.cmp(r)),
+            _ => None,
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Str(s) => write!(f, "{:?}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// Attributes of the request a condition is evaluated against, e.g. `network` or `mfa_age`
+pub trait Context {
+    fn get(&self, key: &str) -> Option<Value>;
+}
+
+impl Context for HashMap<String, Value> {
+    fn get(&self, key: &str) -> Option<Value> {
+        HashMap::get(self, key).cloned()
+    }
+}
+
+/// A context without attributes, used when a check is not given one
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct EmptyContext;
+
+impl Context for EmptyContext {
+    fn get(&self, _key: &str) -> Option<Value> {
+        None
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum Operand {
+    /// A context attribute, possibly dotted like `request.ip`
+    Attr(String),
+    Literal(Value),
+}
+
+impl Operand {
+    fn resolve(&self, ctx: &dyn Context) -> Option<Value> {
+        match self {
+            Operand::Attr(key) => ctx.get(key),
+            Operand::Literal(value) => Some(value.clone()),
+        }
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Attr(key) => write!(f, "{}", key),
+            Operand::Literal(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Display for CmpOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// A condition over the request context, e.g. `network == "corp" && mfa_age <= 900`.
+///
+/// A bare attribute `mfa` is short for `mfa == true`. A comparison with a missing attribute or
+/// values of different types is false, whichever the operator.
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum Condition {
+    Compare(Operand, CmpOp, Operand),
+    /// `attr in ["a", "b"]`
+    In(Operand, Vec<Value>),
+    Not(Box<Condition>),
+    /// Chains of `&&` and `||` are kept as lists, so a long chain stays shallow
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+}
+
+impl Condition {
+    pub fn eval(&self, ctx: &dyn Context) -> bool {
+        match self {
+            Condition::Compare(l, op, r) => {
+                let (Some(l), Some(r)) = (l.resolve(ctx), r.resolve(ctx)) else {
+                    return false;
+                };
+                match (l.compare(&r), op) {
+                    (None, _) => false,
+                    (Some(_), CmpOp::Eq) => l == r,
+                    (Some(_), CmpOp::Ne) => l != r,
+                    (Some(ordering), CmpOp::Lt) => ordering.is_lt(),
+                    (Some(ordering), CmpOp::Le) => ordering.is_le(),
+                    (Some(ordering), CmpOp::Gt) => ordering.is_gt(),
+                    (Some(ordering), CmpOp::Ge) => ordering.is_ge(),
+                }
+            }
+            Condition::In(operand, values) => operand
+                .resolve(ctx)
+                .is_some_and(|value| values.contains(&value)),
+            Condition::Not(c) => !c.eval(ctx),
+            Condition::And(operands) => operands.iter().all(|c| c.eval(ctx)),
+            Condition::Or(operands) => operands.iter().any(|c| c.eval(ctx)),
+        }
+    }
+
+    /// Levels of the condition tree, one for a comparison
+    pub(crate) fn depth(&self) -> usize {
+        match self {
+            Condition::Compare(..) | Condition::In(..) => 1,
+            Condition::Not(c) => 1 + c.depth(),
+            Condition::And(operands) | Condition::Or(operands) => {
+                1 + operands.iter().map(Condition::depth).max().unwrap_or(0)
+            }
+        }
+    }
+
+    /// Both conditions, extending an `And` on either side rather than nesting it
+    pub fn and(self, other: Condition) -> Condition {
+        let mut operands = vec![];
+        for condition in [self, other] {
+            match condition {
+                Condition::And(nested) => operands.extend(nested),
+                condition => operands.push(condition),
+            }
+        }
+        Condition::And(operands)
+    }
+
+    // `||` binds loosest, then `&&`, then `!` and comparisons
+    fn precedence(&self) -> u8 {
+        match self {
+            Condition::Or(_) => 0,
+            Condition::And(_) => 1,
+            _ => 2,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut Formatter<'_>, precedence: u8) -> fmt::Result {
+        if self.precedence() < precedence {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Compare(l, op, r) => write!(f, "{} {} {}", l, op, r),
+            Condition::In(operand, values) => write!(
+                f,
+                "{} in [{}]",
+                operand,
+                values
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            // `!` covers the comparison after it, so only `&&` and `||` need brackets
+            Condition::Not(c) => {
+                write!(f, "!")?;
+                c.fmt_operand(f, 2)
+            }
+            // Operands of the same operator are bracketed, so the list reads back as it is
+            Condition::And(operands) => fmt_list(f, operands, " && ", 2),
+            Condition::Or(operands) => fmt_list(f, operands, " || ", 1),
+        }
+    }
+}
+
+fn fmt_list(
+    f: &mut Formatter<'_>,
+    operands: &[Condition],
+    op: &str,
+    precedence: u8,
+) -> fmt::Result {
+    for (idx, operand) in operands.iter().enumerate() {
+        if idx > 0 {
+            write!(f, "{}", op)?;
+        }
+        operand.fmt_operand(f, precedence)?;
+    }
+    Ok(())
+}
+
+fn parse_or(input: ParseStream, depth: usize) -> Result<Condition, syn::Error> {
+    let mut operands = vec![parse_and(input, depth)?];
+    while input.peek(Token![||]) {
+        input.parse::<Token![||]>()?;
+        operands.push(parse_and(input, depth)?);
+    }
+    Ok(match operands.len() {
+        1 => operands.pop().unwrap(),
+        _ => Condition::Or(operands),
+    })
+}
+
+fn parse_and(input: ParseStream, depth: usize) -> Result<Condition, syn::Error> {
+    let mut operands = vec![parse_unary(input, depth)?];
+    while input.peek(Token![&&]) {
+        input.parse::<Token![&&]>()?;
+        operands.push(parse_unary(input, depth)?);
+    }
+    Ok(match operands.len() {
+        1 => operands.pop().unwrap(),
+        _ => Condition::And(operands),
+    })
+}
+
+fn parse_unary(input: ParseStream, depth: usize) -> Result<Condition, syn::Error> {
+    if depth >= MAX_CONDITION_DEPTH {
+        return Err(syn::Error::new(
+            input.span(),
+            "Condition is nested too deeply",
+        ));
+    }
+    if input.peek(Token![!]) {
+        input.parse::<Token![!]>()?;
+        return Ok(Condition::Not(Box::new(parse_unary(input, depth + 1)?)));
+    }
+    if input.peek(Paren) {
+        let content;
+        parenthesized!(content in input);
+        let inner = parse_or(&content, depth + 1)?;
+        if !content.is_empty() {
+            return Err(content.error("Unexpected token in condition"));
+        }
+        return Ok(inner);
+    }
+    let left: Operand = input.parse()?;
+    if input.peek(Token![in]) {
+        input.parse::<Token![in]>()?;
+        let content;
+        bracketed!(content in input);
+        let values: Punctuated<Value, Token![,]> =
+            content.parse_terminated(Value::parse, Token![,])?;
+        return Ok(Condition::In(left, values.into_iter().collect()));
+    }
+    let op = if input.peek(Token![==]) {
+        input.parse::<Token![==]>()?;
+        CmpOp::Eq
+    } else if input.peek(Token![!=]) {
+        input.parse::<Token![!=]>()?;
+        CmpOp::Ne
+    } else if input.peek(Token![<=]) {
+        input.parse::<Token![<=]>()?;
+        CmpOp::Le
+    } else if input.peek(Token![>=]) {
+        input.parse::<Token![>=]>()?;
+        CmpOp::Ge
+    } else if input.peek(Token![<]) {
+        input.parse::<Token![<]>()?;
+        CmpOp::Lt
+    } else if input.peek(Token![>]) {
+        input.parse::<Token![>]>()?;
+        CmpOp::Gt
+    } else {
+        return match left {
+            Operand::Attr(_) => Ok(Condition::Compare(
+                left,
+                CmpOp::Eq,
+                Operand::Literal(Value::Bool(true)),
+            )),
+            Operand::Literal(_) => Err(input.error("Expected a comparison operator")),
+        };
+    };
+    let right: Operand = input.parse()?;
+    Ok(Condition::Compare(left, op, right))
+}
+
+impl Parse for Value {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        if input.peek(LitStr) {
+            return Ok(Value::Str(input.parse::<LitStr>()?.value()));
+        }
+        if input.peek(LitBool) {
+            return Ok(Value::Bool(input.parse::<LitBool>()?.value));
+        }
+        let negative = input.peek(Token![-]);
+        if negative {
+            input.parse::<Token![-]>()?;
+        }
+        let lit: LitInt = input.parse()?;
+        let value: i128 = lit.base10_parse()?;
+        i64::try_from(if negative { -value } else { value })
+            .map(Value::Int)
+            .map_err(|_| syn::Error::new(lit.span(), "Integer out of range for a condition"))
+    }
+}
+
+impl Parse for Operand {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        if input.peek(LitStr) || input.peek(LitBool) || input.peek(LitInt) || input.peek(Token![-])
+        {
+            return Ok(Operand::Literal(input.parse()?));
+        }
+        if input.peek(Token![in]) || input.peek(Bracket) {
+            return Err(input.error("Expected an attribute or a literal"));
+        }
+        let mut key = Ident::parse_any(input)?.to_string();
+        while input.peek(Token![.]) {
+            input.parse::<Token![.]>()?;
+            key.push('.');
+            key.push_str(&Ident::parse_any(input)?.to_string());
+        }
+        Ok(Operand::Attr(key))
+    }
+}
+
+impl Parse for Condition {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        parse_or(input, 0)
+    }
+}
+
+pub fn parse_condition(condition: &str) -> Result<Condition, PermissionParseError> {
+    parse_condition_with_limits(condition, &ParseLimits::default())
+}
+
+pub(crate) fn parse_condition_with_limits(
+    condition: &str,
+    limits: &ParseLimits,
+) -> Result<Condition, PermissionParseError> {
+    check_input(condition, limits).map_err(PermissionParseError::LimitExceeded)?;
+    Ok(syn::parse_str(condition)?)
+}
+
+impl PermissionItem {
+    /// Only grant when `condition` holds for the request context, see `check_with_context`
+    pub fn when(mut self, condition: Condition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+}
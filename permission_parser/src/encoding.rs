@@ -0,0 +1,854 @@
+// Binary layout (all integers are LEB128 varints unless stated otherwise):
+//
+//   [version: u8] [kind: u8] [name count] [name len, utf8 bytes]* [body]
+//
+// `kind` is one of the `KIND_*` constants below and the body is a single item,
+// a group (count followed by items) or an expression tree in prefix order.
+// An item is its field count followed by the fields, each field being a tag
+// byte with an optional payload (name index into the table, ID, specifier).
+// The fields are followed by a `META_*` flags byte and, for each validity bound
+// set, the bound as seconds and nanoseconds after the Unix epoch, then the
+// grant condition if any. Besides names, IDs and specifiers a field is
+// `FIELD_PREFIX` (name index), `FIELD_RANGE` (start and end),
+// `FIELD_ALTERNATIVES` (count followed by the fields), `FIELD_LARGE_ID` or
+// `FIELD_UUID` (16 big-endian bytes), or `FIELD_VAR` naming one of the
+// variables of an enclosing `EXPR_EXISTS` followed by a `VAR_*` type byte.
+// `EXPR_AND`, `EXPR_OR` and `EXPR_XOR` nodes hold the number of operands
+// followed by the operands, `EXPR_PREDICATE` nodes a condition like a grant,
+// `EXPR_AT_LEAST` nodes the count followed by the number of operands and the
+// operands, and `EXPR_EXISTS` nodes the number of variables followed by their
+// name indices and the body.
+//
+// A condition is a `COND_*` tag followed by its operands: `COND_COMPARE` the
+// left operand, a `CmpOp` byte and the right operand, `COND_IN` the operand
+// and the number of values followed by the values, `COND_NOT` the negated
+// condition and `COND_AND` and `COND_OR` the number of conditions followed by
+// the conditions. An operand is `OPERAND_ATTR` with the name index of the
+// attribute or a value, `VALUE_INT` (zigzag varint), `VALUE_STR` (name index),
+// `VALUE_FALSE` or `VALUE_TRUE`.
+
+use crate::{
+    Condition, ItemExpr, Limit, ParseLimits, PermissionGroup, PermissionItem, PermissionParseError,
+    condition::{CmpOp, Operand, Value},
+    tokenizer::{Field, ListSpecifier, Specifier, VarType},
+};
+use proc_macro2::Span;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use syn::Ident;
+
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Maximum nesting of `ItemExpr` accepted by `ItemExpr::decode`
+pub const MAX_DECODE_DEPTH: usize = 256;
+
+const KIND_ITEM: u8 = 0x01;
+const KIND_GROUP: u8 = 0x02;
+const KIND_EXPR: u8 = 0x03;
+
+const FIELD_NAME: u8 = 0x00;
+const FIELD_ID: u8 = 0x01;
+const FIELD_SPECIFIER: u8 = 0x02;
+const FIELD_GLOB: u8 = 0x03;
+const FIELD_DOUBLE_GLOB: u8 = 0x04;
+const FIELD_TRIPLE_GLOB: u8 = 0x05;
+const FIELD_VAR: u8 = 0x06;
+const FIELD_PREFIX: u8 = 0x07;
+const FIELD_RANGE: u8 = 0x08;
+const FIELD_ALTERNATIVES: u8 = 0x09;
+const FIELD_LARGE_ID: u8 = 0x0a;
+const FIELD_UUID: u8 = 0x0b;
+
+const VAR_ANY: u8 = 0x00;
+const VAR_ID: u8 = 0x01;
+const VAR_NAME: u8 = 0x02;
+
+const META_NOT_BEFORE: u8 = 0x01;
+const META_EXPIRES_AT: u8 = 0x02;
+const META_CONDITION: u8 = 0x04;
+
+const EXPR_PERMISSION: u8 = 0x00;
+const EXPR_NOT: u8 = 0x01;
+const EXPR_AND: u8 = 0x02;
+const EXPR_OR: u8 = 0x03;
+const EXPR_XOR: u8 = 0x04;
+const EXPR_BRACKETED: u8 = 0x05;
+const EXPR_PREDICATE: u8 = 0x06;
+const EXPR_AT_LEAST: u8 = 0x07;
+const EXPR_EXISTS: u8 = 0x08;
+
+const COND_COMPARE: u8 = 0x00;
+const COND_IN: u8 = 0x01;
+const COND_NOT: u8 = 0x02;
+const COND_AND: u8 = 0x03;
+const COND_OR: u8 = 0x04;
+
+const OPERAND_ATTR: u8 = 0x00;
+const VALUE_INT: u8 = 0x01;
+const VALUE_STR: u8 = 0x02;
+const VALUE_FALSE: u8 = 0x03;
+const VALUE_TRUE: u8 = 0x04;
+
+#[derive(Debug)]
+pub enum EncodingError {
+    UnexpectedEnd,
+    TrailingBytes(usize),
+    UnsupportedVersion(u8),
+    UnexpectedKind { expected: u8, found: u8 },
+    InvalidTag(u8),
+    VarintOverflow,
+    InvalidNameIndex(u64),
+    InvalidUtf8,
+    InvalidTime,
+    TooDeep,
+    Invalid(PermissionParseError),
+}
+
+impl From<PermissionParseError> for EncodingError {
+    fn from(err: PermissionParseError) -> Self {
+        Self::Invalid(err)
+    }
+}
+
+fn specifier_to_byte(specifier: &Specifier) -> u8 {
+    match specifier {
+        Specifier::Read => 0,
+        Specifier::Write => 1,
+        Specifier::Assign => 2,
+        Specifier::Enact => 3,
+        Specifier::ListSpecifier { specifier } => match specifier {
+            ListSpecifier::Add => 4,
+            ListSpecifier::Remove => 5,
+            ListSpecifier::ReadOne => 6,
+            ListSpecifier::ListAll => 7,
+        },
+    }
+}
+
+fn cmp_op_to_byte(op: CmpOp) -> u8 {
+    match op {
+        CmpOp::Eq => 0,
+        CmpOp::Ne => 1,
+        CmpOp::Lt => 2,
+        CmpOp::Le => 3,
+        CmpOp::Gt => 4,
+        CmpOp::Ge => 5,
+    }
+}
+
+fn byte_to_cmp_op(byte: u8) -> Result<CmpOp, EncodingError> {
+    Ok(match byte {
+        0 => CmpOp::Eq,
+        1 => CmpOp::Ne,
+        2 => CmpOp::Lt,
+        3 => CmpOp::Le,
+        4 => CmpOp::Gt,
+        5 => CmpOp::Ge,
+        other => return Err(EncodingError::InvalidTag(other)),
+    })
+}
+
+// Whether `syn` reads `name` as a single identifier, checked without tokenising it. Keywords are
+// only identifiers where any word is accepted, as for the attributes of a condition.
+fn is_ident(name: &str, keywords: bool) -> bool {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c == '_' || unicode_ident::is_xid_start(c))
+        && chars.all(unicode_ident::is_xid_continue);
+    valid && (keywords || !KEYWORDS.contains(&name))
+}
+
+// The words `syn` does not accept as an `Ident`
+const KEYWORDS: &[&str] = &[
+    "_", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "Self", "self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+fn byte_to_specifier(byte: u8) -> Result<Specifier, EncodingError> {
+    Ok(match byte {
+        0 => Specifier::Read,
+        1 => Specifier::Write,
+        2 => Specifier::Assign,
+        3 => Specifier::Enact,
+        4 => ListSpecifier::Add.into(),
+        5 => ListSpecifier::Remove.into(),
+        6 => ListSpecifier::ReadOne.into(),
+        7 => ListSpecifier::ListAll.into(),
+        other => return Err(EncodingError::InvalidTag(other)),
+    })
+}
+
+#[derive(Default)]
+struct Encoder {
+    names: Vec<String>,
+    name_index: HashMap<String, u64>,
+    body: Vec<u8>,
+    // Variables of the enclosing `exists`
+    scope: Vec<String>,
+}
+
+impl Encoder {
+    fn intern(&mut self, name: &str) -> u64 {
+        if let Some(idx) = self.name_index.get(name) {
+            return *idx;
+        }
+        let idx = self.names.len() as u64;
+        self.names.push(name.to_string());
+        self.name_index.insert(name.to_string(), idx);
+        idx
+    }
+
+    fn item(&mut self, item: &PermissionItem) -> Result<(), EncodingError> {
+        validate_in_scope(item, &self.scope)?;
+        write_varint(&mut self.body, item.perm.len() as u64);
+        for field in &item.perm {
+            self.field(field);
+        }
+        let bounds = [
+            (META_NOT_BEFORE, item.validity.not_before),
+            (META_EXPIRES_AT, item.validity.expires_at),
+        ];
+        let mut flags = bounds
+            .iter()
+            .filter(|(_, time)| time.is_some())
+            .fold(0, |flags, (flag, _)| flags | flag);
+        if item.condition.is_some() {
+            flags |= META_CONDITION;
+        }
+        self.body.push(flags);
+        for time in bounds.iter().filter_map(|(_, time)| *time) {
+            let since_epoch = time
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| EncodingError::InvalidTime)?;
+            write_varint(&mut self.body, since_epoch.as_secs());
+            write_varint(&mut self.body, since_epoch.subsec_nanos() as u64);
+        }
+        if let Some(condition) = &item.condition {
+            self.condition(condition, 1)?;
+        }
+        Ok(())
+    }
+
+    // `depth` is that of the condition's root, as counted by the decoder
+    fn condition(&mut self, condition: &Condition, depth: usize) -> Result<(), EncodingError> {
+        if depth > MAX_DECODE_DEPTH {
+            return Err(EncodingError::TooDeep);
+        }
+        match condition {
+            Condition::Compare(l, op, r) => {
+                self.body.push(COND_COMPARE);
+                self.operand(l);
+                self.body.push(cmp_op_to_byte(*op));
+                self.operand(r);
+            }
+            Condition::In(operand, values) => {
+                self.body.push(COND_IN);
+                self.operand(operand);
+                write_varint(&mut self.body, values.len() as u64);
+                values.iter().for_each(|value| self.value(value));
+            }
+            Condition::Not(c) => {
+                self.body.push(COND_NOT);
+                self.condition(c, depth + 1)?;
+            }
+            Condition::And(operands) | Condition::Or(operands) => {
+                self.body.push(match condition {
+                    Condition::And(_) => COND_AND,
+                    _ => COND_OR,
+                });
+                write_varint(&mut self.body, operands.len() as u64);
+                for operand in operands {
+                    self.condition(operand, depth + 1)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn operand(&mut self, operand: &Operand) {
+        match operand {
+            Operand::Attr(key) => {
+                let idx = self.intern(key);
+                self.body.push(OPERAND_ATTR);
+                write_varint(&mut self.body, idx);
+            }
+            Operand::Literal(value) => self.value(value),
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Int(i) => {
+                self.body.push(VALUE_INT);
+                write_varint(&mut self.body, ((i << 1) ^ (i >> 63)) as u64);
+            }
+            Value::Str(string) => {
+                let idx = self.intern(string);
+                self.body.push(VALUE_STR);
+                write_varint(&mut self.body, idx);
+            }
+            Value::Bool(false) => self.body.push(VALUE_FALSE),
+            Value::Bool(true) => self.body.push(VALUE_TRUE),
+        }
+    }
+
+    fn field(&mut self, field: &Field) {
+        match field {
+            Field::Name { name } => {
+                let idx = self.intern(name);
+                self.body.push(FIELD_NAME);
+                write_varint(&mut self.body, idx);
+            }
+            Field::ID { id } => {
+                self.body.push(FIELD_ID);
+                write_varint(&mut self.body, *id);
+            }
+            Field::Specifier { specifier } => {
+                self.body.push(FIELD_SPECIFIER);
+                self.body.push(specifier_to_byte(specifier));
+            }
+            Field::Glob => self.body.push(FIELD_GLOB),
+            Field::DoubleGlob => self.body.push(FIELD_DOUBLE_GLOB),
+            Field::TripleGlob => self.body.push(FIELD_TRIPLE_GLOB),
+            Field::VarKind(_, ident, ty) => {
+                let idx = self.intern(&ident.to_string());
+                self.body.push(FIELD_VAR);
+                write_varint(&mut self.body, idx);
+                self.body.push(match ty {
+                    VarType::Any => VAR_ANY,
+                    VarType::Id => VAR_ID,
+                    VarType::Name => VAR_NAME,
+                });
+            }
+            Field::Prefix { prefix } => {
+                let idx = self.intern(prefix);
+                self.body.push(FIELD_PREFIX);
+                write_varint(&mut self.body, idx);
+            }
+            Field::Range { start, end } => {
+                self.body.push(FIELD_RANGE);
+                write_varint(&mut self.body, *start);
+                write_varint(&mut self.body, *end);
+            }
+            Field::Alternatives { fields } => {
+                self.body.push(FIELD_ALTERNATIVES);
+                write_varint(&mut self.body, fields.len() as u64);
+                fields.iter().for_each(|field| self.field(field));
+            }
+            Field::LargeID { id } => {
+                self.body.push(FIELD_LARGE_ID);
+                self.body.extend(id.to_be_bytes());
+            }
+            Field::Uuid { uuid } => {
+                self.body.push(FIELD_UUID);
+                self.body.extend(uuid.to_be_bytes());
+            }
+        }
+    }
+
+    fn chain(&mut self, tag: u8, operands: &[ItemExpr], depth: usize) -> Result<(), EncodingError> {
+        self.body.push(tag);
+        write_varint(&mut self.body, operands.len() as u64);
+        for operand in operands {
+            self.expr(operand, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    // Fails where the decoder would, so whatever is encoded decodes again
+    fn expr(&mut self, expr: &ItemExpr, depth: usize) -> Result<(), EncodingError> {
+        if depth > MAX_DECODE_DEPTH {
+            return Err(EncodingError::TooDeep);
+        }
+        match expr {
+            ItemExpr::Permission(p) => {
+                self.body.push(EXPR_PERMISSION);
+                self.item(p)?;
+            }
+            ItemExpr::Not(e) => {
+                self.body.push(EXPR_NOT);
+                self.expr(e, depth + 1)?;
+            }
+            ItemExpr::And(operands) => self.chain(EXPR_AND, operands, depth)?,
+            ItemExpr::Or(operands) => self.chain(EXPR_OR, operands, depth)?,
+            ItemExpr::Xor(operands) => self.chain(EXPR_XOR, operands, depth)?,
+            ItemExpr::Bracketed(b) => {
+                self.body.push(EXPR_BRACKETED);
+                self.expr(b, depth + 1)?;
+            }
+            ItemExpr::Predicate(condition) => {
+                self.body.push(EXPR_PREDICATE);
+                self.condition(condition, depth + 1)?;
+            }
+            ItemExpr::AtLeast(count, operands) => {
+                self.body.push(EXPR_AT_LEAST);
+                write_varint(&mut self.body, *count as u64);
+                write_varint(&mut self.body, operands.len() as u64);
+                for operand in operands {
+                    self.expr(operand, depth + 1)?;
+                }
+            }
+            ItemExpr::Exists(vars, body) => {
+                self.body.push(EXPR_EXISTS);
+                write_varint(&mut self.body, vars.len() as u64);
+                for var in vars {
+                    let idx = self.intern(var);
+                    write_varint(&mut self.body, idx);
+                }
+                let outer = self.scope.len();
+                self.scope.extend(vars.iter().cloned());
+                check_exists_vars(self.scope.len())?;
+                let result = self.expr(body, depth + 1);
+                self.scope.truncate(outer);
+                result?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self, kind: u8) -> Vec<u8> {
+        let mut output = vec![FORMAT_VERSION, kind];
+        write_varint(&mut output, self.names.len() as u64);
+        for name in &self.names {
+            write_varint(&mut output, name.len() as u64);
+            output.extend_from_slice(name.as_bytes());
+        }
+        output.extend_from_slice(&self.body);
+        output
+    }
+}
+
+// Variables of an enclosing `exists` stand for a name or an ID, anything else is unresolved
+// Checking tries every combination of the values of the placeholders in scope, so their number is
+// bounded like when parsing
+fn check_exists_vars(scope: usize) -> Result<(), PermissionParseError> {
+    let max = ParseLimits::default().max_exists_vars;
+    match scope > max {
+        true => Err(PermissionParseError::LimitExceeded(Limit::ExistsVars(max))),
+        false => Ok(()),
+    }
+}
+
+fn validate_in_scope(item: &PermissionItem, scope: &[String]) -> Result<(), PermissionParseError> {
+    let perm = item
+        .perm
+        .iter()
+        .map(|field| match field {
+            Field::VarKind(_, ident, _) if scope.contains(&ident.to_string()) => Field::Name {
+                name: ident.to_string(),
+            },
+            field => field.clone(),
+        })
+        .collect::<Vec<Field>>();
+    PermissionItem::from(perm).validate()
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    pos: usize,
+    names: Vec<String>,
+    // Variables of the enclosing `exists`
+    scope: Vec<String>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(input: &'a [u8], kind: u8) -> Result<Self, EncodingError> {
+        let mut decoder = Self {
+            input,
+            pos: 0,
+            names: vec![],
+            scope: vec![],
+        };
+        let version = decoder.byte()?;
+        if version != FORMAT_VERSION {
+            return Err(EncodingError::UnsupportedVersion(version));
+        }
+        let found = decoder.byte()?;
+        if found != kind {
+            return Err(EncodingError::UnexpectedKind {
+                expected: kind,
+                found,
+            });
+        }
+        let count = decoder.length()?;
+        for _ in 0..count {
+            let len = decoder.length()?;
+            let bytes = decoder.take(len)?;
+            let name = std::str::from_utf8(bytes).map_err(|_| EncodingError::InvalidUtf8)?;
+            decoder.names.push(name.to_string());
+        }
+        Ok(decoder)
+    }
+
+    fn byte(&mut self) -> Result<u8, EncodingError> {
+        let byte = *self
+            .input
+            .get(self.pos)
+            .ok_or(EncodingError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], EncodingError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .ok_or(EncodingError::UnexpectedEnd)?;
+        let bytes = self
+            .input
+            .get(self.pos..end)
+            .ok_or(EncodingError::UnexpectedEnd)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, EncodingError> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let part = (byte & 0x7f) as u64;
+            if shift == 63 && part > 1 {
+                return Err(EncodingError::VarintOverflow);
+            }
+            value |= part << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(EncodingError::VarintOverflow)
+    }
+
+    // Every counted element takes at least one byte, so a length larger than
+    // the remaining input can be rejected before allocating anything for it
+    fn length(&mut self) -> Result<usize, EncodingError> {
+        let value = self.varint()?;
+        if value > (self.input.len() - self.pos) as u64 {
+            return Err(EncodingError::UnexpectedEnd);
+        }
+        Ok(value as usize)
+    }
+
+    fn item(&mut self) -> Result<PermissionItem, EncodingError> {
+        let count = self.length()?;
+        let mut perm = Vec::with_capacity(count);
+        for _ in 0..count {
+            perm.push(self.field(true)?);
+        }
+        let mut item: PermissionItem = perm.into();
+        validate_in_scope(&item, &self.scope)?;
+        let flags = self.byte()?;
+        if flags & !(META_NOT_BEFORE | META_EXPIRES_AT | META_CONDITION) != 0 {
+            return Err(EncodingError::InvalidTag(flags));
+        }
+        if flags & META_NOT_BEFORE != 0 {
+            item.validity.not_before = Some(self.time()?);
+        }
+        if flags & META_EXPIRES_AT != 0 {
+            item.validity.expires_at = Some(self.time()?);
+        }
+        if flags & META_CONDITION != 0 {
+            item.condition = Some(self.condition(1)?);
+        }
+        Ok(item)
+    }
+
+    // Alternatives only hold single fields, so they are not decoded inside one another
+    fn field(&mut self, alternatives: bool) -> Result<Field, EncodingError> {
+        Ok(match self.byte()? {
+            FIELD_NAME => Field::Name { name: self.name()? },
+            FIELD_ID => Field::ID { id: self.varint()? },
+            FIELD_SPECIFIER => byte_to_specifier(self.byte()?)?.into(),
+            FIELD_GLOB => Field::Glob,
+            FIELD_DOUBLE_GLOB => Field::DoubleGlob,
+            FIELD_TRIPLE_GLOB => Field::TripleGlob,
+            FIELD_VAR => {
+                let name = self.name()?;
+                let ident = match name.strip_prefix("r#") {
+                    Some(raw)
+                        if is_ident(raw, true)
+                            && !matches!(raw, "crate" | "self" | "Self" | "super") =>
+                    {
+                        Ident::new_raw(raw, Span::call_site())
+                    }
+                    None if is_ident(&name, false) => Ident::new(&name, Span::call_site()),
+                    _ => {
+                        return Err(PermissionParseError::InvalidOutput(format!(
+                            "Invalid variable `{}`",
+                            name
+                        ))
+                        .into());
+                    }
+                };
+                let ty = match self.byte()? {
+                    VAR_ANY => VarType::Any,
+                    VAR_ID => VarType::Id,
+                    VAR_NAME => VarType::Name,
+                    other => return Err(EncodingError::InvalidTag(other)),
+                };
+                Field::VarKind(Span::call_site(), ident, ty)
+            }
+            FIELD_PREFIX => Field::Prefix {
+                prefix: self.name()?,
+            },
+            FIELD_RANGE => Field::Range {
+                start: self.varint()?,
+                end: self.varint()?,
+            },
+            FIELD_ALTERNATIVES if alternatives => {
+                let count = self.length()?;
+                Field::Alternatives {
+                    fields: (0..count)
+                        .map(|_| self.field(false))
+                        .collect::<Result<Vec<Field>, EncodingError>>()?,
+                }
+            }
+            FIELD_LARGE_ID => Field::LargeID { id: self.u128()? },
+            FIELD_UUID => Field::Uuid { uuid: self.u128()? },
+            other => return Err(EncodingError::InvalidTag(other)),
+        })
+    }
+
+    fn u128(&mut self) -> Result<u128, EncodingError> {
+        let bytes = self.take(16)?;
+        Ok(u128::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn condition(&mut self, depth: usize) -> Result<Condition, EncodingError> {
+        if depth > MAX_DECODE_DEPTH {
+            return Err(EncodingError::TooDeep);
+        }
+        Ok(match self.byte()? {
+            COND_COMPARE => {
+                let left = self.operand()?;
+                let op = byte_to_cmp_op(self.byte()?)?;
+                Condition::Compare(left, op, self.operand()?)
+            }
+            COND_IN => {
+                let operand = self.operand()?;
+                let count = self.length()?;
+                let values = (0..count)
+                    .map(|_| {
+                        let tag = self.byte()?;
+                        self.value(tag)
+                    })
+                    .collect::<Result<Vec<Value>, EncodingError>>()?;
+                Condition::In(operand, values)
+            }
+            COND_NOT => Condition::Not(Box::new(self.condition(depth + 1)?)),
+            tag @ (COND_AND | COND_OR) => {
+                let count = self.length()?;
+                if count < 2 {
+                    return Err(PermissionParseError::InvalidOutput(
+                        "An operator needs at least two operands".to_string(),
+                    )
+                    .into());
+                }
+                let operands = (0..count)
+                    .map(|_| self.condition(depth + 1))
+                    .collect::<Result<Vec<Condition>, EncodingError>>()?;
+                match tag {
+                    COND_AND => Condition::And(operands),
+                    _ => Condition::Or(operands),
+                }
+            }
+            other => return Err(EncodingError::InvalidTag(other)),
+        })
+    }
+
+    // An attribute is displayed as it is, so it must read back as one, e.g. `request.ip` but not
+    // `true`
+    fn operand(&mut self) -> Result<Operand, EncodingError> {
+        match self.byte()? {
+            OPERAND_ATTR => {
+                let key = self.name()?;
+                let literal = key
+                    .split('.')
+                    .next()
+                    .is_some_and(|first| matches!(first, "true" | "false" | "in"));
+                if literal
+                    || !key.split('.').all(|segment| {
+                        is_ident(segment.strip_prefix("r#").unwrap_or(segment), true)
+                    })
+                {
+                    return Err(PermissionParseError::InvalidOutput(format!(
+                        "Invalid attribute `{}`",
+                        key
+                    ))
+                    .into());
+                }
+                Ok(Operand::Attr(key))
+            }
+            tag => Ok(Operand::Literal(self.value(tag)?)),
+        }
+    }
+
+    fn value(&mut self, tag: u8) -> Result<Value, EncodingError> {
+        Ok(match tag {
+            VALUE_INT => {
+                let zigzag = self.varint()?;
+                Value::Int((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64))
+            }
+            VALUE_STR => Value::Str(self.name()?),
+            VALUE_FALSE => Value::Bool(false),
+            VALUE_TRUE => Value::Bool(true),
+            other => return Err(EncodingError::InvalidTag(other)),
+        })
+    }
+
+    fn name(&mut self) -> Result<String, EncodingError> {
+        let idx = self.varint()?;
+        self.names
+            .get(idx as usize)
+            .cloned()
+            .ok_or(EncodingError::InvalidNameIndex(idx))
+    }
+
+    fn time(&mut self) -> Result<SystemTime, EncodingError> {
+        let secs = self.varint()?;
+        let nanos = self.varint()?;
+        if nanos >= 1_000_000_000 {
+            return Err(EncodingError::InvalidTime);
+        }
+        UNIX_EPOCH
+            .checked_add(Duration::new(secs, nanos as u32))
+            .ok_or(EncodingError::InvalidTime)
+    }
+
+    fn expr(&mut self, depth: usize) -> Result<ItemExpr, EncodingError> {
+        if depth > MAX_DECODE_DEPTH {
+            return Err(EncodingError::TooDeep);
+        }
+        Ok(match self.byte()? {
+            EXPR_PERMISSION => ItemExpr::Permission(self.item()?),
+            EXPR_NOT => ItemExpr::Not(Box::new(self.expr(depth + 1)?)),
+            EXPR_AND => ItemExpr::And(self.chain(depth)?),
+            EXPR_OR => ItemExpr::Or(self.chain(depth)?),
+            EXPR_XOR => ItemExpr::Xor(self.chain(depth)?),
+            EXPR_BRACKETED => ItemExpr::Bracketed(Box::new(self.expr(depth + 1)?)),
+            EXPR_PREDICATE => ItemExpr::Predicate(self.condition(depth + 1)?),
+            EXPR_AT_LEAST => {
+                let count = self.varint()?;
+                let len = self.length()?;
+                // `any()` and `all()` are the only quantifiers without operands
+                let constant = len == 0 && count <= 1;
+                if !constant && (count == 0 || count > len as u64) {
+                    return Err(PermissionParseError::InvalidOutput(format!(
+                        "atleast needs a count from 1 to {}",
+                        len
+                    ))
+                    .into());
+                }
+                let operands = (0..len)
+                    .map(|_| self.expr(depth + 1))
+                    .collect::<Result<Vec<ItemExpr>, EncodingError>>()?;
+                ItemExpr::AtLeast(count as usize, operands)
+            }
+            EXPR_EXISTS => self.exists(depth)?,
+            other => return Err(EncodingError::InvalidTag(other)),
+        })
+    }
+
+    fn chain(&mut self, depth: usize) -> Result<Vec<ItemExpr>, EncodingError> {
+        let len = self.length()?;
+        if len < 2 {
+            return Err(PermissionParseError::InvalidOutput(
+                "An operator needs at least two operands".to_string(),
+            )
+            .into());
+        }
+        (0..len).map(|_| self.expr(depth + 1)).collect()
+    }
+
+    fn exists(&mut self, depth: usize) -> Result<ItemExpr, EncodingError> {
+        let count = self.length()?;
+        if count == 0 {
+            return Err(PermissionParseError::InvalidOutput(
+                "exists needs at least one variable".to_string(),
+            )
+            .into());
+        }
+        let vars = (0..count)
+            .map(|_| self.name())
+            .collect::<Result<Vec<String>, EncodingError>>()?;
+        let outer = self.scope.len();
+        self.scope.extend(vars.iter().cloned());
+        check_exists_vars(self.scope.len())?;
+        let body = self.expr(depth + 1);
+        self.scope.truncate(outer);
+        Ok(ItemExpr::Exists(vars, Box::new(body?)))
+    }
+
+    fn finish<T>(self, value: T) -> Result<T, EncodingError> {
+        if self.pos != self.input.len() {
+            return Err(EncodingError::TrailingBytes(self.input.len() - self.pos));
+        }
+        Ok(value)
+    }
+}
+
+impl PermissionItem {
+    pub fn encode(&self) -> Result<Vec<u8>, EncodingError> {
+        let mut encoder = Encoder::default();
+        encoder.item(self)?;
+        Ok(encoder.finish(KIND_ITEM))
+    }
+
+    pub fn decode(input: &[u8]) -> Result<Self, EncodingError> {
+        let mut decoder = Decoder::new(input, KIND_ITEM)?;
+        let item = decoder.item()?;
+        decoder.finish(item)
+    }
+}
+
+impl PermissionGroup {
+    pub fn encode(&self) -> Result<Vec<u8>, EncodingError> {
+        let mut encoder = Encoder::default();
+        write_varint(&mut encoder.body, self.perms.len() as u64);
+        for item in &self.perms {
+            encoder.item(item)?;
+        }
+        Ok(encoder.finish(KIND_GROUP))
+    }
+
+    pub fn decode(input: &[u8]) -> Result<Self, EncodingError> {
+        let mut decoder = Decoder::new(input, KIND_GROUP)?;
+        let count = decoder.length()?;
+        let mut perms = Vec::with_capacity(count);
+        for _ in 0..count {
+            perms.push(decoder.item()?);
+        }
+        decoder.finish(perms.into())
+    }
+}
+
+impl ItemExpr {
+    pub fn encode(&self) -> Result<Vec<u8>, EncodingError> {
+        let mut encoder = Encoder::default();
+        encoder.expr(self, 1)?;
+        Ok(encoder.finish(KIND_EXPR))
+    }
+
+    pub fn decode(input: &[u8]) -> Result<Self, EncodingError> {
+        let mut decoder = Decoder::new(input, KIND_EXPR)?;
+        let expr = decoder.expr(0)?;
+        decoder.finish(expr)
+    }
+}
@@ -1,86 +1,528 @@
-use crate::{Permission, Permissions};
+use crate::{
+    Permission, Permissions,
+    condition::Condition,
+    limits::{Limit, ParseLimits},
+};
+use proc_macro2::Span;
 use syn::{
-    parenthesized,
+    Ident, LitInt, Token, parenthesized,
     parse::{Parse, ParseStream},
     token::Paren,
 };
 
+// `ctx(...)`, as opposed to a permission named `ctx`
+fn is_predicate(input: ParseStream) -> bool {
+    input.peek2(Paren)
+        && input
+            .fork()
+            .parse::<Ident>()
+            .is_ok_and(|ident| ident == "ctx")
+}
+
+// `any(...)`, `all(...)` or `atleast(...)`
+fn is_quantifier(input: ParseStream) -> bool {
+    input.peek2(Paren)
+        && input
+            .fork()
+            .parse::<Ident>()
+            .is_ok_and(|ident| ident == "any" || ident == "all" || ident == "atleast")
+}
+
 #[derive(Clone)]
 pub enum Expr {
     Permission(Permissions),
+    /// `ctx(<condition>)`, a fact about the request context
+    Predicate(Condition),
     Not(Box<Expr>),
-    And(Box<Expr>, Box<Expr>),
-    Or(Box<Expr>, Box<Expr>),
-    Xor(Box<Expr>, Box<Expr>),
+    /// A chain of the same operator, e.g. `a & b & c`, with at least two operands
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Vec<Expr>),
     Bracketed(Box<Expr>),
+    /// `any(...)`, `all(...)` or `atleast(k, ...)`, true when at least this many operands are
+    AtLeast(usize, Vec<Expr>),
+    /// `name(arg, ...)`, a named expression with a single field for each parameter
+    Call(Ident, Vec<Permissions>),
+    /// `exists u, v: <expr>`, true when some values of the `{u}` and `{v}` placeholders make
+    /// the expression true
+    Exists(Vec<Ident>, Box<Expr>),
 }
 
-impl Parse for Expr {
+impl Expr {
+    /// The names of the named expressions this expression calls
+    pub fn calls(&self) -> Vec<&Ident> {
+        let mut calls = vec![];
+        let mut pending = vec![self];
+        while let Some(expr) = pending.pop() {
+            match expr {
+                Expr::Permission(_) | Expr::Predicate(_) => {}
+                Expr::Not(e) | Expr::Bracketed(e) | Expr::Exists(_, e) => pending.push(e),
+                Expr::And(operands)
+                | Expr::Or(operands)
+                | Expr::Xor(operands)
+                | Expr::AtLeast(_, operands) => pending.extend(operands.iter().rev()),
+                Expr::Call(name, _) => calls.push(name),
+            }
+        }
+        calls
+    }
+
+    /// The permissions of this expression in order, not counting the arguments of calls
+    pub fn permissions(&self) -> Vec<&Permissions> {
+        let mut permissions = vec![];
+        let mut pending = vec![self];
+        while let Some(expr) = pending.pop() {
+            match expr {
+                Expr::Permission(p) => permissions.push(p),
+                Expr::Predicate(_) | Expr::Call(_, _) => {}
+                Expr::Not(e) | Expr::Bracketed(e) | Expr::Exists(_, e) => pending.push(e),
+                Expr::And(operands)
+                | Expr::Or(operands)
+                | Expr::Xor(operands)
+                | Expr::AtLeast(_, operands) => pending.extend(operands.iter().rev()),
+            }
+        }
+        permissions
+    }
+}
+
+/// `let name(param, ...) = <expr>;`, a named expression whose body uses its parameters as
+/// `{param}` placeholders
+#[derive(Clone)]
+pub struct ExprDefinition {
+    pub name: Ident,
+    pub params: Vec<Ident>,
+    pub body: Expr,
+}
+
+impl Parse for ExprDefinition {
     fn parse(input: ParseStream) -> Result<Self, syn::Error> {
-        if input.peek(Paren) {
-            let content;
-            parenthesized!(content in input);
-            let parsed: Expr = content.parse()?;
-            let expr = Box::new(parsed);
-            if input.peek(syn::Token![&]) || input.peek(syn::Token![&&]) {
-                if input.peek(syn::Token![&&]) {
-                    input.parse::<syn::Token![&&]>()?;
-                } else {
-                    input.parse::<syn::Token![&]>()?;
+        parse_definition(input, &mut Limiter::new(ParseLimits::default()))
+    }
+}
+
+pub(crate) fn parse_definition(
+    input: ParseStream,
+    limiter: &mut Limiter,
+) -> Result<ExprDefinition, syn::Error> {
+    input.parse::<Token![let]>()?;
+    let name: Ident = input.parse()?;
+    let content;
+    parenthesized!(content in input);
+    let params = content.parse_terminated(Ident::parse, Token![,])?;
+    input.parse::<Token![=]>()?;
+    let (body, _) = parse_expr(input, limiter, 1)?;
+    input.parse::<Token![;]>()?;
+    Ok(ExprDefinition {
+        name,
+        params: params.into_iter().collect(),
+        body,
+    })
+}
+
+/// Orders the definitions so each comes after those it calls, calls to names which are not
+/// defined here are left alone. Fails with the names along a cycle of calls.
+pub fn definition_order(definitions: &[ExprDefinition]) -> Result<Vec<usize>, Vec<Ident>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        New,
+        Visiting,
+        Done,
+    }
+    let index = |name: &Ident| definitions.iter().position(|d| d.name == *name);
+    let mut state = vec![State::New; definitions.len()];
+    let mut order = vec![];
+    for root in 0..definitions.len() {
+        // Each frame is a definition with the calls left to visit
+        let mut stack: Vec<(usize, Vec<usize>)> = vec![];
+        if state[root] == State::New {
+            state[root] = State::Visiting;
+            stack.push((root, callees(&definitions[root], index)));
+        }
+        while let Some((current, pending)) = stack.last_mut() {
+            let current = *current;
+            match pending.pop() {
+                Some(next) if state[next] == State::Visiting => {
+                    let start = stack.iter().position(|(idx, _)| *idx == next).unwrap();
+                    let mut cycle: Vec<Ident> = stack[start..]
+                        .iter()
+                        .map(|(idx, _)| definitions[*idx].name.clone())
+                        .collect();
+                    cycle.push(definitions[next].name.clone());
+                    return Err(cycle);
                 }
-                let right: Expr = input.parse()?;
-                return Ok(Self::And(expr, Box::new(right)));
-            } else if input.peek(syn::Token![|]) || input.peek(syn::Token![||]) {
-                if input.peek(syn::Token![||]) {
-                    input.parse::<syn::Token![||]>()?;
-                } else {
-                    input.parse::<syn::Token![|]>()?;
+                Some(next) if state[next] == State::New => {
+                    state[next] = State::Visiting;
+                    stack.push((next, callees(&definitions[next], index)));
                 }
-                let right: Expr = input.parse()?;
-                return Ok(Self::Or(expr, Box::new(right)));
-            } else if input.peek(syn::Token![^]) {
-                input.parse::<syn::Token![^]>()?;
-                let right: Expr = input.parse()?;
-                return Ok(Self::Xor(expr, Box::new(right)));
-            }
-            return Ok(Self::Bracketed(expr));
-        }
-        if input.peek(syn::Token![!]) {
-            input.parse::<syn::Token![!]>()?;
-            let expr: Expr = input.parse()?;
-            return Ok(Self::Not(Box::new(expr)));
-        }
-        if input.fork().parse::<Permissions>().is_ok() {
-            let parsed: Permissions = input.parse()?;
-            let expr: Box<Self> = Box::new(Self::Permission(parsed));
-            if input.peek(syn::Token![&]) || input.peek(syn::Token![&&]) {
-                if input.peek(syn::Token![&&]) {
-                    input.parse::<syn::Token![&&]>()?;
-                } else {
-                    input.parse::<syn::Token![&]>()?;
+                Some(_) => {}
+                None => {
+                    state[current] = State::Done;
+                    order.push(current);
+                    stack.pop();
                 }
-                let right: Expr = input.parse()?;
-                return Ok(Self::And(expr, Box::new(right)));
-            } else if input.peek(syn::Token![|]) || input.peek(syn::Token![||]) {
-                if input.peek(syn::Token![||]) {
-                    input.parse::<syn::Token![||]>()?;
-                } else {
-                    input.parse::<syn::Token![|]>()?;
+            }
+        }
+    }
+    Ok(order)
+}
+
+fn callees(definition: &ExprDefinition, index: impl Fn(&Ident) -> Option<usize>) -> Vec<usize> {
+    let mut callees: Vec<usize> = definition
+        .body
+        .calls()
+        .into_iter()
+        .filter_map(index)
+        .collect();
+    // Visited from the back, so the first call is visited first
+    callees.reverse();
+    callees
+}
+
+impl Parse for Expr {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let mut limiter = Limiter::new(ParseLimits::default());
+        Ok(parse_expr(input, &mut limiter, 1)?.0)
+    }
+}
+
+// Counts towards the limits while parsing, keeping the limit which failed the parse
+pub(crate) struct Limiter {
+    limits: ParseLimits,
+    leaves: usize,
+    // Placeholders quantified by the `exists` around the current position
+    exists_vars: usize,
+    pub(crate) exceeded: Option<Limit>,
+}
+
+impl Limiter {
+    pub(crate) fn new(limits: ParseLimits) -> Self {
+        Self {
+            limits,
+            leaves: 0,
+            exists_vars: 0,
+            exceeded: None,
+        }
+    }
+
+    fn exceed(&mut self, limit: Limit, span: Span) -> syn::Error {
+        self.exceeded = Some(limit);
+        syn::Error::new(span, limit.message())
+    }
+
+    fn depth(&mut self, depth: usize, span: Span) -> Result<(), syn::Error> {
+        match depth > self.limits.max_depth {
+            true => Err(self.exceed(Limit::Depth(self.limits.max_depth), span)),
+            false => Ok(()),
+        }
+    }
+
+    fn leaf(&mut self, span: Span) -> Result<(), syn::Error> {
+        self.leaves += 1;
+        match self.leaves > self.limits.max_leaves {
+            true => Err(self.exceed(Limit::Leaves(self.limits.max_leaves), span)),
+            false => Ok(()),
+        }
+    }
+
+    fn exists(&mut self, count: usize, span: Span) -> Result<(), syn::Error> {
+        self.exists_vars += count;
+        match self.exists_vars > self.limits.max_exists_vars {
+            true => Err(self.exceed(Limit::ExistsVars(self.limits.max_exists_vars), span)),
+            false => Ok(()),
+        }
+    }
+
+    pub(crate) fn fields(&mut self, count: usize, span: Span) -> Result<(), syn::Error> {
+        match count > self.limits.max_fields {
+            true => Err(self.exceed(Limit::Fields(self.limits.max_fields), span)),
+            false => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    And,
+    Or,
+    Xor,
+}
+
+impl BinaryOp {
+    fn build(self, operands: Vec<Expr>) -> Expr {
+        match self {
+            BinaryOp::And => Expr::And(operands),
+            BinaryOp::Or => Expr::Or(operands),
+            BinaryOp::Xor => Expr::Xor(operands),
+        }
+    }
+}
+
+// `!` or `exists u, v:`, both apply to everything after them
+enum Prefix {
+    Not,
+    Exists(Vec<Ident>),
+}
+
+fn parse_prefix(input: ParseStream) -> Result<Option<Prefix>, syn::Error> {
+    if input.peek(Token![!]) {
+        input.parse::<Token![!]>()?;
+        return Ok(Some(Prefix::Not));
+    }
+    let is_exists = input.peek2(Ident)
+        && input
+            .fork()
+            .parse::<Ident>()
+            .is_ok_and(|ident| ident == "exists");
+    if !is_exists {
+        return Ok(None);
+    }
+    input.parse::<Ident>()?;
+    let mut vars = vec![input.parse::<Ident>()?];
+    while input.peek(Token![,]) {
+        input.parse::<Token![,]>()?;
+        vars.push(input.parse()?);
+    }
+    input.parse::<Token![:]>()?;
+    Ok(Some(Prefix::Exists(vars)))
+}
+
+fn apply_prefixes(prefixes: Vec<Prefix>, mut expr: Expr) -> Expr {
+    for prefix in prefixes.into_iter().rev() {
+        expr = match prefix {
+            Prefix::Not => Expr::Not(Box::new(expr)),
+            Prefix::Exists(vars) => Expr::Exists(vars, Box::new(expr)),
+        };
+    }
+    expr
+}
+
+// The operator following an operand, if any
+fn parse_op(input: ParseStream) -> Result<Option<BinaryOp>, syn::Error> {
+    if input.peek(syn::Token![&&]) {
+        input.parse::<syn::Token![&&]>()?;
+        return Ok(Some(BinaryOp::And));
+    }
+    if input.peek(syn::Token![&]) {
+        input.parse::<syn::Token![&]>()?;
+        return Ok(Some(BinaryOp::And));
+    }
+    if input.peek(syn::Token![||]) {
+        input.parse::<syn::Token![||]>()?;
+        return Ok(Some(BinaryOp::Or));
+    }
+    if input.peek(syn::Token![|]) {
+        input.parse::<syn::Token![|]>()?;
+        return Ok(Some(BinaryOp::Or));
+    }
+    if input.peek(syn::Token![^]) {
+        input.parse::<syn::Token![^]>()?;
+        return Ok(Some(BinaryOp::Xor));
+    }
+    Ok(None)
+}
+
+// A bracketed expression, quantifier, predicate or permission at tree depth `depth`, with its
+// height
+fn parse_operand(
+    input: ParseStream,
+    limiter: &mut Limiter,
+    depth: usize,
+) -> Result<(Expr, usize), syn::Error> {
+    limiter.depth(depth, input.span())?;
+    if is_quantifier(input) {
+        return parse_quantifier(input, limiter, depth);
+    }
+    if !input.peek(Paren) {
+        return parse_leaf(input, limiter, depth);
+    }
+    let content;
+    parenthesized!(content in input);
+    let (inner, height) = parse_expr(&content, limiter, depth + 1)?;
+    if !content.is_empty() {
+        return Err(content.error("Unexpected token in brackets"));
+    }
+    Ok((Expr::Bracketed(Box::new(inner)), height + 1))
+}
+
+// The operands are comma separated expressions one level below the quantifier, `atleast` takes
+// the count first. Without operands `any()` is false and `all()` true.
+fn parse_quantifier(
+    input: ParseStream,
+    limiter: &mut Limiter,
+    depth: usize,
+) -> Result<(Expr, usize), syn::Error> {
+    let name: Ident = input.parse()?;
+    let content;
+    parenthesized!(content in input);
+    let count = match name == "atleast" {
+        true => {
+            let count: LitInt = content.parse()?;
+            content.parse::<Token![,]>()?;
+            Some((count.base10_parse::<usize>()?, count.span()))
+        }
+        false => None,
+    };
+    if content.is_empty() && count.is_some() {
+        return Err(content.error("atleast(...) needs at least one operand"));
+    }
+    let mut operands = vec![];
+    let mut height = 0;
+    while !content.is_empty() {
+        let (operand, operand_height) = parse_expr(&content, limiter, depth + 1)?;
+        operands.push(operand);
+        height = height.max(operand_height);
+        if !content.is_empty() {
+            content.parse::<Token![,]>()?;
+        }
+    }
+    let count = match count {
+        Some((count, span)) if count == 0 || count > operands.len() => {
+            return Err(syn::Error::new(
+                span,
+                format!("atleast needs a count from 1 to {}", operands.len()),
+            ));
+        }
+        Some((count, _)) => count,
+        None if name == "any" => 1,
+        None => operands.len(),
+    };
+    Ok((Expr::AtLeast(count, operands), height + 1))
+}
+
+// Kept apart from the recursion through brackets, which then only holds small frames. A
+// predicate's condition nests below it, so it is as high as the condition is deep.
+#[inline(never)]
+fn parse_leaf(
+    input: ParseStream,
+    limiter: &mut Limiter,
+    depth: usize,
+) -> Result<(Expr, usize), syn::Error> {
+    limiter.leaf(input.span())?;
+    if is_predicate(input) {
+        input.parse::<Ident>()?;
+        let content;
+        let span = input.span();
+        parenthesized!(content in input);
+        let condition: Condition = content.parse()?;
+        if !content.is_empty() {
+            return Err(content.error("Unexpected token in condition"));
+        }
+        let height = 1 + condition.depth();
+        limiter.depth(depth + height - 1, span)?;
+        return Ok((Expr::Predicate(condition), height));
+    }
+    if input.peek2(Paren) && input.peek(Ident) {
+        let name: Ident = input.parse()?;
+        let content;
+        parenthesized!(content in input);
+        let args = content.parse_terminated(Permission::parse, Token![,])?;
+        return Ok((
+            Expr::Call(
+                name,
+                args.into_iter()
+                    .map(|arg| Permissions {
+                        identifier: std::iter::once(arg).collect(),
+                    })
+                    .collect(),
+            ),
+            1,
+        ));
+    }
+    if input.fork().parse::<Permissions>().is_ok() {
+        let span = input.span();
+        let parsed: Permissions = input.parse()?;
+        limiter.fields(parsed.identifier.len(), span)?;
+        return Ok((Expr::Permission(parsed), 1));
+    }
+    // A single field parses where the whole permission does not, report the permission's error
+    if input.fork().parse::<Permission>().is_ok() {
+        input.parse::<Permissions>()?;
+    }
+    Err(syn::Error::new(input.span(), "Invalid token"))
+}
+
+/// Parses an expression whose root is at tree depth `depth`, returning it with its height.
+/// `!` and `exists` apply to everything after them and operators nest to the right without
+/// precedence, so `!a & b | c` is `!(a & (b | c))`. The chain of operands is collected without
+/// recursion, only brackets recurse, and a run of the same operator is a single node, so a long
+/// `a | b | c | ...` chain is one level deep.
+pub(crate) fn parse_expr(
+    input: ParseStream,
+    limiter: &mut Limiter,
+    depth: usize,
+) -> Result<(Expr, usize), syn::Error> {
+    let mut chain: Vec<(Vec<Prefix>, Expr, usize, BinaryOp)> = vec![];
+    // At most the depth of the next operand, which also depends on the operators after it
+    let mut level = depth;
+    let mut run_op = None;
+    // An `exists` quantifies the rest of the chain, up to the end of its brackets
+    let scope = limiter.exists_vars;
+    let (prefixes, last, last_height) = loop {
+        let mut prefixes = vec![];
+        while let Some(prefix) = parse_prefix(input)? {
+            limiter.depth(level, input.span())?;
+            if let Prefix::Exists(vars) = &prefix {
+                limiter.exists(vars.len(), vars[0].span())?;
+            }
+            prefixes.push(prefix);
+            level += 1;
+            run_op = None;
+        }
+        let (operand, height) = parse_operand(input, limiter, level)?;
+        match parse_op(input)? {
+            Some(op) => {
+                if run_op != Some(op) {
+                    run_op = Some(op);
+                    level += 1;
                 }
-                let right: Expr = input.parse()?;
-                return Ok(Self::Or(expr, Box::new(right)));
-            } else if input.peek(syn::Token![^]) {
-                input.parse::<syn::Token![^]>()?;
-                let right: Expr = input.parse()?;
-                return Ok(Self::Xor(expr, Box::new(right)));
+                chain.push((prefixes, operand, height, op));
             }
-            return Ok(*expr);
+            None => break (prefixes, operand, height),
         }
-        if input.fork().parse::<Permission>().is_ok() {
-            input.parse::<Permissions>()?;
-        } else {
-            return Err(syn::Error::new(input.span(), "Invalid token"));
+    };
+    // Built from the end, where the innermost node is. A prefix or a different operator closes
+    // the run of operands gathered so far.
+    let mut height = last_height + prefixes.len();
+    let mut expr = apply_prefixes(prefixes, last);
+    let mut run = Run::default();
+    while let Some((prefixes, left, left_height, op)) = chain.pop() {
+        if run.op.is_some_and(|run_op| run_op != op) {
+            (expr, height) = run.close(expr, height);
         }
-        unreachable!()
+        run.op = Some(op);
+        run.operands.push(left);
+        run.height = run.height.max(left_height);
+        if !prefixes.is_empty() {
+            (expr, height) = run.close(expr, height);
+            height += prefixes.len();
+            expr = apply_prefixes(prefixes, expr);
+        }
+    }
+    (expr, height) = run.close(expr, height);
+    limiter.depth(depth + height - 1, input.span())?;
+    limiter.exists_vars = scope;
+    Ok((expr, height))
+}
+
+// Operands of the same operator gathered from the end of a chain, with their greatest height
+#[derive(Default)]
+struct Run {
+    op: Option<BinaryOp>,
+    operands: Vec<Expr>,
+    height: usize,
+}
+
+impl Run {
+    // The gathered operands followed by `rest` under a node of their operator, with its height
+    fn close(&mut self, rest: Expr, rest_height: usize) -> (Expr, usize) {
+        let Some(op) = self.op.take() else {
+            return (rest, rest_height);
+        };
+        let mut operands: Vec<Expr> = self.operands.drain(..).rev().collect();
+        operands.push(rest);
+        let height = self.height.max(rest_height) + 1;
+        self.height = 0;
+        (op.build(operands), height)
     }
 }
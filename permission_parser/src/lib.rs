@@ -1,7 +1,19 @@
+pub mod condition;
+pub mod encoding;
 pub mod expr;
+pub mod library;
+pub mod limits;
 pub mod parser;
+pub mod simplify;
 pub mod token;
 pub mod tokenizer;
+pub mod validity;
 
-pub use crate::expr::Expr;
+pub use crate::condition::{Condition, Context, EmptyContext, Value, parse_condition};
+pub use crate::encoding::EncodingError;
+pub use crate::expr::{Expr, ExprDefinition};
+pub use crate::library::ExprLibrary;
+pub use crate::limits::{Limit, ParseLimits};
 pub use crate::parser::*;
+pub use crate::simplify::NormalExpr;
+pub use crate::validity::{Clock, SystemClock, Validity};
@@ -0,0 +1,264 @@
+use crate::{
+    Expr, ItemExpr, PermissionParseError,
+    expr::{Limiter, definition_order, parse_definition},
+    limits::{Limit, ParseLimits, check_input},
+    parser::{Bindings, parse_expr_str},
+    tokenizer::Field,
+};
+use std::{collections::HashMap, path::Path};
+use syn::parse::{ParseStream, Parser};
+
+#[derive(Clone)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+struct Definition {
+    params: Vec<String>,
+    body: ItemExpr,
+    // Depth, leaves and quantified placeholders of the body, which a call adds to the calling
+    // expression
+    size: Size,
+}
+
+/// Named expressions which other expressions call like `is_org_admin(1047)`, defined as
+/// `let is_org_admin(o) = org.{o}.role.admin.enact | org.{o}.role.owner.enact;`. A call is
+/// replaced by the body with its parameters bound to the arguments, so the resulting `ItemExpr`
+/// does not refer to the library.
+#[derive(Clone, Default)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct ExprLibrary {
+    definitions: HashMap<String, Definition>,
+}
+
+impl ExprLibrary {
+    /// Parses a sequence of definitions, which may call each other in any order but not
+    /// recursively. The whole source is subject to the default `ParseLimits`, as is every
+    /// definition once its calls are resolved.
+    pub fn parse(source: &str) -> Result<Self, PermissionParseError> {
+        let limits = ParseLimits::default();
+        check_input(source, &limits).map_err(PermissionParseError::LimitExceeded)?;
+        let mut limiter = Limiter::new(limits);
+        let result = (|input: ParseStream| {
+            let mut definitions = vec![];
+            while !input.is_empty() {
+                definitions.push(parse_definition(input, &mut limiter)?);
+            }
+            Ok(definitions)
+        })
+        .parse_str(source);
+        let definitions = match (result, limiter.exceeded) {
+            (Ok(definitions), _) => definitions,
+            (Err(_), Some(limit)) => return Err(PermissionParseError::LimitExceeded(limit)),
+            (Err(err), None) => return Err(err.into()),
+        };
+        for (idx, definition) in definitions.iter().enumerate() {
+            if definitions[..idx].iter().any(|d| d.name == definition.name) {
+                return Err(format!("Expression `{}` is defined twice", definition.name).into());
+            }
+        }
+        let order = definition_order(&definitions).map_err(|cycle| {
+            let cycle: Vec<String> = cycle.iter().map(|name| name.to_string()).collect();
+            format!("Recursive expression: {}", cycle.join(" -> "))
+        })?;
+        let mut library = Self::default();
+        for idx in order {
+            let definition = definitions[idx].clone();
+            let name = definition.name.to_string();
+            let params: Vec<String> = definition.params.iter().map(|p| p.to_string()).collect();
+            check_size(library.expanded_size(&definition.body), &limits)?;
+            let body = ItemExpr::from_expr_internal(definition.body, true, Some(&library))?;
+            let size = size(&body);
+            if let Some(var) = unbound_var(&body, &params) {
+                return Err(
+                    format!("`{{{}}}` is not a parameter of expression `{}`", var, name).into(),
+                );
+            }
+            library
+                .definitions
+                .insert(name, Definition { params, body, size });
+        }
+        Ok(library)
+    }
+
+    /// Reads and parses a file of definitions, see `ExprLibrary::parse`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PermissionParseError> {
+        let source = std::fs::read_to_string(&path).map_err(|err| {
+            format!(
+                "Cannot read expression library {}: {}",
+                path.as_ref().display(),
+                err
+            )
+        })?;
+        Self::parse(&source)
+    }
+
+    /// Whether an expression of this name is defined
+    pub fn contains(&self, name: &str) -> bool {
+        self.definitions.contains_key(name)
+    }
+
+    /// Like `expr_parse`, with calls resolved in this library
+    pub fn expr_parse(&self, expr: &str) -> Result<ItemExpr, PermissionParseError> {
+        self.parse_internal(expr, false)
+    }
+
+    /// Like `expr_parse_pattern`, with calls resolved in this library. Arguments may be `{var}`
+    /// placeholders themselves.
+    pub fn expr_parse_pattern(&self, expr: &str) -> Result<ItemExpr, PermissionParseError> {
+        self.parse_internal(expr, true)
+    }
+
+    fn parse_internal(
+        &self,
+        expr: &str,
+        allow_var: bool,
+    ) -> Result<ItemExpr, PermissionParseError> {
+        let limits = ParseLimits::default();
+        let expr = parse_expr_str(expr, &limits)?;
+        check_size(self.expanded_size(&expr), &limits)?;
+        ItemExpr::from_expr_internal(expr, allow_var, Some(self))
+    }
+
+    // The size of `expr` once its calls are resolved, without resolving them, so a call of a large
+    // body cannot make the result grow before it is rejected
+    fn expanded_size(&self, expr: &Expr) -> Size {
+        let mut size = Size::default();
+        let mut pending = vec![(expr, 1, 0)];
+        while let Some((expr, depth, scope)) = pending.pop() {
+            size.depth = size.depth.max(depth);
+            size.exists_vars = size.exists_vars.max(scope);
+            match expr {
+                Expr::Permission(_) => size.leaves += 1,
+                Expr::Predicate(condition) => {
+                    size.depth = size.depth.max(depth + condition.depth());
+                    size.leaves += 1;
+                }
+                Expr::Not(e) | Expr::Bracketed(e) => pending.push((e, depth + 1, scope)),
+                Expr::Exists(vars, e) => pending.push((e, depth + 1, scope + vars.len())),
+                Expr::And(operands)
+                | Expr::Or(operands)
+                | Expr::Xor(operands)
+                | Expr::AtLeast(_, operands) => {
+                    pending.extend(operands.iter().map(|operand| (operand, depth + 1, scope)))
+                }
+                // Unknown calls fail when they are resolved
+                Expr::Call(name, _) => {
+                    if let Some(definition) = self.definitions.get(&name.to_string()) {
+                        let body = definition.size;
+                        size.depth = size.depth.max(depth + body.depth);
+                        size.leaves += body.leaves;
+                        size.exists_vars = size.exists_vars.max(scope + body.exists_vars);
+                    }
+                }
+            }
+        }
+        size
+    }
+
+    pub(crate) fn call(
+        &self,
+        name: &str,
+        args: Vec<Field>,
+    ) -> Result<ItemExpr, PermissionParseError> {
+        let definition = self
+            .definitions
+            .get(name)
+            .ok_or_else(|| format!("Unknown expression `{}`", name))?;
+        if args.len() != definition.params.len() {
+            return Err(format!(
+                "Expression `{}` takes {} arguments but {} were given",
+                name,
+                definition.params.len(),
+                args.len()
+            )
+            .into());
+        }
+        let bindings: Bindings = definition.params.iter().cloned().zip(args).collect();
+        bind(&definition.body, &bindings)
+    }
+}
+
+// Placeholders left by a caller's parameter or an `exists` are checked once they are bound
+fn bind(expr: &ItemExpr, bindings: &Bindings) -> Result<ItemExpr, PermissionParseError> {
+    let bound = expr.bind(bindings);
+    let mut pending = vec![&bound];
+    while let Some(expr) = pending.pop() {
+        if let ItemExpr::Permission(p) = expr
+            && !p.has_var()
+        {
+            p.validate()?;
+        }
+        pending.extend(children(expr));
+    }
+    Ok(bound)
+}
+
+fn children(expr: &ItemExpr) -> Vec<&ItemExpr> {
+    match expr {
+        ItemExpr::Permission(_) | ItemExpr::Predicate(_) => vec![],
+        ItemExpr::Not(e) | ItemExpr::Bracketed(e) | ItemExpr::Exists(_, e) => vec![e],
+        ItemExpr::And(operands)
+        | ItemExpr::Or(operands)
+        | ItemExpr::Xor(operands)
+        | ItemExpr::AtLeast(_, operands) => operands.iter().collect(),
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+struct Size {
+    depth: usize,
+    leaves: usize,
+    // The most placeholders quantified around a single node
+    exists_vars: usize,
+}
+
+fn size(expr: &ItemExpr) -> Size {
+    let mut size = Size::default();
+    let mut pending = vec![(expr, 1, 0)];
+    while let Some((expr, depth, scope)) = pending.pop() {
+        size.depth = size.depth.max(depth);
+        size.exists_vars = size.exists_vars.max(scope);
+        match expr {
+            ItemExpr::Permission(_) => size.leaves += 1,
+            ItemExpr::Predicate(condition) => {
+                size.depth = size.depth.max(depth + condition.depth());
+                size.leaves += 1;
+            }
+            _ => {}
+        }
+        let scope = match expr {
+            ItemExpr::Exists(vars, _) => scope + vars.len(),
+            _ => scope,
+        };
+        pending.extend(
+            children(expr)
+                .into_iter()
+                .map(|child| (child, depth + 1, scope)),
+        );
+    }
+    size
+}
+
+// Resolving calls nests bodies into the calling expression, which has to stay within the limits
+// the parser enforces on its own
+fn check_size(size: Size, limits: &ParseLimits) -> Result<(), PermissionParseError> {
+    if size.depth > limits.max_depth {
+        return Err(PermissionParseError::LimitExceeded(Limit::Depth(
+            limits.max_depth,
+        )));
+    }
+    if size.leaves > limits.max_leaves {
+        return Err(PermissionParseError::LimitExceeded(Limit::Leaves(
+            limits.max_leaves,
+        )));
+    }
+    if size.exists_vars > limits.max_exists_vars {
+        return Err(PermissionParseError::LimitExceeded(Limit::ExistsVars(
+            limits.max_exists_vars,
+        )));
+    }
+    Ok(())
+}
+
+fn unbound_var(expr: &ItemExpr, params: &[String]) -> Option<String> {
+    expr.vars().into_iter().find(|var| !params.contains(var))
+}
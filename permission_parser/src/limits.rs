@@ -0,0 +1,103 @@
+// Bounds on untrusted input. Expressions are trees which are walked recursively after parsing
+// (conversion, checks, encoding, dropping), so bounding their depth bounds the stack used.
+
+use crate::encoding::MAX_DECODE_DEPTH;
+
+/// Limits applied by `parse_with_limits` and `expr_parse_with_limits`, the other parse
+/// functions use the defaults
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct ParseLimits {
+    /// Length of the input in bytes
+    pub max_len: usize,
+    /// Nesting of the expression tree, each `!`, `exists`, bracket and quantifier adds a level, as
+    /// do the `!`, `&&` and `||` of a `ctx(...)` condition. A chain of the same operator is a
+    /// single level however long, so `a | b | c` is two levels deep, and operators nest to the
+    /// right, so each change of operator, as in `a & b | c`, adds one.
+    pub max_depth: usize,
+    /// Permissions and `ctx(...)` predicates in an expression
+    pub max_leaves: usize,
+    /// Fields in a single permission
+    pub max_fields: usize,
+    /// Placeholders quantified by the `exists` around any part of an expression, e.g. two for
+    /// `exists u: exists v: ...` and one for `(exists u: ...) & (exists v: ...)`. Checking tries
+    /// every combination of their values, a number of the group's values to this power.
+    /// `ItemExpr::decode` applies the default.
+    pub max_exists_vars: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self {
+            max_len: 64 * 1024,
+            // Anything parsed with the defaults can be encoded and decoded again
+            max_depth: MAX_DECODE_DEPTH,
+            max_leaves: 1024,
+            max_fields: 64,
+            max_exists_vars: 3,
+        }
+    }
+}
+
+/// The limit an input exceeded, with its configured maximum
+// Always `Debug`, as `PermissionParseError` holding it is
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Limit {
+    Len(usize),
+    Depth(usize),
+    Leaves(usize),
+    Fields(usize),
+    ExistsVars(usize),
+}
+
+impl Limit {
+    pub(crate) fn message(&self) -> String {
+        match self {
+            Limit::Len(max) => format!("Input is longer than {} bytes", max),
+            Limit::Depth(max) => format!("Expression is nested deeper than {} levels", max),
+            Limit::Leaves(max) => format!("Expression has more than {} leaves", max),
+            Limit::Fields(max) => format!("Permission has more than {} fields", max),
+            Limit::ExistsVars(max) => {
+                format!(
+                    "Expression quantifies more than {} placeholders at once",
+                    max
+                )
+            }
+        }
+    }
+}
+
+/// Checks the input length and bracket nesting before the input reaches `syn`, whose token
+/// buffer is built recursively over nested brackets. Brackets inside string literals are
+/// skipped, other literals may only over-count.
+pub(crate) fn check_input(input: &str, limits: &ParseLimits) -> Result<(), Limit> {
+    if input.len() > limits.max_len {
+        return Err(Limit::Len(limits.max_len));
+    }
+    let mut depth: usize = 0;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '(' | '[' | '{' => {
+                depth += 1;
+                if depth > limits.max_depth {
+                    return Err(Limit::Depth(limits.max_depth));
+                }
+            }
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
+}
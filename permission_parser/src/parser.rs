@@ -1,15 +1,24 @@
 use crate::{
-    Expr, token,
+    Expr,
+    condition::Condition,
+    expr::{Limiter, parse_expr},
+    library::ExprLibrary,
+    limits::{Limit, ParseLimits, check_input},
+    token,
     tokenizer::{self},
+    validity::Validity,
 };
 use proc_macro2::Span;
 use regex::Regex;
-use std::sync::LazyLock;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    sync::LazyLock,
+};
 use syn::{
-    Ident, Lit, LitInt, Token, braced,
+    Ident, Lit, LitFloat, LitInt, LitStr, Token, braced,
     ext::IdentExt,
-    parse::{Parse, ParseStream},
-    parse_str,
+    parse::{Parse, ParseStream, Parser},
     punctuated::Punctuated,
     spanned::Spanned,
 };
@@ -25,9 +34,9 @@ use syn::{
 //     return RE.is_match_at(&permission, 0);
 // }
 
-fn match_number_sequence(number: &String) -> bool {
+fn match_number_sequence(number: &str) -> bool {
     static RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(0|[1-9][0-9]*)$").unwrap());
-    return RE.is_match_at(&number, 0);
+    RE.is_match_at(number, 0)
 }
 
 #[derive(Clone)]
@@ -45,12 +54,16 @@ pub enum Permission {
     SingleGlob(Span),
     ID(Span, LitInt),
     Name(Span, String),
-    VarName(Span, Ident),
+    VarName(Span, Ident, tokenizer::VarType),
+    Prefix(Span, String),
+    Range(Span, LitInt, LitInt),
+    Alternatives(Span, Vec<Permission>),
+    Uuid(Span, u128),
 }
 
 impl Permission {
     pub fn span(&self) -> Span {
-        return match self {
+        match self {
             Permission::Add(span) => *span,
             Permission::Remove(span) => *span,
             Permission::ReadOne(span) => *span,
@@ -64,12 +77,16 @@ impl Permission {
             Permission::ID(span, _) => *span,
             Permission::Name(span, _) => *span,
             Permission::Enact(span) => *span,
-            Permission::VarName(span, _) => *span,
-        };
+            Permission::VarName(span, _, _) => *span,
+            Permission::Prefix(span, _) => *span,
+            Permission::Range(span, _, _) => *span,
+            Permission::Alternatives(span, _) => *span,
+            Permission::Uuid(span, _) => *span,
+        }
     }
 
     pub fn name(&self) -> &'static str {
-        return match self {
+        match self {
             Permission::Add(_) => "add",
             Permission::Remove(_) => "remove",
             Permission::ReadOne(_) => "read_one",
@@ -83,18 +100,104 @@ impl Permission {
             Permission::SingleGlob(_) => "*",
             Permission::ID(_, _) => "custom_id",
             Permission::Name(_, _) => "custom_name",
-            Permission::VarName(_, _) => "{var}",
-        };
+            Permission::VarName(_, _, _) => "{var}",
+            Permission::Prefix(_, _) => "custom_prefix",
+            Permission::Range(_, _, _) => "custom_range",
+            Permission::Alternatives(_, _) => "{alternatives}",
+            Permission::Uuid(_, _) => "custom_uuid",
+        }
+    }
+
+    fn is_specifier(&self) -> bool {
+        match self {
+            Permission::Add(_)
+            | Permission::Remove(_)
+            | Permission::ReadOne(_)
+            | Permission::ListAll(_)
+            | Permission::Read(_)
+            | Permission::Write(_)
+            | Permission::Assign(_)
+            | Permission::Enact(_) => true,
+            Permission::Alternatives(_, alternatives) => {
+                alternatives.iter().all(Permission::is_specifier)
+            }
+            _ => false,
+        }
+    }
+}
+
+// `{a,b}` holds names and IDs, or specifiers
+fn parse_alternatives(content: ParseStream, span: Span) -> Result<Permission, syn::Error> {
+    let alternatives: Vec<Permission> = content
+        .parse_terminated(Permission::parse, Token![,])?
+        .into_iter()
+        .collect();
+    if alternatives.len() < 2 {
+        return Err(syn::Error::new(
+            span,
+            "Alternatives need at least two fields, use `{var}` for a placeholder",
+        ));
+    }
+    for alternative in &alternatives {
+        if !matches!(
+            alternative,
+            Permission::Name(_, _) | Permission::ID(_, _) | Permission::Uuid(_, _)
+        ) && !alternative.is_specifier()
+            || matches!(alternative, Permission::Alternatives(_, _))
+        {
+            return Err(syn::Error::new(
+                alternative.span(),
+                format!(
+                    "Alternatives can only hold names, IDs and specifiers, not `{}`",
+                    alternative.name()
+                ),
+            ));
+        }
+    }
+    if alternatives.iter().any(Permission::is_specifier)
+        && !alternatives.iter().all(Permission::is_specifier)
+    {
+        return Err(syn::Error::new(
+            span,
+            "Alternatives cannot mix specifiers with names and IDs",
+        ));
     }
+    Ok(Permission::Alternatives(span, alternatives))
 }
 
 impl Parse for Permission {
     fn parse(input: ParseStream) -> Result<Self, syn::Error> {
         if input.peek(syn::token::Brace) {
             let content;
-            braced!(content in input);
-            let parsed: Ident = content.parse()?;
-            return Ok(Permission::VarName(parsed.span(), parsed));
+            let brace = braced!(content in input);
+            let fork = content.fork();
+            if fork.parse::<Ident>().is_ok() && (fork.is_empty() || fork.peek(Token![:])) {
+                let parsed: Ident = content.parse()?;
+                let ty = match content.parse::<Option<Token![:]>>()? {
+                    None => tokenizer::VarType::Any,
+                    Some(_) => {
+                        let ty: Ident = content.parse()?;
+                        match ty.to_string().as_str() {
+                            "id" => tokenizer::VarType::Id,
+                            "name" => tokenizer::VarType::Name,
+                            _ => {
+                                return Err(syn::Error::new(
+                                    ty.span(),
+                                    format!(
+                                        "Unknown placeholder type `{}`, use `id` or `name`",
+                                        ty
+                                    ),
+                                ));
+                            }
+                        }
+                    }
+                };
+                if !content.is_empty() {
+                    return Err(content.error("Expected `}` after the placeholder type"));
+                }
+                return Ok(Permission::VarName(parsed.span(), parsed, ty));
+            }
+            return parse_alternatives(&content, brace.span.join());
         }
         if input.peek(token::TripleGlob) {
             let r = input.parse::<token::TripleGlob>()?;
@@ -144,18 +247,53 @@ impl Parse for Permission {
             let state = input.fork();
             let value: LitInt = state.parse()?;
             let str_content = value.to_string();
-            if match_number_sequence(&str_content) && value.base10_parse::<i64>().is_ok() {
+            if match_number_sequence(&str_content) && value.base10_parse::<u128>().is_ok() {
                 input.parse::<LitInt>()?;
                 // let value = value.base10_parse::<i64>()?;
+                if input.peek(Token![..]) && !input.peek(Token![..=]) {
+                    return parse_range(input, value);
+                }
                 return Ok(Permission::ID(value.span(), value));
             }
         }
+        if input.peek(LitStr) {
+            let value: LitStr = input.parse()?;
+            match value.suffix() {
+                "" => {}
+                "uuid" => {
+                    return match tokenizer::parse_uuid(&value.value()) {
+                        Some(uuid) => Ok(Permission::Uuid(value.span(), uuid)),
+                        None => Err(syn::Error::new(
+                            value.span(),
+                            "Invalid UUID: use the hyphenated form like `\"550e8400-e29b-41d4-a716-446655440000\"uuid`",
+                        )),
+                    };
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "Invalid name: a quoted name cannot have a suffix other than `uuid`",
+                    ));
+                }
+            }
+            if input.peek(token::SingleGlob) && !input.peek(token::DoubleGlob) {
+                let glob = input.parse::<token::SingleGlob>()?;
+                let span = value.span().join(glob.span()).unwrap_or(value.span());
+                if value.value().is_empty() {
+                    return Err(syn::Error::new(
+                        span,
+                        "A prefix cannot be empty, use `*` instead",
+                    ));
+                }
+                return Ok(Permission::Prefix(span, value.value()));
+            }
+            return Ok(Permission::Name(value.span(), value.value()));
+        }
         if input.peek(Lit) {
             let value: Lit = input.parse()?;
             return Ok(Permission::Name(
                 value.span(),
                 match value.clone() {
-                    Lit::Str(s) => s.value(),
                     Lit::Bool(b) => b.value.to_string(),
                     Lit::Float(_) => {
                         return Err(syn::Error::new(
@@ -163,7 +301,12 @@ impl Parse for Permission {
                             "2 ID cannot appear consecutively",
                         ));
                     }
-                    Lit::Int(_) => unreachable!(),
+                    Lit::Int(_) => {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            "Invalid ID: use an unsigned integer without leading zeros or suffix",
+                        ));
+                    }
                     Lit::Byte(_) => {
                         return Err(syn::Error::new(
                             value.span(),
@@ -184,16 +327,49 @@ impl Parse for Permission {
                         ));
                     }
                     Lit::Verbatim(v) => v.to_string(),
-                    _ => unreachable!(),
+                    _ => {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            "Invalid syntax: unsupported literal",
+                        ));
+                    }
                 },
             ));
         }
         // let value: Ident = input.parse()?;
         let value = input.call(Ident::parse_any)?;
-        return Ok(Permission::Name(value.span(), value.to_string()));
+        if input.peek(token::SingleGlob) && !input.peek(token::DoubleGlob) {
+            let glob = input.parse::<token::SingleGlob>()?;
+            let span = value.span().join(glob.span()).unwrap_or(value.span());
+            return Ok(Permission::Prefix(span, value.to_string()));
+        }
+        Ok(Permission::Name(value.span(), value.to_string()))
     }
 }
 
+// `start..end` after its start, which has been parsed as an ID
+fn parse_range(input: ParseStream, start: LitInt) -> Result<Permission, syn::Error> {
+    input.parse::<Token![..]>()?;
+    range(start, input.parse()?)
+}
+
+fn range(start: LitInt, end: LitInt) -> Result<Permission, syn::Error> {
+    if !match_number_sequence(&end.to_string()) {
+        return Err(syn::Error::new(
+            end.span(),
+            "Invalid ID: use an unsigned integer without leading zeros or suffix",
+        ));
+    }
+    if end.base10_parse::<u64>()? <= start.base10_parse::<u64>()? {
+        return Err(syn::Error::new(
+            end.span(),
+            "Invalid range: the end must be greater than the start",
+        ));
+    }
+    let span = start.span().join(end.span()).unwrap_or(start.span());
+    Ok(Permission::Range(span, start, end))
+}
+
 #[derive(Clone)]
 pub struct Permissions {
     pub identifier: Punctuated<Permission, Token![.]>,
@@ -204,9 +380,82 @@ enum Terminator {
     Specifier(Permission, Span),
 }
 
+// An ID followed by the separator and a segment not starting with a letter, as in `1047.*` or
+// `1047."my-project"`, is lexed as the float `1047.`, which is split back into the two
+fn split_float(input: ParseStream) -> Result<Option<(LitInt, Token![.])>, syn::Error> {
+    if !input.peek(LitFloat) {
+        return Ok(None);
+    }
+    let float: LitFloat = input.fork().parse()?;
+    let text = float.to_string();
+    let Some(id) = text
+        .strip_suffix('.')
+        .filter(|id| id.bytes().all(|b| b.is_ascii_digit()))
+    else {
+        return Ok(None);
+    };
+    if !match_number_sequence(id) {
+        return Err(syn::Error::new(
+            float.span(),
+            "Invalid ID: use an unsigned integer without leading zeros or suffix",
+        ));
+    }
+    input.parse::<LitFloat>()?;
+    Ok(Some((
+        LitInt::new(id, float.span()),
+        Token![.](float.span()),
+    )))
+}
+
+// One segment with the separator after it if it was lexed together with the segment
+fn parse_segment(input: ParseStream) -> Result<(Permission, Option<Token![.]>), syn::Error> {
+    if let Some((id, dot)) = split_float(input)? {
+        if id.base10_parse::<u128>().is_err() {
+            return Err(syn::Error::new(
+                id.span(),
+                "Invalid ID: use an unsigned integer without leading zeros or suffix",
+            ));
+        }
+        return Ok((Permission::ID(id.span(), id), Some(dot)));
+    }
+    let fork = input.fork();
+    if fork.parse::<LitInt>().is_ok()
+        && fork.peek(Token![..])
+        && !fork.peek(Token![..=])
+        && fork.parse::<Token![..]>().is_ok()
+        && fork.peek(LitFloat)
+    {
+        let start: LitInt = input.parse()?;
+        if !match_number_sequence(&start.to_string()) {
+            return Err(syn::Error::new(
+                start.span(),
+                "Invalid ID: use an unsigned integer without leading zeros or suffix",
+            ));
+        }
+        input.parse::<Token![..]>()?;
+        return match split_float(input)? {
+            Some((end, dot)) => Ok((range(start, end)?, Some(dot))),
+            None => Err(syn::Error::new(
+                input.span(),
+                "Invalid ID: use an unsigned integer without leading zeros or suffix",
+            )),
+        };
+    }
+    Ok((input.parse()?, None))
+}
+
 impl Parse for Permissions {
     fn parse(input: ParseStream) -> Result<Self, syn::Error> {
-        let permissions = syn::punctuated::Punctuated::parse_separated_nonempty(&input)?;
+        let mut permissions = Punctuated::new();
+        loop {
+            let (permission, dot) = parse_segment(input)?;
+            permissions.push_value(permission);
+            match dot {
+                Some(dot) => permissions.push_punct(dot),
+                None if input.peek(Token![.]) => permissions.push_punct(input.parse()?),
+                None => break,
+            }
+        }
         let mut triple_glob_count: i32 = 0;
         let mut is_terminated: Option<Terminator> = None;
         for item in &permissions {
@@ -241,10 +490,14 @@ impl Parse for Permissions {
                 }
                 Permission::Name(_, _)
                 | Permission::ID(_, _)
+                | Permission::Uuid(_, _)
                 | Permission::SingleGlob(_)
-                | Permission::DoubleGlob(_) 
-                | Permission::VarName(_, _, ) => {}
-                specifier @ _ => {
+                | Permission::DoubleGlob(_)
+                | Permission::VarName(_, _, _)
+                | Permission::Prefix(_, _)
+                | Permission::Range(_, _, _) => {}
+                Permission::Alternatives(_, _) if !item.is_specifier() => {}
+                specifier => {
                     is_terminated = match specifier {
                         Permission::Add(span)
                         | Permission::Remove(span)
@@ -260,9 +513,9 @@ impl Parse for Permissions {
                 }
             }
         }
-        return Ok(Permissions {
+        Ok(Permissions {
             identifier: permissions,
-        });
+        })
     }
 }
 
@@ -270,6 +523,8 @@ impl Parse for Permissions {
 pub enum PermissionParseError {
     Syn(syn::Error),
     InvalidOutput(String),
+    /// The input exceeded one of the `ParseLimits`
+    LimitExceeded(Limit),
 }
 
 impl From<syn::Error> for PermissionParseError {
@@ -284,77 +539,271 @@ impl From<String> for PermissionParseError {
     }
 }
 
+fn permission_to_field(permission: &Permission) -> Result<tokenizer::Field, PermissionParseError> {
+    Ok(match permission {
+        Permission::ID(_, i) => tokenizer::Field::id(i.base10_parse::<u128>()?),
+        Permission::Uuid(_, uuid) => tokenizer::Field::Uuid { uuid: *uuid },
+        Permission::Name(_, name) => tokenizer::Field::Name {
+            name: name.to_string(),
+        },
+        Permission::Add(_) => tokenizer::ListSpecifier::Add.into(),
+        Permission::Remove(_) => tokenizer::ListSpecifier::Remove.into(),
+        Permission::ReadOne(_) => tokenizer::ListSpecifier::ReadOne.into(),
+        Permission::ListAll(_) => tokenizer::ListSpecifier::ListAll.into(),
+        Permission::Read(_) => tokenizer::Specifier::Read.into(),
+        Permission::Write(_) => tokenizer::Specifier::Write.into(),
+        Permission::Assign(_) => tokenizer::Specifier::Assign.into(),
+        Permission::Enact(_) => tokenizer::Specifier::Enact.into(),
+        Permission::SingleGlob(_) => tokenizer::Field::Glob,
+        Permission::DoubleGlob(_) => tokenizer::Field::DoubleGlob,
+        Permission::TripleGlob(_) => tokenizer::Field::TripleGlob,
+        Permission::VarName(span, ident, ty) => {
+            tokenizer::Field::VarKind(*span, ident.clone(), *ty)
+        }
+        Permission::Prefix(_, prefix) => tokenizer::Field::Prefix {
+            prefix: prefix.to_string(),
+        },
+        Permission::Range(_, start, end) => tokenizer::Field::Range {
+            start: start.base10_parse::<u64>()?,
+            end: end.base10_parse::<u64>()?,
+        },
+        Permission::Alternatives(_, alternatives) => tokenizer::Field::Alternatives {
+            fields: alternatives
+                .iter()
+                .map(permission_to_field)
+                .collect::<Result<Vec<tokenizer::Field>, PermissionParseError>>()?,
+        },
+    })
+}
+
 pub fn token_converter(
     permissions: Permissions,
 ) -> Result<Vec<tokenizer::Field>, PermissionParseError> {
-    let parse_result: Result<Vec<tokenizer::Field>, PermissionParseError> = permissions
+    permissions
         .identifier
         .iter()
-        .map(|permission| {
-            Ok(match permission {
-                Permission::ID(_, i) => tokenizer::Field::ID {
-                    id: i.base10_parse::<u64>()?,
-                },
-                Permission::Name(_, name) => tokenizer::Field::Name {
-                    name: name.to_string(),
-                },
-                Permission::Add(_) => tokenizer::ListSpecifier::Add.into(),
-                Permission::Remove(_) => tokenizer::ListSpecifier::Remove.into(),
-                Permission::ReadOne(_) => tokenizer::ListSpecifier::ReadOne.into(),
-                Permission::ListAll(_) => tokenizer::ListSpecifier::ListAll.into(),
-                Permission::Read(_) => tokenizer::Specifier::Read.into(),
-                Permission::Write(_) => tokenizer::Specifier::Write.into(),
-                Permission::Assign(_) => tokenizer::Specifier::Assign.into(),
-                Permission::Enact(_) => tokenizer::Specifier::Enact.into(),
-                Permission::SingleGlob(_) => tokenizer::Field::Glob,
-                Permission::DoubleGlob(_) => tokenizer::Field::DoubleGlob,
-                Permission::TripleGlob(_) => tokenizer::Field::TripleGlob,
-                Permission::VarName(span, ident) => tokenizer::Field::VarKind(*span, ident.clone()),
-            })
-        })
-        .collect();
-
-    return Ok(parse_result?);
+        .map(permission_to_field)
+        .collect()
 }
 
-fn parse_internal(permission: &str) -> Result<Vec<tokenizer::Field>, PermissionParseError> {
+fn parse_internal(
+    permission: &str,
+    limits: &ParseLimits,
+) -> Result<Vec<tokenizer::Field>, PermissionParseError> {
     // if !match_strings(permission) {
     //     return Err(PermissionParseError::InvalidOutput(
     //         "The given permission string does not match the required format".to_string(),
     //     ));
     // }
+    check_input(permission, limits).map_err(PermissionParseError::LimitExceeded)?;
     let result: Permissions = syn::parse_str(permission)?;
+    if result.identifier.len() > limits.max_fields {
+        return Err(PermissionParseError::LimitExceeded(Limit::Fields(
+            limits.max_fields,
+        )));
+    }
 
-    return token_converter(result);
+    token_converter(result)
 }
 
+#[derive(PartialEq, Eq, Hash)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub struct PermissionItem {
     pub perm: Vec<tokenizer::Field>,
+    /// When the grant applies, only considered by the time-aware checks
+    pub validity: Validity,
+    /// Request context the grant is limited to, only considered by the context-aware checks
+    pub condition: Option<Condition>,
 }
 
 impl Clone for PermissionItem {
     fn clone(&self) -> Self {
         Self {
             perm: self.perm.iter().map(|i| (*i).clone()).collect(),
+            validity: self.validity,
+            condition: self.condition.clone(),
         }
     }
 }
 
-impl From<Vec<tokenizer::Field>> for PermissionItem {
-    fn from(value: Vec<tokenizer::Field>) -> Self {
-        Self { perm: value }
+// The invariants `parse_alternatives` enforces
+fn validate_alternatives(fields: &[tokenizer::Field]) -> Result<(), PermissionParseError> {
+    if fields.len() < 2 {
+        return Err("Alternatives need at least two fields".to_string().into());
+    }
+    if let Some(field) = fields.iter().find(|field| {
+        !matches!(
+            field,
+            tokenizer::Field::Name { name: _ }
+                | tokenizer::Field::ID { id: _ }
+                | tokenizer::Field::LargeID { id: _ }
+                | tokenizer::Field::Uuid { uuid: _ }
+                | tokenizer::Field::Specifier { specifier: _ }
+        )
+    }) {
+        return Err(format!(
+            "Alternatives can only hold names, IDs and specifiers, not `{}`",
+            field
+        )
+        .into());
     }
+    let specifiers = fields.iter().filter(|field| field.is_specifier()).count();
+    if specifiers != 0 && specifiers != fields.len() {
+        return Err("Alternatives cannot mix specifiers with names and IDs"
+            .to_string()
+            .into());
+    }
+    Ok(())
 }
 
-impl ToString for PermissionItem {
-    fn to_string(&self) -> String {
-        return self
+/// Values for `{var}` placeholders, keyed by variable name
+pub type Bindings = HashMap<String, tokenizer::Field>;
+
+impl PermissionItem {
+    /// Checks the invariants enforced by `Permissions::parse` on an already built item
+    pub fn validate(&self) -> Result<(), PermissionParseError> {
+        if self.perm.is_empty() {
+            return Err("A permission must contain at least one field"
+                .to_string()
+                .into());
+        }
+        let mut triple_glob_count: i32 = 0;
+        for (idx, field) in self.perm.iter().enumerate() {
+            match field {
+                tokenizer::Field::TripleGlob => {
+                    if triple_glob_count > 0 {
+                        return Err("Cannot use triple glob more than once in a permission"
+                            .to_string()
+                            .into());
+                    }
+                    triple_glob_count += 1;
+                }
+                tokenizer::Field::Specifier { specifier } => {
+                    if idx + 1 != self.perm.len() {
+                        return Err(format!(
+                            "Cannot use further define the permission after using specifier (`{}`)",
+                            specifier
+                        )
+                        .into());
+                    }
+                }
+                tokenizer::Field::VarKind(_, ident, _) => {
+                    return Err(format!("Unresolved variable `{{{}}}` in permission", ident).into());
+                }
+                tokenizer::Field::Prefix { prefix } => {
+                    if prefix.is_empty() {
+                        return Err("A prefix cannot be empty, use `*` instead"
+                            .to_string()
+                            .into());
+                    }
+                }
+                tokenizer::Field::Range { start, end } => {
+                    if end <= start {
+                        return Err(format!(
+                            "Invalid range `{}..{}`: the end must be greater than the start",
+                            start, end
+                        )
+                        .into());
+                    }
+                }
+                tokenizer::Field::Alternatives { fields } => {
+                    validate_alternatives(fields)?;
+                    if field.is_specifier() && idx + 1 != self.perm.len() {
+                        return Err(format!(
+                            "Cannot use further define the permission after using specifier (`{}`)",
+                            field
+                        )
+                        .into());
+                    }
+                }
+                tokenizer::Field::LargeID { id } => {
+                    if u64::try_from(*id).is_ok() {
+                        return Err(format!("ID `{}` fits `Field::ID`, use it instead", id).into());
+                    }
+                }
+                tokenizer::Field::Name { name: _ }
+                | tokenizer::Field::ID { id: _ }
+                | tokenizer::Field::Uuid { uuid: _ }
+                | tokenizer::Field::Glob
+                | tokenizer::Field::DoubleGlob => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_var(&self) -> bool {
+        self.perm
+            .iter()
+            .any(|field| matches!(field, tokenizer::Field::VarKind(..)))
+    }
+
+    /// Replaces every `{var}` placeholder with its value from `bindings`, which must be of the
+    /// type of a typed placeholder like `{var:id}`
+    pub fn bind(&self, bindings: &Bindings) -> Result<PermissionItem, PermissionParseError> {
+        let perm = self
             .perm
             .iter()
-            .map(|field| field.to_string())
-            .collect::<Vec<String>>()
-            .join(".");
+            .map(|field| match field {
+                tokenizer::Field::VarKind(_, ident, ty) => match bindings.get(&ident.to_string()) {
+                    Some(value) if ty.accepts(value) => Ok(value.clone()),
+                    Some(value) => {
+                        Err(format!("Variable `{}` cannot be bound to `{}`", field, value).into())
+                    }
+                    None => Err(format!("No value bound for variable `{{{}}}`", ident).into()),
+                },
+                field => Ok(field.clone()),
+            })
+            .collect::<Result<Vec<tokenizer::Field>, PermissionParseError>>()?;
+        Ok(PermissionItem {
+            perm,
+            validity: self.validity,
+            condition: self.condition.clone(),
+        })
+    }
+
+    /// Like `bind`, but placeholders without a value of their type in `bindings` are kept
+    pub fn bind_known(&self, bindings: &Bindings) -> PermissionItem {
+        let perm = self
+            .perm
+            .iter()
+            .map(|field| match field {
+                tokenizer::Field::VarKind(_, ident, ty) => bindings
+                    .get(&ident.to_string())
+                    .filter(|value| ty.accepts(value))
+                    .unwrap_or(field)
+                    .clone(),
+                field => field.clone(),
+            })
+            .collect();
+        PermissionItem {
+            perm,
+            validity: self.validity,
+            condition: self.condition.clone(),
+        }
+    }
+}
+
+impl From<Vec<tokenizer::Field>> for PermissionItem {
+    fn from(value: Vec<tokenizer::Field>) -> Self {
+        Self {
+            perm: value,
+            validity: Validity::default(),
+            condition: None,
+        }
+    }
+}
+
+impl Display for PermissionItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.perm
+                .iter()
+                .map(|field| field.to_string())
+                .collect::<Vec<String>>()
+                .join(".")
+        )
     }
 }
 
@@ -390,7 +839,7 @@ impl From<&PermissionGroup> for PermissionGroup {
 }
 
 impl PermissionGroup {
-    pub fn add(&mut self, item: PermissionItem) -> () {
+    pub fn add(&mut self, item: PermissionItem) {
         self.perms.push(item);
     }
 }
@@ -404,71 +853,306 @@ impl Clone for PermissionGroup {
 }
 
 pub fn parse(permission: &str) -> Result<PermissionItem, PermissionParseError> {
-    Ok(parse_internal(permission)?
+    parse_with_limits(permission, &ParseLimits::default())
+}
+
+pub fn parse_with_limits(
+    permission: &str,
+    limits: &ParseLimits,
+) -> Result<PermissionItem, PermissionParseError> {
+    Ok(parse_internal(permission, limits)?
         .iter()
         .map(|field| match field {
-            tokenizer::Field::VarKind(_, _) => Err(PermissionParseError::InvalidOutput(
+            tokenizer::Field::VarKind(..) => Err(PermissionParseError::InvalidOutput(
                 "Cannot use variable encapsulation when parsing in string form".to_string(),
             )),
-            field @ _ => Ok(field.clone()),
+            field => Ok(field.clone()),
         })
         .collect::<Result<Vec<tokenizer::Field>, PermissionParseError>>()?
         .into())
 }
 
-#[derive(Clone)]
+/// Parses a permission which may contain `{var}` placeholders, e.g. `org.1047.doc.{id}.read`
+pub fn parse_pattern(permission: &str) -> Result<PermissionItem, PermissionParseError> {
+    Ok(parse_internal(permission, &ParseLimits::default())?.into())
+}
+
+#[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub enum ItemExpr {
     Permission(PermissionItem),
+    /// A condition on the request context, written `ctx(<condition>)`
+    Predicate(Condition),
     Not(Box<ItemExpr>),
-    And(Box<ItemExpr>, Box<ItemExpr>),
-    Or(Box<ItemExpr>, Box<ItemExpr>),
-    Xor(Box<ItemExpr>, Box<ItemExpr>),
+    /// A chain of the same operator, e.g. `a & b & c`, with at least two operands
+    And(Vec<ItemExpr>),
+    Or(Vec<ItemExpr>),
+    Xor(Vec<ItemExpr>),
     Bracketed(Box<ItemExpr>),
+    /// True when at least this many operands are, written `any(...)` for one, `all(...)` for all
+    /// of them and `atleast(k, ...)` otherwise. `any()` and `all()` have no operands and are false
+    /// and true.
+    AtLeast(usize, Vec<ItemExpr>),
+    /// `exists u, v: <expr>`, true when some names or IDs for the `{u}` and `{v}` placeholders
+    /// in the expression make it true
+    Exists(Vec<String>, Box<ItemExpr>),
 }
 
 impl ItemExpr {
     pub fn from_expr(item: Expr) -> Result<Self, PermissionParseError> {
+        Self::from_expr_internal(item, false, None)
+    }
+
+    /// Like `from_expr`, but keeps `{var}` placeholders to be bound later with `PermissionItem::bind`
+    pub fn from_expr_pattern(item: Expr) -> Result<Self, PermissionParseError> {
+        Self::from_expr_internal(item, true, None)
+    }
+
+    fn item_from_permissions(
+        permissions: Permissions,
+        allow_var: bool,
+    ) -> Result<PermissionItem, PermissionParseError> {
+        Ok(PermissionItem {
+            perm: token_converter(permissions)?
+                .iter()
+                .map(|field| match field {
+                    tokenizer::Field::VarKind(..) if !allow_var => {
+                        Err(PermissionParseError::InvalidOutput(
+                            "Cannot use variable encapsulation when parsing in string form"
+                                .to_string(),
+                        ))
+                    }
+                    field => Ok(field.clone()),
+                })
+                .collect::<Result<Vec<tokenizer::Field>, PermissionParseError>>()?,
+            validity: Validity::default(),
+            condition: None,
+        })
+    }
+
+    fn from_call(
+        name: Ident,
+        args: Vec<Permissions>,
+        allow_var: bool,
+        library: Option<&ExprLibrary>,
+    ) -> Result<Self, PermissionParseError> {
+        let args = args
+            .into_iter()
+            .map(|arg| Ok(Self::item_from_permissions(arg, allow_var)?.perm.remove(0)))
+            .collect::<Result<Vec<tokenizer::Field>, PermissionParseError>>()?;
+        match library {
+            Some(library) => Ok(Self::Bracketed(Box::new(
+                library.call(&name.to_string(), args)?,
+            ))),
+            None => Err(format!("Unknown expression `{}`", name).into()),
+        }
+    }
+
+    /// Calls are resolved with `library`, or fail without one
+    pub(crate) fn from_expr_internal(
+        item: Expr,
+        allow_var: bool,
+        library: Option<&ExprLibrary>,
+    ) -> Result<Self, PermissionParseError> {
         Ok(match item {
-            Expr::Permission(p) => Self::Permission(PermissionItem {
-                perm: token_converter(p)?
-                    .iter()
-                    .map(|field| match field {
-                        tokenizer::Field::VarKind(_, _) => {
-                            Err(PermissionParseError::InvalidOutput(
-                                "Cannot use variable encapsulation when parsing in string form"
-                                    .to_string(),
-                            ))
-                        }
-                        field @ _ => Ok(field.clone()),
-                    })
-                    .collect::<Result<Vec<tokenizer::Field>, PermissionParseError>>()?
-                    .into(),
-            }),
-            Expr::Not(n) => Self::Not(Box::new(Self::from_expr(*n)?)),
-            Expr::And(l, r) => Self::And(
-                Box::new(Self::from_expr(*l)?),
-                Box::new(Self::from_expr(*r)?),
-            ),
-            Expr::Or(l, r) => Self::Or(
-                Box::new(Self::from_expr(*l)?),
-                Box::new(Self::from_expr(*r)?),
-            ),
-            Expr::Xor(l, r) => Self::Xor(
-                Box::new(Self::from_expr(*l)?),
-                Box::new(Self::from_expr(*r)?),
-            ),
-            Expr::Bracketed(b) => Self::Bracketed(Box::new(Self::from_expr(*b)?)),
+            Expr::Permission(p) => Self::Permission(Self::item_from_permissions(p, allow_var)?),
+            Expr::Predicate(condition) => Self::Predicate(condition),
+            Expr::Not(n) => Self::Not(Box::new(Self::from_expr_internal(*n, allow_var, library)?)),
+            Expr::And(operands) => Self::And(Self::from_operands(operands, allow_var, library)?),
+            Expr::Or(operands) => Self::Or(Self::from_operands(operands, allow_var, library)?),
+            Expr::Xor(operands) => Self::Xor(Self::from_operands(operands, allow_var, library)?),
+            Expr::Bracketed(b) => {
+                Self::Bracketed(Box::new(Self::from_expr_internal(*b, allow_var, library)?))
+            }
+            Expr::AtLeast(count, operands) => {
+                Self::AtLeast(count, Self::from_operands(operands, allow_var, library)?)
+            }
+            Expr::Call(name, args) => Self::from_call(name, args, allow_var, library)?,
+            Expr::Exists(vars, body) => Self::from_exists(vars, *body, allow_var, library)?,
         })
     }
+
+    fn from_operands(
+        operands: Vec<Expr>,
+        allow_var: bool,
+        library: Option<&ExprLibrary>,
+    ) -> Result<Vec<Self>, PermissionParseError> {
+        operands
+            .into_iter()
+            .map(|operand| Self::from_expr_internal(operand, allow_var, library))
+            .collect()
+    }
+
+    // The body may use the quantified placeholders even where others are not allowed
+    fn from_exists(
+        vars: Vec<Ident>,
+        body: Expr,
+        allow_var: bool,
+        library: Option<&ExprLibrary>,
+    ) -> Result<Self, PermissionParseError> {
+        let body = Self::from_expr_internal(body, true, library)?;
+        let exists = Self::Exists(vars.iter().map(|v| v.to_string()).collect(), Box::new(body));
+        match exists.vars().first() {
+            Some(var) if !allow_var => Err(format!(
+                "Variable `{{{}}}` is not bound by `exists` and placeholders cannot be used when parsing in string form",
+                var
+            )
+            .into()),
+            _ => Ok(exists),
+        }
+    }
+
+    /// The `{var}` placeholders not bound by an `exists`, in order of first appearance
+    pub fn vars(&self) -> Vec<String> {
+        let mut vars = vec![];
+        self.collect_vars(&mut vec![], &mut vars);
+        vars
+    }
+
+    fn collect_vars(&self, bound: &mut Vec<String>, vars: &mut Vec<String>) {
+        match self {
+            ItemExpr::Permission(p) => {
+                for field in &p.perm {
+                    if let tokenizer::Field::VarKind(_, ident, _) = field {
+                        let name = ident.to_string();
+                        if !bound.contains(&name) && !vars.contains(&name) {
+                            vars.push(name);
+                        }
+                    }
+                }
+            }
+            ItemExpr::Predicate(_) => {}
+            ItemExpr::Not(e) | ItemExpr::Bracketed(e) => e.collect_vars(bound, vars),
+            ItemExpr::And(operands)
+            | ItemExpr::Or(operands)
+            | ItemExpr::Xor(operands)
+            | ItemExpr::AtLeast(_, operands) => {
+                operands.iter().for_each(|e| e.collect_vars(bound, vars))
+            }
+            ItemExpr::Exists(quantified, body) => {
+                let outer = bound.len();
+                bound.extend(quantified.iter().cloned());
+                body.collect_vars(bound, vars);
+                bound.truncate(outer);
+            }
+        }
+    }
+
+    /// Replaces the `{var}` placeholders which have a value in `bindings` and are not bound by
+    /// an `exists`, others are kept
+    pub fn bind(&self, bindings: &Bindings) -> ItemExpr {
+        let operands = |operands: &[ItemExpr]| operands.iter().map(|e| e.bind(bindings)).collect();
+        match self {
+            ItemExpr::Permission(p) => ItemExpr::Permission(p.bind_known(bindings)),
+            ItemExpr::Predicate(condition) => ItemExpr::Predicate(condition.clone()),
+            ItemExpr::Not(e) => ItemExpr::Not(Box::new(e.bind(bindings))),
+            ItemExpr::Bracketed(e) => ItemExpr::Bracketed(Box::new(e.bind(bindings))),
+            ItemExpr::And(chain) => ItemExpr::And(operands(chain)),
+            ItemExpr::Or(chain) => ItemExpr::Or(operands(chain)),
+            ItemExpr::Xor(chain) => ItemExpr::Xor(operands(chain)),
+            ItemExpr::AtLeast(count, chain) => ItemExpr::AtLeast(*count, operands(chain)),
+            ItemExpr::Exists(quantified, body) => {
+                let mut inner = bindings.clone();
+                quantified.iter().for_each(|var| {
+                    inner.remove(var);
+                });
+                ItemExpr::Exists(quantified.clone(), Box::new(body.bind(&inner)))
+            }
+        }
+    }
+}
+
+// Operators nest to the right and `!` and `exists` cover everything after them, so an operand
+// before the last needs brackets when it is itself an operator, a `!` or an `exists`, and the
+// last one when it is the same operator, which would otherwise join the chain
+impl Display for ItemExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (operands, op) = match self {
+            ItemExpr::Permission(p) => return write!(f, "{}", p),
+            ItemExpr::Predicate(condition) => return write!(f, "ctx({})", condition),
+            ItemExpr::Not(e) => return write!(f, "!{}", e),
+            ItemExpr::Bracketed(e) => return write!(f, "({})", e),
+            ItemExpr::Exists(vars, body) => {
+                return write!(f, "exists {}: {}", vars.join(", "), body);
+            }
+            ItemExpr::AtLeast(count, operands) => {
+                let list = operands
+                    .iter()
+                    .map(|operand| operand.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                return match *count {
+                    1 => write!(f, "any({})", list),
+                    count if count == operands.len() => write!(f, "all({})", list),
+                    count => write!(f, "atleast({}, {})", count, list),
+                };
+            }
+            ItemExpr::And(operands) => (operands, " & "),
+            ItemExpr::Or(operands) => (operands, " | "),
+            ItemExpr::Xor(operands) => (operands, " ^ "),
+        };
+        for (idx, operand) in operands.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(op)?;
+            }
+            let bracketed = match operand {
+                ItemExpr::Permission(_)
+                | ItemExpr::Predicate(_)
+                | ItemExpr::Bracketed(_)
+                | ItemExpr::AtLeast(_, _) => false,
+                _ if idx + 1 < operands.len() => true,
+                operand => std::mem::discriminant(operand) == std::mem::discriminant(self),
+            };
+            match bracketed {
+                true => write!(f, "({})", operand)?,
+                false => write!(f, "{}", operand)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn parse_expr_str(
+    expr: &str,
+    limits: &ParseLimits,
+) -> Result<Expr, PermissionParseError> {
+    check_input(expr, limits).map_err(PermissionParseError::LimitExceeded)?;
+    let mut limiter = Limiter::new(*limits);
+    let result = (|input: ParseStream| parse_expr(input, &mut limiter, 1)).parse_str(expr);
+    match (result, limiter.exceeded) {
+        (Ok((expr, _)), _) => Ok(expr),
+        (Err(_), Some(limit)) => Err(PermissionParseError::LimitExceeded(limit)),
+        (Err(err), None) => Err(err.into()),
+    }
 }
 
 pub fn expr_parse(expr: &str) -> Result<ItemExpr, PermissionParseError> {
-    let result: Expr = parse_str(expr)?;
-    return ItemExpr::from_expr(result);
+    expr_parse_with_limits(expr, &ParseLimits::default())
+}
+
+/// Parses an expression from untrusted input, failing with `LimitExceeded` instead of using
+/// unbounded memory or stack
+pub fn expr_parse_with_limits(
+    expr: &str,
+    limits: &ParseLimits,
+) -> Result<ItemExpr, PermissionParseError> {
+    ItemExpr::from_expr(parse_expr_str(expr, limits)?)
+}
+
+/// Parses an expression which may contain `{var}` placeholders
+pub fn expr_parse_pattern(expr: &str) -> Result<ItemExpr, PermissionParseError> {
+    ItemExpr::from_expr_pattern(parse_expr_str(expr, &ParseLimits::default())?)
 }
 
 #[test]
 fn test_parse() {
-    parse(&"a.b.c.***.d".to_string()).unwrap();
+    parse("a.b.c.***.d").unwrap();
+}
+
+#[test]
+fn test_parse_invalid_literal() {
+    // Found by fuzzing, integer literals which are no ID used to panic
+    assert!(parse("org.6esur.read").is_err());
+    assert!(parse("org.007").is_err());
+    assert!(expr_parse("org.1u8 | org.2").is_err());
 }
@@ -0,0 +1,369 @@
+use crate::{condition::Condition, parser::ItemExpr, parser::PermissionItem, tokenizer::Field};
+use std::fmt::{self, Display, Formatter};
+
+/// `ItemExpr` in negation normal form, built by `ItemExpr::simplify`. Chains of the same operator
+/// are flattened into one node, brackets are dropped and `Not` only wraps a permission, a
+/// predicate or an `Exists`. `True` and `False` only appear at the top, when the whole expression
+/// folds away, and are displayed as the empty `all()` and `any()`.
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum NormalExpr {
+    True,
+    False,
+    Permission(PermissionItem),
+    Predicate(Condition),
+    Not(Box<NormalExpr>),
+    And(Vec<NormalExpr>),
+    Or(Vec<NormalExpr>),
+    Xor(Vec<NormalExpr>),
+    /// At least this many operands, more than one and fewer than all of them
+    AtLeast(usize, Vec<NormalExpr>),
+    /// Some values of the variables make the body true. The body is never constant, uses every
+    /// variable and is neither an `|` nor an `&` of operands without a variable in common, those
+    /// being split into an `exists` per operand
+    Exists(Vec<String>, Box<NormalExpr>),
+}
+
+impl ItemExpr {
+    /// Normalises the expression: nested `&`, `|` and `^` are flattened, `!` is pushed down to
+    /// the leaves with De Morgan, repeated operands are removed, and an operand next to its own
+    /// negation folds the operator, so `a & !a` is `False` and `a ^ a` drops both.
+    pub fn simplify(&self) -> NormalExpr {
+        normalize(self, false)
+    }
+}
+
+fn normalize_all(operands: &[ItemExpr], negate: bool) -> Vec<NormalExpr> {
+    operands.iter().map(|e| normalize(e, negate)).collect()
+}
+
+fn normalize(expr: &ItemExpr, negate: bool) -> NormalExpr {
+    match expr {
+        ItemExpr::Permission(p) => leaf(NormalExpr::Permission(p.clone()), negate),
+        ItemExpr::Predicate(c) => leaf(NormalExpr::Predicate(c.clone()), negate),
+        ItemExpr::Not(e) => normalize(e, !negate),
+        ItemExpr::Bracketed(e) => normalize(e, negate),
+        ItemExpr::And(operands) if negate => or(normalize_all(operands, true)),
+        ItemExpr::And(operands) => and(normalize_all(operands, false)),
+        ItemExpr::Or(operands) if negate => and(normalize_all(operands, true)),
+        ItemExpr::Or(operands) => or(normalize_all(operands, false)),
+        // Negating any operand negates the result, the first one keeps the output order stable
+        ItemExpr::Xor(operands) => xor(operands
+            .iter()
+            .enumerate()
+            .map(|(idx, e)| normalize(e, negate && idx == 0))
+            .collect()),
+        // Fewer than `count` operands hold exactly when more than `len - count` of them fail
+        ItemExpr::AtLeast(count, operands) => {
+            let count = match negate {
+                true => (operands.len() + 1).saturating_sub(*count),
+                false => *count,
+            };
+            at_least(count, normalize_all(operands, negate))
+        }
+        // There is always a value to try, so a constant body decides the result on its own
+        ItemExpr::Exists(vars, body) => match normalize(body, false) {
+            constant @ (NormalExpr::True | NormalExpr::False) => leaf_constant(constant, negate),
+            body => match quantify(vars, body) {
+                NormalExpr::Exists(vars, body) => leaf(NormalExpr::Exists(vars, body), negate),
+                split if negate => split.negate(),
+                split => split,
+            },
+        },
+    }
+}
+
+// `exists` distributes over `|`, and over `&` between operands with no placeholder in common,
+// so placeholders used apart are tried apart: `exists u, v: a(u) & b(v)` becomes
+// `(exists u: a(u)) & (exists v: b(v))`, trying the values of `u` and then those of `v` instead of
+// every pair. Placeholders the body does not use are dropped.
+fn quantify(vars: &[String], body: NormalExpr) -> NormalExpr {
+    let used = |expr: &NormalExpr| -> Vec<String> {
+        let free = expr.free_vars();
+        vars.iter()
+            .filter(|var| free.contains(var))
+            .cloned()
+            .collect()
+    };
+    let body_vars = used(&body);
+    if body_vars.is_empty() {
+        return body;
+    }
+    match body {
+        NormalExpr::Or(operands) => or(operands
+            .into_iter()
+            .map(|operand| quantify(&body_vars, operand))
+            .collect()),
+        NormalExpr::And(operands) => {
+            // Operands sharing a placeholder, directly or through others, stay together
+            let mut groups: Vec<(Vec<String>, Vec<NormalExpr>)> = vec![];
+            for operand in operands {
+                let mut group = (used(&operand), vec![operand]);
+                let mut idx = 0;
+                while idx < groups.len() {
+                    match groups[idx].0.iter().any(|var| group.0.contains(var)) {
+                        true => {
+                            let (vars, operands) = groups.remove(idx);
+                            for var in vars {
+                                if !group.0.contains(&var) {
+                                    group.0.push(var);
+                                }
+                            }
+                            group.1.splice(0..0, operands);
+                        }
+                        false => idx += 1,
+                    }
+                }
+                groups.push(group);
+            }
+            if groups.len() == 1 {
+                let (_, operands) = groups.pop().unwrap();
+                return NormalExpr::Exists(body_vars, Box::new(NormalExpr::And(operands)));
+            }
+            and(groups
+                .into_iter()
+                .map(|(_, operands)| quantify(&body_vars, and(operands)))
+                .collect())
+        }
+        body => NormalExpr::Exists(body_vars, Box::new(body)),
+    }
+}
+
+fn leaf_constant(constant: NormalExpr, negate: bool) -> NormalExpr {
+    match negate {
+        true => constant.negate(),
+        false => constant,
+    }
+}
+
+fn leaf(expr: NormalExpr, negate: bool) -> NormalExpr {
+    match negate {
+        true => NormalExpr::Not(Box::new(expr)),
+        false => expr,
+    }
+}
+
+impl NormalExpr {
+    /// The negation, again in normal form. Operands of a normal form are distinct and no two
+    /// negate each other, which stays true for their negations, so nothing needs to fold again.
+    pub fn negate(&self) -> NormalExpr {
+        match self {
+            NormalExpr::True => NormalExpr::False,
+            NormalExpr::False => NormalExpr::True,
+            NormalExpr::Permission(_) | NormalExpr::Predicate(_) | NormalExpr::Exists(_, _) => {
+                NormalExpr::Not(Box::new(self.clone()))
+            }
+            NormalExpr::Not(e) => (**e).clone(),
+            NormalExpr::And(operands) => {
+                NormalExpr::Or(operands.iter().map(NormalExpr::negate).collect())
+            }
+            NormalExpr::Or(operands) => {
+                NormalExpr::And(operands.iter().map(NormalExpr::negate).collect())
+            }
+            NormalExpr::Xor(operands) => {
+                let mut operands = operands.clone();
+                operands[0] = operands[0].negate();
+                NormalExpr::Xor(operands)
+            }
+            NormalExpr::AtLeast(count, operands) => NormalExpr::AtLeast(
+                operands.len() + 1 - count,
+                operands.iter().map(NormalExpr::negate).collect(),
+            ),
+        }
+    }
+
+    /// The placeholders the expression uses without an `exists` of its own quantifying them
+    pub fn free_vars(&self) -> Vec<String> {
+        let mut vars = vec![];
+        self.collect_free_vars(&[], &mut vars);
+        vars
+    }
+
+    fn collect_free_vars(&self, bound: &[String], vars: &mut Vec<String>) {
+        match self {
+            NormalExpr::True | NormalExpr::False | NormalExpr::Predicate(_) => {}
+            NormalExpr::Permission(p) => {
+                for field in &p.perm {
+                    if let Field::VarKind(_, ident, _) = field {
+                        let name = ident.to_string();
+                        if !bound.contains(&name) && !vars.contains(&name) {
+                            vars.push(name);
+                        }
+                    }
+                }
+            }
+            NormalExpr::Not(e) => e.collect_free_vars(bound, vars),
+            NormalExpr::And(operands)
+            | NormalExpr::Or(operands)
+            | NormalExpr::Xor(operands)
+            | NormalExpr::AtLeast(_, operands) => operands
+                .iter()
+                .for_each(|e| e.collect_free_vars(bound, vars)),
+            NormalExpr::Exists(quantified, body) => {
+                let bound = [bound, quantified.as_slice()].concat();
+                body.collect_free_vars(&bound, vars);
+            }
+        }
+    }
+
+    fn is_operator(&self) -> bool {
+        matches!(
+            self,
+            NormalExpr::And(_) | NormalExpr::Or(_) | NormalExpr::Xor(_)
+        )
+    }
+}
+
+// Builds an `&` (absorbing `False`, dropping `True`) or, with the constants swapped, an `|`
+fn junction(
+    operands: Vec<NormalExpr>,
+    identity: NormalExpr,
+    absorbing: NormalExpr,
+    flatten: fn(NormalExpr) -> Vec<NormalExpr>,
+    build: fn(Vec<NormalExpr>) -> NormalExpr,
+) -> NormalExpr {
+    let mut flat: Vec<NormalExpr> = vec![];
+    for operand in operands {
+        for operand in flatten(operand) {
+            if operand == absorbing {
+                return absorbing;
+            }
+            if operand == identity || flat.contains(&operand) {
+                continue;
+            }
+            flat.push(operand);
+        }
+    }
+    let negated: Vec<NormalExpr> = flat.iter().map(NormalExpr::negate).collect();
+    if negated.iter().any(|n| flat.contains(n)) {
+        return absorbing;
+    }
+    match flat.len() {
+        0 => identity,
+        1 => flat.pop().unwrap(),
+        _ => build(flat),
+    }
+}
+
+fn and(operands: Vec<NormalExpr>) -> NormalExpr {
+    junction(
+        operands,
+        NormalExpr::True,
+        NormalExpr::False,
+        |e| match e {
+            NormalExpr::And(nested) => nested,
+            e => vec![e],
+        },
+        NormalExpr::And,
+    )
+}
+
+fn or(operands: Vec<NormalExpr>) -> NormalExpr {
+    junction(
+        operands,
+        NormalExpr::False,
+        NormalExpr::True,
+        |e| match e {
+            NormalExpr::Or(nested) => nested,
+            e => vec![e],
+        },
+        NormalExpr::Or,
+    )
+}
+
+// `x ^ x` cancels out and `x ^ !x` is `True`, constants are collected into one parity which
+// negates the result at the end
+fn xor(operands: Vec<NormalExpr>) -> NormalExpr {
+    let mut flat: Vec<NormalExpr> = vec![];
+    let mut parity = false;
+    let mut push = |flat: &mut Vec<NormalExpr>, operand: NormalExpr| match operand {
+        NormalExpr::True => parity = !parity,
+        NormalExpr::False => {}
+        operand => {
+            if let Some(idx) = flat.iter().position(|e| *e == operand) {
+                flat.remove(idx);
+            } else if let Some(idx) = flat.iter().position(|e| *e == operand.negate()) {
+                flat.remove(idx);
+                parity = !parity;
+            } else {
+                flat.push(operand);
+            }
+        }
+    };
+    for operand in operands {
+        match operand {
+            NormalExpr::Xor(nested) => nested.into_iter().for_each(|e| push(&mut flat, e)),
+            operand => push(&mut flat, operand),
+        }
+    }
+    let result = match flat.len() {
+        0 => NormalExpr::False,
+        1 => flat.pop().unwrap(),
+        _ => NormalExpr::Xor(flat),
+    };
+    match parity {
+        true => result.negate(),
+        false => result,
+    }
+}
+
+// Operands are counted, so repeated ones stay, only constants fold into the count
+fn at_least(mut count: usize, operands: Vec<NormalExpr>) -> NormalExpr {
+    let mut rest = vec![];
+    for operand in operands {
+        match operand {
+            NormalExpr::True => count = count.saturating_sub(1),
+            NormalExpr::False => {}
+            operand => rest.push(operand),
+        }
+    }
+    match count {
+        0 => NormalExpr::True,
+        count if count > rest.len() => NormalExpr::False,
+        1 => or(rest),
+        count if count == rest.len() => and(rest),
+        count => NormalExpr::AtLeast(count, rest),
+    }
+}
+
+// The grammar has no precedence and `!` and `exists` cover everything after them, so nested
+// operators and a `!` or an `exists` followed by more operands are bracketed, nothing else is
+impl Display for NormalExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (operands, op) = match self {
+            NormalExpr::AtLeast(count, operands) => {
+                let list = operands
+                    .iter()
+                    .map(|operand| operand.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                return write!(f, "atleast({}, {})", count, list);
+            }
+            NormalExpr::True => return write!(f, "all()"),
+            NormalExpr::False => return write!(f, "any()"),
+            NormalExpr::Permission(p) => return write!(f, "{}", p),
+            NormalExpr::Predicate(c) => return write!(f, "ctx({})", c),
+            NormalExpr::Not(e) => return write!(f, "!{}", e),
+            NormalExpr::Exists(vars, body) => {
+                return write!(f, "exists {}: {}", vars.join(", "), body);
+            }
+            NormalExpr::And(operands) => (operands, " & "),
+            NormalExpr::Or(operands) => (operands, " | "),
+            NormalExpr::Xor(operands) => (operands, " ^ "),
+        };
+        for (idx, operand) in operands.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "{}", op)?;
+            }
+            let last = idx + 1 == operands.len();
+            match operand {
+                NormalExpr::Not(_) | NormalExpr::Exists(_, _) if !last => {
+                    write!(f, "({})", operand)?
+                }
+                operand if operand.is_operator() => write!(f, "({})", operand)?,
+                operand => write!(f, "{}", operand)?,
+            }
+        }
+        Ok(())
+    }
+}
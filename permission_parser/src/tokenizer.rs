@@ -1,7 +1,12 @@
+use crate::PermissionParseError;
 use proc_macro2::Span;
+use std::{
+    fmt::{self, Display, Formatter},
+    hash::{Hash, Hasher},
+};
 use syn::Ident;
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub enum Specifier {
     ListSpecifier { specifier: ListSpecifier },
@@ -11,14 +16,14 @@ pub enum Specifier {
     Enact,
 }
 
-impl ToString for Specifier {
-    fn to_string(&self) -> String {
+impl Display for Specifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Specifier::ListSpecifier { specifier } => specifier.to_string(),
-            Specifier::Assign => "assign".to_string(),
-            Specifier::Read => "read".to_string(),
-            Specifier::Write => "write".to_string(),
-            Specifier::Enact => "enact".to_string(),
+            Specifier::ListSpecifier { specifier } => write!(f, "{}", specifier),
+            Specifier::Assign => write!(f, "assign"),
+            Specifier::Read => write!(f, "read"),
+            Specifier::Write => write!(f, "write"),
+            Specifier::Enact => write!(f, "enact"),
         }
     }
 }
@@ -28,7 +33,7 @@ impl From<ListSpecifier> for Specifier {
         Self::ListSpecifier { specifier: value }
     }
 }
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub enum ListSpecifier {
     Add,
@@ -37,13 +42,13 @@ pub enum ListSpecifier {
     ListAll,
 }
 
-impl ToString for ListSpecifier {
-    fn to_string(&self) -> String {
+impl Display for ListSpecifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            ListSpecifier::Add => "add".to_string(),
-            ListSpecifier::ListAll => "list_all".to_string(),
-            ListSpecifier::ReadOne => "read_one".to_string(),
-            ListSpecifier::Remove => "remove".to_string(),
+            ListSpecifier::Add => write!(f, "add"),
+            ListSpecifier::ListAll => write!(f, "list_all"),
+            ListSpecifier::ReadOne => write!(f, "read_one"),
+            ListSpecifier::Remove => write!(f, "remove"),
         }
     }
 }
@@ -57,7 +62,429 @@ pub enum Field {
     Glob,       // Qualify for Name, ID
     DoubleGlob, // Qualify for Name, ID and Specifier
     TripleGlob, // Qualify for Name, ID and Specifier for any length (can only appear once)
-    VarKind(Span, Ident),
+    VarKind(Span, Ident, VarType),
+    Prefix { prefix: String }, // `team_*`, names starting with the prefix
+    Range { start: u64, end: u64 }, // `100..200`, IDs from `start` up to but excluding `end`
+    Alternatives { fields: Vec<Field> }, // `{read,write}`, any of the names and IDs, or specifiers
+    LargeID { id: u128 },      // IDs above `u64::MAX`, such as 128-bit snowflakes
+    Uuid { uuid: u128 },       // `"550e8400-e29b-41d4-a716-446655440000"uuid`
+}
+
+/// The values a `{var}` placeholder accepts, `{var:id}` and `{var:name}` force a kind
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum VarType {
+    Any,
+    Id,
+    Name,
+}
+
+impl VarType {
+    /// Whether a placeholder of this type can be bound to `value`, a `*` standing for any value
+    pub fn accepts(&self, value: &Field) -> bool {
+        match (self, value) {
+            (VarType::Any, _) | (_, Field::Glob) => true,
+            (_, Field::Alternatives { fields }) => fields.iter().all(|field| self.accepts(field)),
+            (VarType::Id, Field::ID { id: _ } | Field::LargeID { id: _ } | Field::Range { .. }) => {
+                true
+            }
+            (VarType::Name, Field::Name { name: _ } | Field::Prefix { prefix: _ }) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for VarType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VarType::Any => Ok(()),
+            VarType::Id => write!(f, ":id"),
+            VarType::Name => write!(f, ":name"),
+        }
+    }
+}
+
+/// Converts the value of a `{var}` placeholder in `perm_parser!` and `perm_expr!` into its
+/// segment. Integers become IDs, strings become names, whatever they hold, and specifiers and
+/// fields are used as they are; implement it for your own types to use them as placeholders.
+/// A value without a segment, like a negative integer, is an `Err`, which only `try_perm_parser!`
+/// can return, see `InfallibleField`.
+pub trait IntoField {
+    fn to_field(&self) -> Result<Field, PermissionParseError>;
+}
+
+/// Converts the value of a `{var:id}` placeholder into an ID
+pub trait IntoId {
+    fn to_id(&self) -> Result<u128, PermissionParseError>;
+}
+
+/// Converts the value of a `{var:name}` placeholder into a name
+pub trait IntoName {
+    fn to_name(&self) -> String;
+}
+
+/// Values whose conversion cannot fail, the only ones `perm_parser!` and `perm_expr!` take in a
+/// `{var}` or `{var:id}` placeholder. Signed integers are left out, as a negative one is not an
+/// ID: use `try_perm_parser!` for them, which returns the error. Implement it next to `IntoField`
+/// or `IntoId` for your own types when their conversion always succeeds.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` may fail to convert, so it cannot fill a placeholder of `perm_parser!` or `perm_expr!`",
+    label = "use `try_perm_parser!` for this value",
+    note = "a negative integer is not an ID, so signed integers need `try_perm_parser!` or an unsigned type"
+)]
+pub trait InfallibleField {}
+
+macro_rules! unsigned_id {
+    ($($t:ty),*) => {
+        $(
+            impl IntoId for $t {
+                fn to_id(&self) -> Result<u128, PermissionParseError> {
+                    Ok(*self as u128)
+                }
+            }
+
+            impl InfallibleField for $t {}
+        )*
+    };
+}
+
+// Signed integers are accepted by `try_perm_parser!` as long as they are not negative
+macro_rules! signed_id {
+    ($($t:ty),*) => {
+        $(
+            impl IntoId for $t {
+                fn to_id(&self) -> Result<u128, PermissionParseError> {
+                    u128::try_from(*self)
+                        .map_err(|_| format!("An ID cannot be negative, got `{}`", self).into())
+                }
+            }
+        )*
+    };
+}
+
+unsigned_id!(u8, u16, u32, u64, u128, usize);
+signed_id!(i8, i16, i32, i64, i128, isize);
+
+impl<T: IntoId + ?Sized> IntoId for &T {
+    fn to_id(&self) -> Result<u128, PermissionParseError> {
+        (**self).to_id()
+    }
+}
+
+macro_rules! id_field {
+    ($($t:ty),*) => {
+        $(
+            impl IntoField for $t {
+                fn to_field(&self) -> Result<Field, PermissionParseError> {
+                    Ok(Field::id(self.to_id()?))
+                }
+            }
+
+            impl IntoName for $t {
+                fn to_name(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+id_field!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+
+impl IntoName for str {
+    fn to_name(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl IntoName for String {
+    fn to_name(&self) -> String {
+        self.clone()
+    }
+}
+
+impl<T: IntoName + ?Sized> IntoName for &T {
+    fn to_name(&self) -> String {
+        (**self).to_name()
+    }
+}
+
+impl InfallibleField for str {}
+
+impl InfallibleField for String {}
+
+impl InfallibleField for Specifier {}
+
+impl InfallibleField for ListSpecifier {}
+
+impl InfallibleField for Field {}
+
+#[cfg(feature = "uuid")]
+impl InfallibleField for uuid::Uuid {}
+
+impl<T: InfallibleField + ?Sized> InfallibleField for &T {}
+
+impl IntoField for str {
+    fn to_field(&self) -> Result<Field, PermissionParseError> {
+        Ok(Field::Name {
+            name: self.to_string(),
+        })
+    }
+}
+
+impl IntoField for String {
+    fn to_field(&self) -> Result<Field, PermissionParseError> {
+        self.as_str().to_field()
+    }
+}
+
+impl IntoField for Specifier {
+    fn to_field(&self) -> Result<Field, PermissionParseError> {
+        Ok((*self).into())
+    }
+}
+
+impl IntoField for ListSpecifier {
+    fn to_field(&self) -> Result<Field, PermissionParseError> {
+        Ok((*self).into())
+    }
+}
+
+impl IntoField for Field {
+    fn to_field(&self) -> Result<Field, PermissionParseError> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl IntoField for uuid::Uuid {
+    fn to_field(&self) -> Result<Field, PermissionParseError> {
+        Ok(Field::Uuid {
+            uuid: self.as_u128(),
+        })
+    }
+}
+
+impl<T: IntoField + ?Sized> IntoField for &T {
+    fn to_field(&self) -> Result<Field, PermissionParseError> {
+        (**self).to_field()
+    }
+}
+
+fn unresolved(name: &str, ty: VarType) -> Field {
+    Field::VarKind(Span::call_site(), Ident::new(name, Span::call_site()), ty)
+}
+
+// The placeholders of `exists` and `perm_def!` bodies hold fields, which fill a typed placeholder
+// when they are of its kind and otherwise leave it unresolved, so the permission is not granted
+fn typed_field(value: &Field, name: &str, ty: VarType) -> Field {
+    match ty.accepts(value) {
+        true => value.clone(),
+        false => unresolved(name, ty),
+    }
+}
+
+/// Checks at compile time that `perm_parser!` and `perm_expr!` can fill a `{var}` or `{var:id}`
+/// placeholder with the value
+#[doc(hidden)]
+pub fn infallible<T: InfallibleField + ?Sized>(_: &T) {}
+
+/// Fills a placeholder of `perm_parser!` and `perm_expr!` with its converted value. The value is
+/// an `InfallibleField`, so an `Err` only comes from an implementation breaking that promise and
+/// leaves the placeholder unresolved, so the permission matches nothing.
+#[doc(hidden)]
+pub fn placeholder(value: Result<Field, PermissionParseError>, name: &str, ty: VarType) -> Field {
+    value.unwrap_or_else(|_| unresolved(name, ty))
+}
+
+/// Fills a `{var:id}` placeholder in the macros, which call it as a method so references are
+/// dereferenced
+#[doc(hidden)]
+pub trait IdPlaceholder {
+    fn id_placeholder(&self, name: &str) -> Result<Field, PermissionParseError>;
+}
+
+impl<T: IntoId + ?Sized> IdPlaceholder for T {
+    fn id_placeholder(&self, _: &str) -> Result<Field, PermissionParseError> {
+        Ok(Field::id(self.to_id()?))
+    }
+}
+
+impl IdPlaceholder for Field {
+    fn id_placeholder(&self, name: &str) -> Result<Field, PermissionParseError> {
+        Ok(typed_field(self, name, VarType::Id))
+    }
+}
+
+/// Fills a `{var:name}` placeholder in the macros, like `IdPlaceholder`
+#[doc(hidden)]
+pub trait NamePlaceholder {
+    fn name_placeholder(&self, name: &str) -> Result<Field, PermissionParseError>;
+}
+
+impl<T: IntoName + ?Sized> NamePlaceholder for T {
+    fn name_placeholder(&self, _: &str) -> Result<Field, PermissionParseError> {
+        Ok(Field::Name {
+            name: self.to_name(),
+        })
+    }
+}
+
+impl NamePlaceholder for Field {
+    fn name_placeholder(&self, name: &str) -> Result<Field, PermissionParseError> {
+        Ok(typed_field(self, name, VarType::Name))
+    }
+}
+
+/// Reads a UUID in its hyphenated form like `550e8400-e29b-41d4-a716-446655440000`, as
+/// `uuid::Uuid` displays it, in either case
+pub fn parse_uuid(text: &str) -> Option<u128> {
+    let groups: Vec<&str> = text.split('-').collect();
+    if !groups.iter().map(|group| group.len()).eq([8, 4, 4, 4, 12])
+        || !groups
+            .iter()
+            .all(|group| group.bytes().all(|b| b.is_ascii_hexdigit()))
+    {
+        return None;
+    }
+    u128::from_str_radix(&groups.concat(), 16).ok()
+}
+
+impl Field {
+    /// An ID of any size, `Field::ID` where it fits and `Field::LargeID` otherwise
+    pub fn id(id: u128) -> Field {
+        match u64::try_from(id) {
+            Ok(id) => Field::ID { id },
+            Err(_) => Field::LargeID { id },
+        }
+    }
+
+    /// Checks a value interpolated into a permission by `try_perm_parser!`, which must be a
+    /// single name, ID or specifier. Names that could be mistaken for more, i.e. empty ones,
+    /// ones holding a `.` or `*` and specifier keywords, are rejected, so input like `*` or
+    /// `a.b` cannot widen or deepen the permission.
+    pub fn interpolated(self) -> Result<Field, PermissionParseError> {
+        match &self {
+            Field::Name { name } if name.is_empty() => {
+                Err("An interpolated name cannot be empty".to_string().into())
+            }
+            Field::Name { name } if name.contains('.') => Err(format!(
+                "Interpolated name {:?} contains the scope separator `.`",
+                name
+            )
+            .into()),
+            Field::Name { name } if name.contains('*') => {
+                Err(format!("Interpolated name {:?} looks like a glob", name).into())
+            }
+            Field::Name { name } if SPECIFIER_KEYWORDS.contains(&name.as_str()) => {
+                Err(format!("Interpolated name {:?} is a specifier keyword", name).into())
+            }
+            Field::Name { name: _ }
+            | Field::ID { id: _ }
+            | Field::LargeID { id: _ }
+            | Field::Uuid { uuid: _ }
+            | Field::Specifier { specifier: _ } => Ok(self),
+            _ => Err(format!(
+                "Interpolated value `{}` is not a single name, ID or specifier",
+                self
+            )
+            .into()),
+        }
+    }
+
+    /// Whether the field only matches specifiers, which may only end a permission
+    pub fn is_specifier(&self) -> bool {
+        match self {
+            Field::Specifier { specifier: _ } => true,
+            Field::Alternatives { fields } => fields.iter().all(Field::is_specifier),
+            _ => false,
+        }
+    }
+
+    /// Whether every segment `other` matches is also matched by `self`, for fields standing for
+    /// a single segment (not `***` or a `{var}` placeholder)
+    pub fn covers(&self, other: &Field) -> bool {
+        match (self, other) {
+            (_, Field::VarKind(..) | Field::TripleGlob) => false,
+            (_, Field::Alternatives { fields }) => fields.iter().all(|field| self.covers(field)),
+            (Field::DoubleGlob, _) => true,
+            (_, Field::DoubleGlob) => false,
+            (Field::Glob, other) => !other.is_specifier(),
+            (Field::Alternatives { fields }, Field::Range { start, end })
+                if end.saturating_sub(*start) <= fields.len() as u64 =>
+            {
+                (*start..*end).all(|id| self.covers(&Field::ID { id }))
+            }
+            (Field::Alternatives { fields }, other) => {
+                fields.iter().any(|field| field.covers(other))
+            }
+            (Field::Prefix { prefix }, Field::Name { name }) => name.starts_with(prefix.as_str()),
+            (Field::Prefix { prefix }, Field::Prefix { prefix: other }) => {
+                other.starts_with(prefix.as_str())
+            }
+            (Field::Range { start, end }, Field::ID { id }) => start <= id && id < end,
+            (Field::Range { start, end }, Field::Range { start: s, end: e }) => {
+                start <= s && e <= end
+            }
+            (Field::ID { id }, Field::Range { start, end }) => {
+                start == id && id.checked_add(1) == Some(*end)
+            }
+            (
+                Field::Name { name: _ }
+                | Field::ID { id: _ }
+                | Field::LargeID { id: _ }
+                | Field::Uuid { uuid: _ }
+                | Field::Specifier { specifier: _ },
+                other,
+            ) => self == other,
+            _ => false,
+        }
+    }
+}
+
+// `Span` carries no equality, so variables are compared by their identifier and type only
+impl PartialEq for Field {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Field::Name { name: l }, Field::Name { name: r }) => l == r,
+            (Field::ID { id: l }, Field::ID { id: r }) => l == r,
+            (Field::Specifier { specifier: l }, Field::Specifier { specifier: r }) => l == r,
+            (Field::Glob, Field::Glob)
+            | (Field::DoubleGlob, Field::DoubleGlob)
+            | (Field::TripleGlob, Field::TripleGlob) => true,
+            (Field::VarKind(_, l, lt), Field::VarKind(_, r, rt)) => l == r && lt == rt,
+            (Field::Prefix { prefix: l }, Field::Prefix { prefix: r }) => l == r,
+            (Field::Range { start: ls, end: le }, Field::Range { start: rs, end: re }) => {
+                ls == rs && le == re
+            }
+            (Field::Alternatives { fields: l }, Field::Alternatives { fields: r }) => l == r,
+            (Field::LargeID { id: l }, Field::LargeID { id: r }) => l == r,
+            (Field::Uuid { uuid: l }, Field::Uuid { uuid: r }) => l == r,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Field {}
+
+impl Hash for Field {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Field::Name { name } => name.hash(state),
+            Field::ID { id } => id.hash(state),
+            Field::Specifier { specifier } => specifier.hash(state),
+            Field::VarKind(_, ident, ty) => (ident, ty).hash(state),
+            Field::Prefix { prefix } => prefix.hash(state),
+            Field::Range { start, end } => (start, end).hash(state),
+            Field::Alternatives { fields } => fields.hash(state),
+            Field::LargeID { id } => id.hash(state),
+            Field::Uuid { uuid } => uuid.hash(state),
+            Field::Glob | Field::DoubleGlob | Field::TripleGlob => {}
+        }
+    }
 }
 
 impl From<ListSpecifier> for Field {
@@ -74,16 +501,61 @@ impl From<Specifier> for Field {
     }
 }
 
-impl ToString for Field {
-    fn to_string(&self) -> String {
+const SPECIFIER_KEYWORDS: [&str; 8] = [
+    "read", "write", "assign", "enact", "add", "remove", "read_one", "list_all",
+];
+
+// Whether the name reads back as the same name without quotes: identifier-shaped and not a
+// specifier
+fn is_bare_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !SPECIFIER_KEYWORDS.contains(&name)
+}
+
+// Other names are quoted with Rust string escapes, which `parse` reads back
+fn write_name(f: &mut Formatter<'_>, name: &str) -> fmt::Result {
+    match is_bare_name(name) {
+        true => write!(f, "{}", name),
+        false => write!(f, "{:?}", name),
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Field::Name { name } => name.clone(),
-            Field::ID { id } => id.to_string(),
-            Field::Specifier { specifier } => specifier.to_string(),
-            Field::Glob => "*".to_string(),
-            Field::DoubleGlob => "**".to_string(),
-            Field::TripleGlob => "***".to_string(),
-            Field::VarKind(_, ident) => format!("{{{}}}", ident.to_string()),
+            Field::Name { name } => write_name(f, name),
+            Field::ID { id } => write!(f, "{}", id),
+            Field::LargeID { id } => write!(f, "{}", id),
+            Field::Uuid { uuid } => {
+                let hex = format!("{:032x}", uuid);
+                write!(
+                    f,
+                    "\"{}-{}-{}-{}-{}\"uuid",
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..]
+                )
+            }
+            Field::Specifier { specifier } => write!(f, "{}", specifier),
+            Field::Glob => write!(f, "*"),
+            Field::DoubleGlob => write!(f, "**"),
+            Field::TripleGlob => write!(f, "***"),
+            Field::VarKind(_, ident, ty) => write!(f, "{{{}{}}}", ident, ty),
+            Field::Prefix { prefix } => {
+                write_name(f, prefix)?;
+                write!(f, "*")
+            }
+            Field::Range { start, end } => write!(f, "{}..{}", start, end),
+            Field::Alternatives { fields } => {
+                let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+                write!(f, "{{{}}}", fields.join(","))
+            }
         }
     }
 }
@@ -0,0 +1,87 @@
+use crate::{PermissionGroup, PermissionItem};
+use std::time::SystemTime;
+
+/// Source of the current time for time-bounded grants, replaceable in tests
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+/// The system wall clock
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A fixed point in time
+impl Clock for SystemTime {
+    fn now(&self) -> SystemTime {
+        *self
+    }
+}
+
+/// Time window in which a grant applies, `not_before` inclusive and `expires_at` exclusive.
+/// The default is unbounded on both ends.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct Validity {
+    pub not_before: Option<SystemTime>,
+    pub expires_at: Option<SystemTime>,
+}
+
+impl Validity {
+    pub fn is_unbounded(&self) -> bool {
+        self.not_before.is_none() && self.expires_at.is_none()
+    }
+
+    pub fn is_active_at(&self, now: SystemTime) -> bool {
+        self.not_before.is_none_or(|start| start <= now) && !self.is_expired_at(now)
+    }
+
+    pub fn is_expired_at(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|end| end <= now)
+    }
+}
+
+impl PermissionItem {
+    /// Only grant from `time` onwards
+    pub fn not_before(mut self, time: SystemTime) -> Self {
+        self.validity.not_before = Some(time);
+        self
+    }
+
+    /// Stop granting at `time`
+    pub fn expires_at(mut self, time: SystemTime) -> Self {
+        self.validity.expires_at = Some(time);
+        self
+    }
+
+    pub fn is_active(&self, clock: &impl Clock) -> bool {
+        self.validity.is_active_at(clock.now())
+    }
+}
+
+impl PermissionGroup {
+    /// The grants which apply right now, for checking expressions against
+    pub fn active(&self, clock: &impl Clock) -> PermissionGroup {
+        let now = clock.now();
+        self.perms
+            .iter()
+            .filter(|p| p.validity.is_active_at(now))
+            .cloned()
+            .collect::<Vec<PermissionItem>>()
+            .into()
+    }
+
+    /// Drops grants which have expired, keeping those not active yet. Returns how many were removed.
+    pub fn prune_expired(&mut self, clock: &impl Clock) -> usize {
+        let now = clock.now();
+        let before = self.perms.len();
+        self.perms.retain(|p| !p.validity.is_expired_at(now));
+        before - self.perms.len()
+    }
+}
@@ -0,0 +1,537 @@
+// Set operations on grant patterns, over the valid permissions a grant matches with `check_one`.
+//
+// A grant without `***` matches its fields followed by any number of segments, one with `***`
+// matches the fields before it, one or more segments, then the fields after it at the very end.
+// For a fixed length every position therefore matches a `Class` independently, which makes
+// intersections and inclusion a per-position question. Specifiers may only end a permission, so
+// they are never matched before the last position. Prefixes, ranges and alternatives are literal
+// classes which match several names, IDs or specifiers.
+
+use permission_parser::{
+    PermissionGroup, PermissionItem,
+    tokenizer::{Field, ListSpecifier, Specifier},
+};
+
+const SPECIFIERS: [Specifier; 8] = [
+    Specifier::Read,
+    Specifier::Write,
+    Specifier::Assign,
+    Specifier::Enact,
+    Specifier::ListSpecifier {
+        specifier: ListSpecifier::Add,
+    },
+    Specifier::ListSpecifier {
+        specifier: ListSpecifier::Remove,
+    },
+    Specifier::ListSpecifier {
+        specifier: ListSpecifier::ReadOne,
+    },
+    Specifier::ListSpecifier {
+        specifier: ListSpecifier::ListAll,
+    },
+];
+
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+enum Class {
+    Lit(Field),
+    /// `*`, any name or ID
+    Star,
+    /// `**`, any segment
+    Any,
+}
+
+impl Class {
+    fn of(field: &Field) -> Class {
+        match field {
+            Field::Glob => Class::Star,
+            Field::DoubleGlob | Field::TripleGlob => Class::Any,
+            field => Class::Lit(field.clone()),
+        }
+    }
+
+    fn to_field(&self) -> Field {
+        match self {
+            Class::Lit(field) => field.clone(),
+            Class::Star => Field::Glob,
+            Class::Any => Field::DoubleGlob,
+        }
+    }
+
+    fn is_specifier(&self) -> bool {
+        matches!(self, Class::Lit(field) if field.is_specifier())
+    }
+
+    fn meet(&self, other: &Class, last: bool) -> Option<Class> {
+        let result = match (self, other) {
+            (Class::Any, c) | (c, Class::Any) => c.clone(),
+            (Class::Star, Class::Star) => Class::Star,
+            (Class::Star, c) | (c, Class::Star) => {
+                if c.is_specifier() {
+                    return None;
+                }
+                c.clone()
+            }
+            (Class::Lit(a), Class::Lit(b)) => Class::Lit(meet(a, b)?),
+        };
+        if result.is_specifier() && !last {
+            return None;
+        }
+        Some(result)
+    }
+
+    fn contains(&self, inner: &Class, last: bool) -> bool {
+        if inner.is_specifier() && !last {
+            return true;
+        }
+        match (self, inner) {
+            (Class::Any, _) => true,
+            (Class::Star, Class::Any) => !last,
+            (Class::Star, c) => !c.is_specifier(),
+            (Class::Lit(a), Class::Lit(b)) => a.covers(b),
+            (Class::Lit(_), _) => false,
+        }
+    }
+
+    // The part of `self` outside `other` which classes can express. All names and IDs but one
+    // have no class, so only whole classes and the specifiers of a last `**` are split off.
+    fn minus(&self, other: &Class, last: bool) -> Vec<Class> {
+        if self.meet(other, last).is_none() {
+            return vec![self.clone()];
+        }
+        if other.contains(self, last) {
+            return vec![];
+        }
+        let specifiers = SPECIFIERS
+            .iter()
+            .map(|specifier| Class::Lit((*specifier).into()))
+            .filter(|class| !other.contains(class, last));
+        match (self, other) {
+            (Class::Any, other) if other.is_specifier() => {
+                std::iter::once(Class::Star).chain(specifiers).collect()
+            }
+            (Class::Any, _) if last => specifiers.collect(),
+            _ => vec![],
+        }
+    }
+}
+
+// The segments matched by both fields, neither of them a glob
+fn meet(a: &Field, b: &Field) -> Option<Field> {
+    if a.covers(b) {
+        return Some(b.clone());
+    }
+    if b.covers(a) {
+        return Some(a.clone());
+    }
+    match (a, b) {
+        (Field::Alternatives { fields }, other) | (other, Field::Alternatives { fields }) => {
+            let mut common: Vec<Field> =
+                fields.iter().filter(|f| other.covers(f)).cloned().collect();
+            match common.len() {
+                0 => None,
+                1 => common.pop(),
+                _ => Some(Field::Alternatives { fields: common }),
+            }
+        }
+        (Field::Range { start: s1, end: e1 }, Field::Range { start: s2, end: e2 }) => {
+            let (start, end) = (*s1.max(s2), *e1.min(e2));
+            (start < end).then_some(Field::Range { start, end })
+        }
+        _ => None,
+    }
+}
+
+/// `prefix`, then at least `gap` segments of any kind (exactly none when `gap` is `None`),
+/// then `suffix`
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+struct Shape {
+    prefix: Vec<Class>,
+    gap: Option<usize>,
+    suffix: Vec<Class>,
+}
+
+impl Shape {
+    fn of(item: &PermissionItem) -> Shape {
+        let classes: Vec<Class> = item.perm.iter().map(Class::of).collect();
+        match item
+            .perm
+            .iter()
+            .position(|f| matches!(f, Field::TripleGlob))
+        {
+            Some(idx) => Shape {
+                prefix: classes[..idx].to_vec(),
+                gap: Some(1),
+                suffix: classes[idx + 1..].to_vec(),
+            },
+            None => Shape {
+                prefix: classes,
+                gap: Some(0),
+                suffix: vec![],
+            },
+        }
+    }
+
+    fn exact(classes: Vec<Class>) -> Shape {
+        Shape {
+            prefix: classes,
+            gap: None,
+            suffix: vec![],
+        }
+    }
+
+    fn min_len(&self) -> usize {
+        self.prefix.len() + self.gap.unwrap_or(0) + self.suffix.len()
+    }
+
+    fn accepts_len(&self, len: usize) -> bool {
+        match self.gap {
+            Some(_) => len >= self.min_len(),
+            None => len == self.min_len(),
+        }
+    }
+
+    fn class_at(&self, len: usize, idx: usize) -> Class {
+        if idx < self.prefix.len() {
+            self.prefix[idx].clone()
+        } else if idx >= len - self.suffix.len() {
+            self.suffix[idx + self.suffix.len() - len].clone()
+        } else {
+            Class::Any
+        }
+    }
+
+    // From this length on, both shapes only grow in their gaps
+    fn stable_len(&self, other: &Shape) -> usize {
+        let prefix = self.prefix.len().max(other.prefix.len());
+        let suffix = self.suffix.len().max(other.suffix.len());
+        (prefix + suffix + 1)
+            .max(self.min_len())
+            .max(other.min_len())
+    }
+
+    fn meet_at(&self, other: &Shape, len: usize) -> Option<Vec<Class>> {
+        (0..len)
+            .map(|idx| {
+                self.class_at(len, idx)
+                    .meet(&other.class_at(len, idx), idx + 1 == len)
+            })
+            .collect()
+    }
+
+    fn intersect(&self, other: &Shape) -> Vec<Shape> {
+        let lo = self.min_len().max(other.min_len());
+        if self.gap.is_none() || other.gap.is_none() {
+            return match self.accepts_len(lo) && other.accepts_len(lo) {
+                true => self
+                    .meet_at(other, lo)
+                    .map(Shape::exact)
+                    .into_iter()
+                    .collect(),
+                false => vec![],
+            };
+        }
+        let stable = self.stable_len(other);
+        let mut result: Vec<Shape> = (lo..stable)
+            .filter_map(|len| self.meet_at(other, len).map(Shape::exact))
+            .collect();
+        let prefix_len = self.prefix.len().max(other.prefix.len());
+        let suffix_len = self.suffix.len().max(other.suffix.len());
+        if let Some(classes) = self.meet_at(other, stable) {
+            result.push(Shape {
+                prefix: classes[..prefix_len].to_vec(),
+                gap: Some(stable - prefix_len - suffix_len),
+                suffix: classes[stable - suffix_len..].to_vec(),
+            });
+        }
+        result
+    }
+
+    // The class at each position of the permissions of length `len`, `None` when there are none
+    fn classes_at(&self, len: usize) -> Option<Vec<Class>> {
+        if !self.accepts_len(len) {
+            return None;
+        }
+        let classes: Vec<Class> = (0..len).map(|idx| self.class_at(len, idx)).collect();
+        match classes[..len.saturating_sub(1)]
+            .iter()
+            .any(Class::is_specifier)
+        {
+            true => None,
+            false => Some(classes),
+        }
+    }
+
+    // Disjoint pieces of the permissions of length `len` matched by `self` and not by `other`
+    fn difference_at(&self, other: &Shape, len: usize) -> Vec<Vec<Class>> {
+        let Some(classes) = self.classes_at(len) else {
+            return vec![];
+        };
+        let common = match other.accepts_len(len) {
+            true => self.meet_at(other, len),
+            false => None,
+        };
+        let Some(common) = common else {
+            return vec![classes];
+        };
+        let mut pieces = vec![];
+        for idx in 0..len {
+            for class in classes[idx].minus(&other.class_at(len, idx), idx + 1 == len) {
+                let mut piece = common[..idx].to_vec();
+                piece.push(class);
+                piece.extend(classes[idx + 1..].iter().cloned());
+                pieces.push(piece);
+            }
+        }
+        pieces
+    }
+
+    fn difference(&self, other: &Shape) -> Vec<Shape> {
+        if self.gap.is_none() {
+            return self
+                .difference_at(other, self.min_len())
+                .into_iter()
+                .map(Shape::exact)
+                .collect();
+        }
+        let stable = self.stable_len(other);
+        let mut result: Vec<Shape> = (self.min_len()..stable)
+            .flat_map(|len| self.difference_at(other, len))
+            .map(Shape::exact)
+            .collect();
+        let prefix_len = self.prefix.len().max(other.prefix.len());
+        let suffix_len = self.suffix.len().max(other.suffix.len());
+        // The segments between the prefixes and suffixes are `**` on both sides, so the pieces
+        // at the stable length stay open
+        result.extend(
+            self.difference_at(other, stable)
+                .into_iter()
+                .map(|classes| Shape {
+                    prefix: classes[..prefix_len].to_vec(),
+                    gap: Some(stable - prefix_len - suffix_len),
+                    suffix: classes[stable - suffix_len..].to_vec(),
+                }),
+        );
+        result
+    }
+
+    fn is_subset(&self, outer: &Shape) -> bool {
+        let last = match self.gap {
+            Some(_) => self.stable_len(outer),
+            None => self.min_len(),
+        };
+        (self.min_len()..=last).all(|len| {
+            self.classes_at(len).is_none_or(|classes| {
+                outer.accepts_len(len)
+                    && (0..len).all(|idx| {
+                        outer
+                            .class_at(len, idx)
+                            .contains(&classes[idx], idx + 1 == len)
+                    })
+            })
+        })
+    }
+
+    // Canonical form for comparing shapes: a `**` before the last segment matches the same as
+    // `*`, as only the last segment can be a specifier, and is part of the gap when next to it
+    fn normalize(mut self) -> Shape {
+        fn stars(classes: &mut [Class]) {
+            for class in classes.iter_mut().filter(|class| **class == Class::Any) {
+                *class = Class::Star;
+            }
+        }
+        let Some(mut gap) = self.gap else {
+            let inner = self.prefix.len().saturating_sub(1);
+            stars(&mut self.prefix[..inner]);
+            return self;
+        };
+        let inner = self.suffix.len().saturating_sub(1);
+        stars(&mut self.suffix[..inner]);
+        while self.suffix.len() > 1 && self.suffix[0] == Class::Star {
+            self.suffix.remove(0);
+            gap += 1;
+        }
+        if self.suffix == [Class::Any] {
+            self.suffix.clear();
+            gap += 1;
+        }
+        if gap > 0 || !self.suffix.is_empty() {
+            stars(&mut self.prefix);
+            // Without a suffix the gap may end in a specifier, which a `*` does not match
+            while !self.suffix.is_empty() && self.prefix.last() == Some(&Class::Star) {
+                self.prefix.pop();
+                gap += 1;
+            }
+        } else {
+            let inner = self.prefix.len().saturating_sub(1);
+            stars(&mut self.prefix[..inner]);
+        }
+        self.gap = Some(gap);
+        self
+    }
+
+    // Folds exact shapes one segment shorter than an open shape into its gap
+    fn merge(shapes: Vec<Shape>) -> Vec<Shape> {
+        let mut shapes: Vec<Shape> =
+            shapes
+                .into_iter()
+                .map(Shape::normalize)
+                .fold(vec![], |mut shapes, shape| {
+                    if !shapes.contains(&shape) {
+                        shapes.push(shape);
+                    }
+                    shapes
+                });
+        loop {
+            let found = shapes.iter().enumerate().find_map(|(open_idx, open)| {
+                // `***` needs at least one segment, so a gap of one before a suffix stays
+                let gap = open
+                    .gap
+                    .filter(|gap| *gap > 1 || *gap == 1 && open.suffix.is_empty())?;
+                let mut classes = open.prefix.clone();
+                classes.extend(std::iter::repeat_n(Class::Any, gap - 1));
+                classes.extend(open.suffix.iter().cloned());
+                let exact = Shape::exact(classes).normalize();
+                let exact_idx = shapes.iter().position(|s| *s == exact).or_else(|| {
+                    // With nothing between, the last segment of the prefix is the last of the
+                    // exact shape, where `*` matches less than the `**` of the gap after it
+                    if gap != 1 || !open.suffix.is_empty() {
+                        return None;
+                    }
+                    let (last, inner) = exact.prefix.split_last()?;
+                    shapes.iter().position(|s| {
+                        s.gap.is_none()
+                            && s.prefix.split_last().is_some_and(|(s_last, s_inner)| {
+                                s_inner == inner && s_last.contains(last, true)
+                            })
+                    })
+                })?;
+                Some((open_idx, exact_idx))
+            });
+            let Some((open_idx, exact_idx)) = found else {
+                return shapes;
+            };
+            let exact = shapes.remove(exact_idx);
+            let open_idx = open_idx - usize::from(exact_idx < open_idx);
+            let open = &mut shapes[open_idx];
+            open.gap = open.gap.map(|gap| gap - 1);
+            if open.gap == Some(0) && open.suffix.is_empty() {
+                open.prefix = exact.prefix;
+            }
+        }
+    }
+
+    // A fixed length ending in `**` has no grant, but its specifiers do
+    fn split_specifiers(self) -> Vec<Shape> {
+        if self.gap.is_some() || self.prefix.last() != Some(&Class::Any) {
+            return vec![self];
+        }
+        SPECIFIERS
+            .iter()
+            .map(|specifier| {
+                let mut classes = self.prefix.clone();
+                classes.pop();
+                classes.push(Class::Lit((*specifier).into()));
+                Shape::exact(classes)
+            })
+            .collect()
+    }
+
+    // `None` when no grant matches exactly the permissions of the shape
+    fn to_item(&self) -> Option<PermissionItem> {
+        let mut perm: Vec<Field> = self.prefix.iter().map(Class::to_field).collect();
+        match self.gap {
+            None => {
+                // Nothing can follow a specifier, so the implicit trailing segments are empty
+                if !self.prefix.last().is_some_and(Class::is_specifier) {
+                    return None;
+                }
+            }
+            Some(gap) if self.suffix.is_empty() => {
+                perm.extend(std::iter::repeat_n(Field::DoubleGlob, gap));
+            }
+            Some(0) => return None,
+            Some(gap) => {
+                perm.extend(std::iter::repeat_n(Field::DoubleGlob, gap - 1));
+                perm.push(Field::TripleGlob);
+                perm.extend(self.suffix.iter().map(Class::to_field));
+            }
+        }
+        let item: PermissionItem = perm.into();
+        item.validate().ok()?;
+        Some(item)
+    }
+}
+
+fn to_items(shapes: Vec<Shape>) -> Vec<PermissionItem> {
+    let mut items: Vec<PermissionItem> = vec![];
+    for item in Shape::merge(shapes)
+        .into_iter()
+        .flat_map(Shape::split_specifiers)
+        .filter_map(|shape| shape.to_item())
+    {
+        if !items.contains(&item) {
+            items.push(item);
+        }
+    }
+    items
+}
+
+/// Grants matching exactly the permissions both grants match. Parts which no grant can express
+/// (a fixed length ending in a name or ID, e.g. `org.1047.doc` from `org.1047` ∩ `org.***.doc`)
+/// are left out, so the result never matches more than either side.
+pub(crate) fn intersect(a: &PermissionItem, b: &PermissionItem) -> Vec<PermissionItem> {
+    to_items(Shape::of(a).intersect(&Shape::of(b)))
+}
+
+/// Whether every permission matched by `inner` is also matched by `outer`
+pub(crate) fn is_subset(inner: &PermissionItem, outer: &PermissionItem) -> bool {
+    Shape::of(inner).is_subset(&Shape::of(outer))
+}
+
+/// Whether `grant` matches every permission the pattern `require` stands for. A requirement is
+/// a single permission, so unlike a grant it matches nothing after its last field.
+pub(crate) fn grants_pattern(require: &PermissionItem, grant: &PermissionItem) -> bool {
+    let shape = match require.perm.contains(&Field::TripleGlob) {
+        true => Shape::of(require),
+        false => Shape::exact(require.perm.iter().map(Class::of).collect()),
+    };
+    shape.is_subset(&Shape::of(grant))
+}
+
+/// Set operations on grant patterns, over the valid permissions each grant matches with
+/// `check_one`. Validity and conditions are ignored and left unset on the results.
+pub trait PatternAlgebra {
+    /// Grants matching the permissions matched by both. Parts no grant can express, a fixed
+    /// length ending in a name or ID such as `org.1047.doc` from `org.1047` and `org.***.doc`,
+    /// are left out, so the result never matches more than either side.
+    fn intersect(&self, other: &PermissionItem) -> Vec<PermissionItem>;
+    /// Whether some permission is matched by both, including the parts `intersect` leaves out
+    fn overlaps(&self, other: &PermissionItem) -> bool;
+    /// Whether every permission `other` matches is also matched by `self`, i.e. `check_one(p,
+    /// self)` for every `p` with `check_one(p, other)`
+    fn subsumes(&self, other: &PermissionItem) -> bool;
+    /// Grants matching permissions matched by `self` but not by `other`. Only the parts grants
+    /// can express are kept (`org.*` without `org.1047` has none), so the result may be smaller
+    /// than the difference but never matches anything `other` matches.
+    fn difference(&self, other: &PermissionItem) -> PermissionGroup;
+}
+
+impl PatternAlgebra for PermissionItem {
+    fn intersect(&self, other: &PermissionItem) -> Vec<PermissionItem> {
+        intersect(self, other)
+    }
+
+    fn overlaps(&self, other: &PermissionItem) -> bool {
+        !Shape::of(self).intersect(&Shape::of(other)).is_empty()
+    }
+
+    fn subsumes(&self, other: &PermissionItem) -> bool {
+        is_subset(other, self)
+    }
+
+    fn difference(&self, other: &PermissionItem) -> PermissionGroup {
+        to_items(Shape::of(self).difference(&Shape::of(other))).into()
+    }
+}
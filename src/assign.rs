@@ -0,0 +1,67 @@
+use crate::algebra::is_subset;
+use permission_parser::{
+    PermissionGroup, PermissionItem,
+    tokenizer::{Field, Specifier},
+};
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct GrantChangeError {
+    /// Grants added by the change which the granter may not assign
+    pub granted: Vec<PermissionItem>,
+    /// Grants removed by the change which the granter may not assign
+    pub revoked: Vec<PermissionItem>,
+}
+
+// The permissions a held grant lets its holder assign: everything at `T` for `T.assign`, and
+// everything a grant without a specifier matches, since it also matches `T.assign` for each of
+// them. A `***` followed by more fields pins the end of the permission and matches no `assign`.
+fn assign_scope(held: &PermissionItem) -> Option<PermissionItem> {
+    match held.perm.last()? {
+        // `T.assign`, or alternatives such as `T.{assign,read}`
+        field if field.is_specifier() && field.covers(&Specifier::Assign.into()) => {
+            let scope: PermissionItem = held.perm[..held.perm.len() - 1].to_vec().into();
+            match scope.perm.is_empty() {
+                true => None,
+                false => Some(scope),
+            }
+        }
+        field if field.is_specifier() => None,
+        Field::TripleGlob => Some(held.clone()),
+        _ if held.perm.contains(&Field::TripleGlob) => None,
+        _ => Some(held.clone()),
+    }
+}
+
+/// Whether `granter` may grant or revoke `target`, which requires `T.assign` for a `T` covering
+/// all of `target`. Like in `check`, the validity of the granter's grants is ignored and grants
+/// carrying a condition are skipped.
+pub fn can_assign(granter: &PermissionGroup, target: &PermissionItem) -> bool {
+    granter
+        .perms
+        .iter()
+        .filter(|p| p.condition.is_none())
+        .filter_map(assign_scope)
+        .any(|scope| is_subset(target, &scope))
+}
+
+/// Checks that `granter` may make every grant and revocation needed to turn `old_group` into
+/// `new_group`. A grant whose validity or condition changed counts as revoked and granted again.
+pub fn validate_grant_change(
+    granter: &PermissionGroup,
+    old_group: &PermissionGroup,
+    new_group: &PermissionGroup,
+) -> Result<(), GrantChangeError> {
+    let offending = |from: &PermissionGroup, to: &PermissionGroup| -> Vec<PermissionItem> {
+        to.perms
+            .iter()
+            .filter(|item| !from.perms.contains(item) && !can_assign(granter, item))
+            .cloned()
+            .collect()
+    };
+    let granted = offending(old_group, new_group);
+    let revoked = offending(new_group, old_group);
+    match granted.is_empty() && revoked.is_empty() {
+        true => Ok(()),
+        false => Err(GrantChangeError { granted, revoked }),
+    }
+}
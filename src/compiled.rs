@@ -0,0 +1,303 @@
+use crate::{applicable, check, exists};
+use permission_parser::{
+    Bindings, Clock, Condition, Context, EmptyContext, ItemExpr, NormalExpr, PermissionGroup,
+    PermissionItem, tokenizer::Field,
+};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+enum Op {
+    Const(bool),
+    Leaf(usize),
+    Predicate(usize),
+    Not,
+    Xor,
+    // Replace the top operands with whether at least the first number of them are true
+    AtLeast(usize, usize),
+    // Whether some values of the variables make the program of this index in `exists` true
+    Exists(usize),
+    // Leave the top of the stack in place and jump when it decides the result,
+    // otherwise pop it and fall through to the right hand side
+    JumpIfFalse(usize),
+    JumpIfTrue(usize),
+}
+
+/// `ItemExpr` simplified and flattened into a stack program, with identical leaves sharing one slot so
+/// each distinct permission is checked against a group at most once per evaluation
+#[derive(Clone)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct CompiledExpr {
+    leaves: Vec<PermissionItem>,
+    predicates: Vec<Condition>,
+    exists: Vec<(Vec<String>, CompiledExpr)>,
+    ops: Vec<Op>,
+}
+
+struct Compiler {
+    leaves: Vec<PermissionItem>,
+    leaf_index: HashMap<PermissionItem, usize>,
+    predicates: Vec<Condition>,
+    exists: Vec<(Vec<String>, CompiledExpr)>,
+    ops: Vec<Op>,
+}
+
+impl Compiler {
+    fn emit(&mut self, expr: &NormalExpr) {
+        match expr {
+            NormalExpr::True => self.ops.push(Op::Const(true)),
+            NormalExpr::False => self.ops.push(Op::Const(false)),
+            NormalExpr::Permission(p) => {
+                let idx = match self.leaf_index.get(p) {
+                    Some(idx) => *idx,
+                    None => {
+                        self.leaves.push(p.clone());
+                        self.leaf_index.insert(p.clone(), self.leaves.len() - 1);
+                        self.leaves.len() - 1
+                    }
+                };
+                self.ops.push(Op::Leaf(idx));
+            }
+            NormalExpr::Predicate(condition) => {
+                self.predicates.push(condition.clone());
+                self.ops.push(Op::Predicate(self.predicates.len() - 1));
+            }
+            NormalExpr::Not(e) => {
+                self.emit(e);
+                self.ops.push(Op::Not);
+            }
+            NormalExpr::And(operands) => self.emit_short_circuit(operands, false),
+            NormalExpr::Or(operands) => self.emit_short_circuit(operands, true),
+            NormalExpr::Xor(operands) => {
+                self.emit(&operands[0]);
+                for operand in &operands[1..] {
+                    self.emit(operand);
+                    self.ops.push(Op::Xor);
+                }
+            }
+            NormalExpr::AtLeast(count, operands) => {
+                operands.iter().for_each(|operand| self.emit(operand));
+                self.ops.push(Op::AtLeast(*count, operands.len()));
+            }
+            // The body is bound to each combination of values and evaluated on its own
+            NormalExpr::Exists(vars, body) => {
+                self.exists
+                    .push((vars.clone(), CompiledExpr::compile_normal(body)));
+                self.ops.push(Op::Exists(self.exists.len() - 1));
+            }
+        }
+    }
+
+    // Every operand but the last may decide the result and jump past the remaining ones
+    fn emit_short_circuit(&mut self, operands: &[NormalExpr], jump_on: bool) {
+        let (last, rest) = operands.split_last().unwrap();
+        let mut jumps = vec![];
+        for operand in rest {
+            self.emit(operand);
+            jumps.push(self.ops.len());
+            self.ops.push(Op::JumpIfFalse(0));
+        }
+        self.emit(last);
+        let target = self.ops.len();
+        for jump in jumps {
+            self.ops[jump] = if jump_on {
+                Op::JumpIfTrue(target)
+            } else {
+                Op::JumpIfFalse(target)
+            };
+        }
+    }
+}
+
+impl CompiledExpr {
+    /// Compiles the simplified form of `expr`, see `ItemExpr::simplify`
+    pub fn compile(expr: &ItemExpr) -> Self {
+        Self::compile_normal(&expr.simplify())
+    }
+
+    pub fn compile_normal(expr: &NormalExpr) -> Self {
+        let mut compiler = Compiler {
+            leaves: vec![],
+            leaf_index: HashMap::new(),
+            predicates: vec![],
+            exists: vec![],
+            ops: vec![],
+        };
+        compiler.emit(expr);
+        Self {
+            leaves: compiler.leaves,
+            predicates: compiler.predicates,
+            exists: compiler.exists,
+            ops: compiler.ops,
+        }
+    }
+
+    /// Placeholders the `exists` nodes use without binding them, which need a value before the
+    /// nodes can be evaluated
+    pub(crate) fn exists_vars(&self) -> Vec<String> {
+        let mut vars: Vec<String> = vec![];
+        for (bound, body) in &self.exists {
+            let free =
+                body.leaves
+                    .iter()
+                    .flat_map(|leaf| &leaf.perm)
+                    .filter_map(|field| match field {
+                        Field::VarKind(_, ident, _) => Some(ident.to_string()),
+                        _ => None,
+                    });
+            for var in free.chain(body.exists_vars()) {
+                if !bound.contains(&var) && !vars.contains(&var) {
+                    vars.push(var);
+                }
+            }
+        }
+        vars
+    }
+
+    /// Evaluates the `exists` node of this index with its free placeholders bound first
+    pub(crate) fn eval_exists(
+        &self,
+        idx: usize,
+        group: &PermissionGroup,
+        ctx: &dyn Context,
+        bindings: &Bindings,
+    ) -> bool {
+        let (vars, body) = &self.exists[idx];
+        let mut bindings = bindings.clone();
+        exists(group, vars.len(), |values| {
+            bindings.extend(vars.iter().cloned().zip(values.iter().cloned()));
+            body.eval_bound(group, ctx, &bindings)
+        })
+    }
+
+    /// The distinct permissions referenced by the expression
+    pub fn leaves(&self) -> &[PermissionItem] {
+        &self.leaves
+    }
+
+    /// Evaluates the program, with `ctx(...)` predicates seeing an empty context and conditional
+    /// grants skipped, like in `check`. Validity is ignored, see `eval_at`.
+    pub fn eval(&self, group: &PermissionGroup) -> bool {
+        self.eval_applicable(group, &EmptyContext)
+    }
+
+    /// Evaluates the program, with `ctx(...)` predicates and the conditions of grants evaluated
+    /// against `ctx`. Validity is ignored, see `eval_at`.
+    pub fn eval_with_context(&self, group: &PermissionGroup, ctx: &dyn Context) -> bool {
+        self.eval_applicable(&applicable(group, None, ctx), ctx)
+    }
+
+    /// Like `eval_with_context`, but grants outside their validity window at the clock's current
+    /// time are ignored too
+    pub fn eval_at(&self, group: &PermissionGroup, ctx: &dyn Context, clock: &impl Clock) -> bool {
+        self.eval_applicable(&applicable(group, Some(clock.now()), ctx), ctx)
+    }
+
+    // Evaluates against a group already narrowed by `applicable`, grants still carrying a
+    // condition being skipped
+    pub(crate) fn eval_applicable(&self, group: &PermissionGroup, ctx: &dyn Context) -> bool {
+        self.eval_bound(group, ctx, &Bindings::new())
+    }
+
+    // The program is reused for every value of the placeholders, which are only substituted into
+    // the leaves using them as they are checked
+    fn eval_bound(&self, group: &PermissionGroup, ctx: &dyn Context, bindings: &Bindings) -> bool {
+        let mut cache = vec![None; self.leaves.len()];
+        self.eval_with(
+            &mut cache,
+            &mut vec![],
+            ctx,
+            |idx| match self.leaves[idx].has_var() {
+                true => check(&self.leaves[idx].bind_known(bindings), group),
+                false => check(&self.leaves[idx], group),
+            },
+            |idx| self.eval_exists(idx, group, ctx, bindings),
+        )
+    }
+
+    /// Evaluates the program against every group, reusing the evaluation buffers. Conditional
+    /// grants are skipped and validity is ignored, like in `eval`.
+    pub fn eval_many(&self, groups: &[PermissionGroup]) -> Vec<bool> {
+        let mut cache = vec![None; self.leaves.len()];
+        let mut stack = vec![];
+        groups
+            .iter()
+            .map(|group| {
+                cache.fill(None);
+                stack.clear();
+                self.eval_with(
+                    &mut cache,
+                    &mut stack,
+                    &EmptyContext,
+                    |idx| check(&self.leaves[idx], group),
+                    |idx| self.eval_exists(idx, group, &EmptyContext, &Bindings::new()),
+                )
+            })
+            .collect()
+    }
+
+    /// Evaluates the program with leaf results supplied by index into `leaves()`, and the results
+    /// of `exists` nodes by their order in the simplified expression
+    pub fn eval_leaves(
+        &self,
+        ctx: &dyn Context,
+        leaf_value: impl Fn(usize) -> bool,
+        exists_value: impl Fn(usize) -> bool,
+    ) -> bool {
+        let mut cache = vec![None; self.leaves.len()];
+        self.eval_with(&mut cache, &mut vec![], ctx, leaf_value, exists_value)
+    }
+
+    fn eval_with(
+        &self,
+        cache: &mut [Option<bool>],
+        stack: &mut Vec<bool>,
+        ctx: &dyn Context,
+        leaf_value: impl Fn(usize) -> bool,
+        exists_value: impl Fn(usize) -> bool,
+    ) -> bool {
+        let mut pc = 0;
+        while pc < self.ops.len() {
+            match self.ops[pc] {
+                Op::Const(value) => stack.push(value),
+                Op::Leaf(idx) => {
+                    let value = *cache[idx].get_or_insert_with(|| leaf_value(idx));
+                    stack.push(value);
+                }
+                Op::Predicate(idx) => stack.push(self.predicates[idx].eval(ctx)),
+                Op::Exists(idx) => stack.push(exists_value(idx)),
+                Op::Not => {
+                    let top = stack.last_mut().unwrap();
+                    *top = !*top;
+                }
+                Op::Xor => {
+                    let right = stack.pop().unwrap();
+                    let top = stack.last_mut().unwrap();
+                    *top ^= right;
+                }
+                Op::AtLeast(count, len) => {
+                    let start = stack.len() - len;
+                    let holds = stack[start..].iter().filter(|value| **value).count() >= count;
+                    stack.truncate(start);
+                    stack.push(holds);
+                }
+                Op::JumpIfFalse(target) => {
+                    if !*stack.last().unwrap() {
+                        pc = target;
+                        continue;
+                    }
+                    stack.pop();
+                }
+                Op::JumpIfTrue(target) => {
+                    if *stack.last().unwrap() {
+                        pc = target;
+                        continue;
+                    }
+                    stack.pop();
+                }
+            }
+            pc += 1;
+        }
+        stack.pop().unwrap()
+    }
+}
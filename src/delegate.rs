@@ -0,0 +1,69 @@
+use crate::algebra::{intersect, is_subset};
+use permission_parser::{
+    Condition, PermissionGroup, PermissionItem, Validity, tokenizer::Specifier,
+};
+use std::fmt::{self, Debug, Formatter};
+
+pub enum DelegationError {
+    /// The requested `assign` grant is not fully held by a single grant of the holder
+    AssignNotHeld(Box<PermissionItem>),
+}
+
+// By hand so `unwrap` works in release builds, where `PermissionItem` has no `Debug`
+impl Debug for DelegationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AssignNotHeld(item) => write!(f, "AssignNotHeld({})", item),
+        }
+    }
+}
+
+// Delegated grants stay within the time window and conditions of both sides
+fn narrowest(a: Validity, b: Validity) -> Validity {
+    Validity {
+        not_before: a.not_before.max(b.not_before),
+        expires_at: match (a.expires_at, b.expires_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        },
+    }
+}
+
+fn both(a: &Option<Condition>, b: &Option<Condition>) -> Option<Condition> {
+    match (a, b) {
+        (Some(a), Some(b)) if a == b => Some(a.clone()),
+        (Some(a), Some(b)) => Some(a.clone().and(b.clone())),
+        (a, b) => a.clone().or(b.clone()),
+    }
+}
+
+pub trait Delegate {
+    /// The part of `requested` the holder may hand out, i.e. the intersection of `requested`
+    /// with the holder's grants. Each delegated grant keeps the validity window and condition
+    /// of both grants it came from. Requested `.assign` grants must be held in full.
+    fn delegate(&self, requested: &PermissionGroup) -> Result<PermissionGroup, DelegationError>;
+}
+
+impl Delegate for PermissionGroup {
+    fn delegate(&self, requested: &PermissionGroup) -> Result<PermissionGroup, DelegationError> {
+        let mut delegated: Vec<PermissionItem> = vec![];
+        for request in &requested.perms {
+            let assign = request.perm.last().is_some_and(|field| {
+                field.is_specifier() && field.covers(&Specifier::Assign.into())
+            });
+            if assign && !self.perms.iter().any(|held| is_subset(request, held)) {
+                return Err(DelegationError::AssignNotHeld(Box::new(request.clone())));
+            }
+            for held in &self.perms {
+                for mut item in intersect(request, held) {
+                    item.validity = narrowest(request.validity, held.validity);
+                    item.condition = both(&request.condition, &held.condition);
+                    if !delegated.contains(&item) {
+                        delegated.push(item);
+                    }
+                }
+            }
+        }
+        Ok(delegated.into())
+    }
+}
@@ -0,0 +1,110 @@
+use crate::check_one;
+use bitvec::{bitvec, vec::BitVec};
+use permission_parser::{PermissionGroup, PermissionItem, tokenizer::Field};
+use std::collections::HashMap;
+
+/// Checks run over a whole `PermissionGroup` at once
+pub trait GroupCheck {
+    /// Checks every requirement against the group, bit `i` being `check(&requires[i], group)`.
+    /// Like in `check`, grants carrying a condition are skipped and validity is ignored.
+    fn check_many(&self, requires: &[PermissionItem]) -> BitVec;
+
+    /// The requirements which the group permits, in their original order
+    fn filter<'a>(&self, requires: &'a [PermissionItem]) -> Vec<&'a PermissionItem> {
+        let result = self.check_many(requires);
+        requires
+            .iter()
+            .zip(result.iter())
+            .filter(|(_, permitted)| **permitted)
+            .map(|(require, _)| require)
+            .collect()
+    }
+}
+
+fn is_literal(field: &Field) -> bool {
+    matches!(
+        field,
+        Field::Name { name: _ }
+            | Field::ID { id: _ }
+            | Field::LargeID { id: _ }
+            | Field::Uuid { uuid: _ }
+            | Field::Specifier { specifier: _ }
+    )
+}
+
+enum PrefixWalk<'a> {
+    // The permission cannot grant anything starting with the prefix
+    Conflict,
+    // The permission lines up with the prefix and continues with this field
+    Next(&'a Field),
+    // Ended inside the prefix or hit `***`, every requirement has to be checked
+    Any,
+}
+
+fn walk_prefix<'a>(permission: &'a PermissionItem, prefix: &[Field]) -> PrefixWalk<'a> {
+    for (idx, require) in prefix.iter().enumerate() {
+        match permission.perm.get(idx) {
+            None | Some(Field::TripleGlob) => return PrefixWalk::Any,
+            Some(field) if !field.covers(require) => return PrefixWalk::Conflict,
+            Some(_) => {}
+        }
+    }
+    match permission.perm.get(prefix.len()) {
+        None | Some(Field::TripleGlob) => PrefixWalk::Any,
+        Some(field) => PrefixWalk::Next(field),
+    }
+}
+
+impl GroupCheck for PermissionGroup {
+    fn check_many(&self, requires: &[PermissionItem]) -> BitVec {
+        let mut result = bitvec![0; requires.len()];
+        let Some(first) = requires.first() else {
+            return result;
+        };
+        // Literal fields shared by every requirement, each permission is only walked over them once
+        let mut prefix_len = first.perm.iter().take_while(|f| is_literal(f)).count();
+        for require in requires {
+            prefix_len = prefix_len.min(
+                first
+                    .perm
+                    .iter()
+                    .zip(require.perm.iter())
+                    .take_while(|(l, r)| l == r)
+                    .count(),
+            );
+        }
+        let prefix = &first.perm[..prefix_len];
+        // Requirements with a literal right after the prefix can only be granted by a
+        // permission holding a matching field at that level, the rest must always be checked
+        let mut by_next: HashMap<&Field, Vec<usize>> = HashMap::new();
+        let mut unindexed: Vec<usize> = vec![];
+        for (idx, require) in requires.iter().enumerate() {
+            match require.perm.get(prefix_len) {
+                Some(field) if is_literal(field) => by_next.entry(field).or_default().push(idx),
+                _ => unindexed.push(idx),
+            }
+        }
+        let all: Vec<usize> = (0..requires.len()).collect();
+        for permission in self.perms.iter().filter(|p| p.condition.is_none()) {
+            let candidates: Box<dyn Iterator<Item = &usize>> = match walk_prefix(permission, prefix)
+            {
+                PrefixWalk::Conflict => continue,
+                PrefixWalk::Any => Box::new(all.iter()),
+                PrefixWalk::Next(field) if is_literal(field) => Box::new(
+                    by_next
+                        .get(field)
+                        .into_iter()
+                        .flatten()
+                        .chain(unindexed.iter()),
+                ),
+                PrefixWalk::Next(_) => Box::new(all.iter()),
+            };
+            for idx in candidates {
+                if !result[*idx] && check_one(&requires[*idx], permission) {
+                    result.set(*idx, true);
+                }
+            }
+        }
+        result
+    }
+}
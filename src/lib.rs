@@ -1,6 +1,79 @@
-use permission_parser::{ItemExpr, PermissionGroup, PermissionItem, tokenizer};
+mod algebra;
+mod assign;
+mod compiled;
+mod delegate;
+mod group;
+mod query;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(feature = "sql")]
+pub mod sql;
+
+pub use crate::algebra::PatternAlgebra;
+pub use crate::assign::{GrantChangeError, can_assign, validate_grant_change};
+pub use crate::compiled::CompiledExpr;
+pub use crate::delegate::{Delegate, DelegationError};
+pub use crate::group::GroupCheck;
+pub use crate::query::{PermittedValues, exists, permitted_values};
+pub use bitvec::vec::BitVec;
+use permission_parser::{
+    Bindings, Clock, Context, EmptyContext, ItemExpr, PermissionGroup, PermissionItem,
+    PermissionParseError, tokenizer,
+};
+use std::{borrow::Cow, rc::Rc, time::SystemTime};
+
+// Called by the code the macros generate for each permission they are given. Whether it
+// registers anything is decided by the `registry` feature of this crate, as the macros' own
+// features only apply to their build.
+#[doc(hidden)]
+#[cfg(feature = "registry")]
+#[macro_export]
+macro_rules! __register_permission {
+    ($permission:expr, $source:expr) => {
+        $crate::registry::inventory::submit! {
+            $crate::registry::RegisteredPermission {
+                permission: $permission,
+                source: $source,
+                file: ::core::file!(),
+                line: ::core::line!(),
+                column: ::core::column!(),
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[cfg(not(feature = "registry"))]
+#[macro_export]
+macro_rules! __register_permission {
+    ($permission:expr, $source:expr) => {};
+}
 
 pub fn check_one(require: &PermissionItem, permission: &PermissionItem) -> bool {
+    // A placeholder left unresolved, e.g. by a value of the wrong kind, is granted by nothing,
+    // not even `***` or a shorter permission
+    if require
+        .perm
+        .iter()
+        .any(|field| matches!(field, tokenizer::Field::VarKind(..)))
+    {
+        return false;
+    }
+    // A requirement with globs, prefixes, ranges or alternatives must have every permission it
+    // stands for granted, which the pattern algebra decides exactly; the matching below is for
+    // concrete permissions
+    if require.perm.iter().any(|field| {
+        !matches!(
+            field,
+            tokenizer::Field::Name { name: _ }
+                | tokenizer::Field::ID { id: _ }
+                | tokenizer::Field::LargeID { id: _ }
+                | tokenizer::Field::Uuid { uuid: _ }
+                | tokenizer::Field::Specifier { specifier: _ }
+        )
+    }) {
+        return algebra::grants_pattern(require, permission);
+    }
     let mut idx_left = 0;
     let mut idx_right = 0;
     let size_left = require.perm.len();
@@ -35,8 +108,8 @@ pub fn check_one(require: &PermissionItem, permission: &PermissionItem) -> bool
                 match_right_triple_glob = false;
             }
         }
-        let field_required = require.perm[idx_left].clone();
-        let field_permission = permission.perm[idx_right].clone();
+        let field_required = &require.perm[idx_left];
+        let field_permission = &permission.perm[idx_right];
         if match_left_triple_glob {
             match field_permission {
                 tokenizer::Field::DoubleGlob | tokenizer::Field::TripleGlob => {}
@@ -158,6 +231,15 @@ pub fn check_one(require: &PermissionItem, permission: &PermissionItem) -> bool
                 },
                 _,
             ) => {}
+            (
+                tokenizer::Field::Specifier {
+                    specifier: tokenizer::Specifier::Enact,
+                },
+                tokenizer::Field::Specifier {
+                    specifier: tokenizer::Specifier::Enact,
+                },
+                _,
+            ) => {}
             (tokenizer::Field::ID { id: _ }, tokenizer::Field::Glob, _)
             | (tokenizer::Field::Name { name: _ }, tokenizer::Field::Glob, _)
             | (tokenizer::Field::Glob, tokenizer::Field::Glob, _) => {}
@@ -179,8 +261,11 @@ pub fn check_one(require: &PermissionItem, permission: &PermissionItem) -> bool
                 };
             }
 
-            (_, _, _) => {
-                return false;
+            // Prefixes, ranges and alternatives in the grant, anything else does not match
+            (required, granted, false) => {
+                if !granted.covers(required) {
+                    return false;
+                }
             }
         }
         if size_left - idx_left == size_right - idx_right {
@@ -196,42 +281,276 @@ pub fn check_one(require: &PermissionItem, permission: &PermissionItem) -> bool
     if match_right_triple_glob {
         return true;
     }
-    return true;
+    true
 }
 
+/// Grants carrying a condition are skipped, as there is no context to evaluate it against, see
+/// `check_with_context`. Validity is ignored, see `check_at`.
 pub fn check(require: &PermissionItem, permissions: &PermissionGroup) -> bool {
-    permissions.perms.iter().any(|p| check_one(require, p))
+    permissions
+        .perms
+        .iter()
+        .any(|p| applies(p, None, None) && check_one(require, p))
+}
+
+// Whether a grant counts for a request at `now` with the context. Without a time the validity is
+// ignored, while without a context a condition never holds, so conditional grants fail closed.
+fn applies(
+    permission: &PermissionItem,
+    now: Option<SystemTime>,
+    ctx: Option<&dyn Context>,
+) -> bool {
+    now.is_none_or(|now| permission.validity.is_active_at(now))
+        && permission
+            .condition
+            .as_ref()
+            .is_none_or(|c| ctx.is_some_and(|ctx| c.eval(ctx)))
+}
+
+// The grants which count for a request at `now` with the context, without their conditions since
+// they hold, so that the checks without a context take them into account
+pub(crate) fn applicable<'a>(
+    permissions: &'a PermissionGroup,
+    now: Option<SystemTime>,
+    ctx: &dyn Context,
+) -> Cow<'a, PermissionGroup> {
+    if permissions
+        .perms
+        .iter()
+        .all(|p| p.condition.is_none() && applies(p, now, None))
+    {
+        return Cow::Borrowed(permissions);
+    }
+    Cow::Owned(
+        permissions
+            .perms
+            .iter()
+            .filter(|p| applies(p, now, Some(ctx)))
+            .map(|p| PermissionItem {
+                condition: None,
+                ..p.clone()
+            })
+            .collect::<Vec<PermissionItem>>()
+            .into(),
+    )
+}
+
+/// Like `check`, but grants outside their `not_before`/`expires_at` window at the clock's
+/// current time are ignored. See `check_with_context_at` for conditional grants.
+pub fn check_at(
+    require: &PermissionItem,
+    permissions: &PermissionGroup,
+    clock: &impl Clock,
+) -> bool {
+    let now = clock.now();
+    permissions
+        .perms
+        .iter()
+        .any(|p| applies(p, Some(now), None) && check_one(require, p))
+}
+
+/// Like `check`, but grants carrying a condition only count when it holds for `ctx`. Validity is
+/// ignored, see `check_with_context_at`.
+pub fn check_with_context(
+    require: &PermissionItem,
+    permissions: &PermissionGroup,
+    ctx: &dyn Context,
+) -> bool {
+    permissions
+        .perms
+        .iter()
+        .any(|p| applies(p, None, Some(ctx)) && check_one(require, p))
+}
+
+/// Like `check_with_context`, but grants outside their validity window at the clock's current
+/// time are ignored too
+pub fn check_with_context_at(
+    require: &PermissionItem,
+    permissions: &PermissionGroup,
+    ctx: &dyn Context,
+    clock: &impl Clock,
+) -> bool {
+    let now = clock.now();
+    permissions
+        .perms
+        .iter()
+        .any(|p| applies(p, Some(now), Some(ctx)) && check_one(require, p))
+}
+
+/// Evaluates the expression tree directly. This is the slow path, as each `exists` is simplified
+/// and compiled again on every call: to check an expression more than once, compile it once with
+/// `CompiledExpr::compile` or build a `ComplexCheck` from it.
+/// `ctx(...)` predicates see an empty context and conditional grants are skipped, like in `check`,
+/// and validity is ignored.
+pub fn check_expr(expr: &ItemExpr, permissions: &PermissionGroup) -> bool {
+    eval_expr(expr, permissions, &EmptyContext)
+}
+
+/// Like `check_expr`, with `ctx(...)` predicates and the conditions of grants evaluated against
+/// `ctx`. Validity is ignored, see `check_expr_at`.
+pub fn check_expr_with_context(
+    expr: &ItemExpr,
+    permissions: &PermissionGroup,
+    ctx: &dyn Context,
+) -> bool {
+    eval_expr(expr, &applicable(permissions, None, ctx), ctx)
+}
+
+/// Like `check_expr_with_context`, but grants outside their validity window at the clock's
+/// current time are ignored too
+pub fn check_expr_at(
+    expr: &ItemExpr,
+    permissions: &PermissionGroup,
+    ctx: &dyn Context,
+    clock: &impl Clock,
+) -> bool {
+    eval_expr(expr, &applicable(permissions, Some(clock.now()), ctx), ctx)
 }
 
-fn check_expr(expr: &ItemExpr, permissions: &PermissionGroup) -> bool {
+fn eval_expr(expr: &ItemExpr, permissions: &PermissionGroup, ctx: &dyn Context) -> bool {
+    let recurse = |e: &ItemExpr| eval_expr(e, permissions, ctx);
     match expr {
         ItemExpr::Permission(p) => check(p, permissions),
-        ItemExpr::And(l, r) => check_expr(l, permissions) && check_expr(r, permissions),
-        ItemExpr::Or(l, r) => check_expr(l, permissions) || check_expr(r, permissions),
-        ItemExpr::Not(e) => !check_expr(e, permissions),
-        ItemExpr::Xor(l, r) => check_expr(l, permissions) ^ check_expr(r, permissions),
-        ItemExpr::Bracketed(b) => check_expr(b, permissions),
+        ItemExpr::Predicate(condition) => condition.eval(ctx),
+        ItemExpr::And(operands) => operands.iter().all(recurse),
+        ItemExpr::Or(operands) => operands.iter().any(recurse),
+        ItemExpr::Not(e) => !recurse(e),
+        ItemExpr::Xor(operands) => operands.iter().fold(false, |acc, e| acc ^ recurse(e)),
+        ItemExpr::Bracketed(b) => recurse(b),
+        ItemExpr::AtLeast(count, operands) => at_least(*count, operands, recurse),
+        ItemExpr::Exists(_, _) => check_exists(expr, permissions, ctx),
+    }
+}
+
+// The simplified form tries placeholders used apart one after the other, see
+// `NormalExpr::Exists`, and its program is evaluated for every value without being rebuilt
+fn check_exists(exists: &ItemExpr, permissions: &PermissionGroup, ctx: &dyn Context) -> bool {
+    CompiledExpr::compile(exists).eval_applicable(permissions, ctx)
+}
+
+// Stops once `count` operands hold or too few are left to reach it
+fn at_least(count: usize, operands: &[ItemExpr], holds: impl Fn(&ItemExpr) -> bool) -> bool {
+    let mut missing = count;
+    for (idx, operand) in operands.iter().enumerate() {
+        if missing == 0 || operands.len() - idx < missing {
+            break;
+        }
+        if holds(operand) {
+            missing -= 1;
+        }
     }
+    missing == 0
 }
 
+type CheckFn = dyn Fn(&PermissionGroup, &dyn Context) -> bool;
+
 pub struct ComplexCheck {
-    check_fn: Box<dyn Fn(&PermissionGroup) -> bool>,
+    check_fn: Box<CheckFn>,
+    program: Option<Rc<CompiledExpr>>,
+    source: Option<ItemExpr>,
 }
 
 impl ComplexCheck {
     pub fn new(check_fn: Box<dyn Fn(&PermissionGroup) -> bool>) -> Self {
-        Self { check_fn }
+        Self::new_with_context(Box::new(move |group, _| check_fn(group)))
+    }
+
+    /// A check which also looks at the request context, as generated for `ctx(...)` predicates
+    pub fn new_with_context(check_fn: Box<CheckFn>) -> Self {
+        Self {
+            check_fn,
+            program: None,
+            source: None,
+        }
+    }
+
+    /// The expression the check was built from with `ComplexCheck::from`, which displays as
+    /// expression syntax for audit logs and error messages
+    pub fn source(&self) -> Option<&ItemExpr> {
+        self.source.as_ref()
     }
 
+    /// Checks the group, with `ctx(...)` predicates seeing an empty context and conditional
+    /// grants skipped, like in `check`. Validity is ignored, see `with_perm_at`.
     pub fn with_perm(&self, group: impl Into<PermissionGroup>) -> bool {
-        // Into::<Fn(&PermissionGroup) -> bool>::into(self.check_fn)(group.into())
-        (self.check_fn)(&group.into())
+        (self.check_fn)(&group.into(), &EmptyContext)
+    }
+
+    /// Checks the group, with `ctx(...)` predicates and the conditions of grants evaluated
+    /// against `ctx`. Validity is ignored, see `with_perm_at`.
+    pub fn with_perm_and_context(
+        &self,
+        group: impl Into<PermissionGroup>,
+        ctx: &dyn Context,
+    ) -> bool {
+        (self.check_fn)(&applicable(&group.into(), None, ctx), ctx)
+    }
+
+    /// Like `with_perm_and_context`, but grants outside their validity window at the clock's
+    /// current time are ignored too
+    pub fn with_perm_at(
+        &self,
+        group: impl Into<PermissionGroup>,
+        ctx: &dyn Context,
+        clock: &impl Clock,
+    ) -> bool {
+        (self.check_fn)(&applicable(&group.into(), Some(clock.now()), ctx), ctx)
+    }
+
+    /// Evaluates the check once per set of bindings for its `{var}` placeholders (see
+    /// `expr_parse_pattern`), checking each bound permission in one pass over the group.
+    /// Only available for checks built with `ComplexCheck::from`, `ctx(...)` predicates see an
+    /// empty context, conditional grants are skipped and validity is ignored.
+    pub fn with_perm_many(
+        &self,
+        group: impl Into<PermissionGroup>,
+        bindings: &[Bindings],
+    ) -> Result<BitVec, PermissionParseError> {
+        let program = self.program.as_ref().ok_or_else(|| {
+            PermissionParseError::InvalidOutput(
+                "with_perm_many requires a check built from an ItemExpr".to_string(),
+            )
+        })?;
+        let group = group.into();
+        if let Some(var) = program
+            .exists_vars()
+            .into_iter()
+            .find(|var| bindings.iter().any(|b| !b.contains_key(var)))
+        {
+            return Err(format!("No value bound for variable `{{{}}}`", var).into());
+        }
+        let leaf_results = program
+            .leaves()
+            .iter()
+            .map(|leaf| {
+                if !leaf.has_var() {
+                    return Ok(BitVec::repeat(check(leaf, &group), bindings.len()));
+                }
+                let bound = bindings
+                    .iter()
+                    .map(|b| leaf.bind(b))
+                    .collect::<Result<Vec<PermissionItem>, PermissionParseError>>()?;
+                Ok(group.check_many(&bound))
+            })
+            .collect::<Result<Vec<BitVec>, PermissionParseError>>()?;
+        Ok((0..bindings.len())
+            .map(|idx| {
+                program.eval_leaves(
+                    &EmptyContext,
+                    |leaf| leaf_results[leaf][idx],
+                    |node| program.eval_exists(node, &group, &EmptyContext, &bindings[idx]),
+                )
+            })
+            .collect())
     }
 
     pub fn from(expr: &ItemExpr) -> Self {
-        let expr = expr.clone();
+        let program = Rc::new(CompiledExpr::compile_normal(&expr.simplify()));
+        let check_program = program.clone();
         Self {
-            check_fn: Box::new(move |group| check_expr(&expr, group)),
+            check_fn: Box::new(move |group, ctx| check_program.eval_applicable(group, ctx)),
+            program: Some(program),
+            source: Some(expr.clone()),
         }
     }
 }
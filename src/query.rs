@@ -0,0 +1,236 @@
+use crate::check_one;
+use permission_parser::{
+    Bindings, PermissionGroup, PermissionItem, PermissionParseError, tokenizer::Field,
+};
+use std::collections::{BTreeSet, HashSet};
+
+/// Values a placeholder may take for the bound permission to be granted
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum PermittedValues {
+    /// Any name or ID, a glob in the group covers the placeholder
+    All,
+    /// Only these names and IDs, prefixes and ranges, in the order they appear in the group
+    Values(Vec<Field>),
+}
+
+impl PermittedValues {
+    pub fn contains(&self, value: &Field) -> bool {
+        match self {
+            PermittedValues::All => true,
+            PermittedValues::Values(values) => values.iter().any(|v| v.covers(value)),
+        }
+    }
+}
+
+/// Placeholder names of the pattern in order of first appearance
+pub(crate) fn pattern_vars(pattern: &PermissionItem) -> Vec<String> {
+    let mut vars: Vec<String> = vec![];
+    for field in &pattern.perm {
+        if let Field::VarKind(_, ident, _) = field {
+            let name = ident.to_string();
+            if !vars.contains(&name) {
+                vars.push(name);
+            }
+        }
+    }
+    vars
+}
+
+/// Every combination of placeholder values (`None` being any value) under which a single
+/// permission grants the pattern, with combinations covered by a broader one removed
+pub(crate) fn permitted_assignments(
+    pattern: &PermissionItem,
+    vars: &[String],
+    permission: &PermissionItem,
+) -> Result<Vec<Vec<Option<Field>>>, PermissionParseError> {
+    // A value of the wrong kind for a typed placeholder leaves it unresolved, which is never granted
+    let bind = |assignment: &[Option<Field>]| {
+        let bindings: Bindings = vars
+            .iter()
+            .cloned()
+            .zip(assignment.iter().map(|v| v.clone().unwrap_or(Field::Glob)))
+            .collect();
+        pattern.bind_known(&bindings)
+    };
+    let any = vec![None; vars.len()];
+    if check_one(&bind(&any), permission) {
+        return Ok(vec![any]);
+    }
+    // A specific value can only be required by the permission if it spells it out
+    let mut literals: Vec<Field> = vec![];
+    for field in value_fields(permission) {
+        if !literals.contains(field) {
+            literals.push(field.clone());
+        }
+    }
+    let candidates: Vec<Option<Field>> = std::iter::once(None)
+        .chain(literals.into_iter().map(Some))
+        .collect();
+    let mut result: Vec<Vec<Option<Field>>> = vec![];
+    let mut counter = vec![0; vars.len()];
+    loop {
+        let assignment: Vec<Option<Field>> =
+            counter.iter().map(|idx| candidates[*idx].clone()).collect();
+        if check_one(&bind(&assignment), permission) {
+            result.push(assignment);
+        }
+        let Some(pos) = counter.iter().rposition(|idx| idx + 1 < candidates.len()) else {
+            break;
+        };
+        counter[pos] += 1;
+        counter[pos + 1..].fill(0);
+    }
+    Ok(minimise(result))
+}
+
+// The names, IDs, prefixes and ranges a permission spells out, alternatives being split up
+fn value_fields(permission: &PermissionItem) -> impl Iterator<Item = &Field> {
+    permission
+        .perm
+        .iter()
+        .flat_map(|field| match field {
+            Field::Alternatives { fields } => fields.iter().collect(),
+            field => vec![field],
+        })
+        .filter(|field| {
+            matches!(
+                field,
+                Field::Name { name: _ }
+                    | Field::ID { id: _ }
+                    | Field::LargeID { id: _ }
+                    | Field::Uuid { uuid: _ }
+                    | Field::Prefix { prefix: _ }
+                    | Field::Range { start: _, end: _ }
+            )
+        })
+}
+
+fn covers(broad: &[Option<Field>], narrow: &[Option<Field>]) -> bool {
+    broad
+        .iter()
+        .zip(narrow.iter())
+        .all(|(b, n)| b.is_none() || b == n)
+}
+
+pub(crate) fn minimise(assignments: Vec<Vec<Option<Field>>>) -> Vec<Vec<Option<Field>>> {
+    let mut result: Vec<Vec<Option<Field>>> = vec![];
+    for (idx, assignment) in assignments.iter().enumerate() {
+        let redundant = assignments.iter().enumerate().any(|(other_idx, other)| {
+            other_idx != idx
+                && covers(other, assignment)
+                && (other != assignment || other_idx < idx)
+        });
+        if !redundant {
+            result.push(assignment.clone());
+        }
+    }
+    result
+}
+
+/// The inverse of `check`: which values of the single `{var}` placeholder in `pattern`
+/// (see `parse_pattern`) give a permission granted by the group. Like in `check`, grants carrying a
+/// condition are skipped and validity is ignored, narrow the group with `PermissionGroup::active`
+/// first to leave out expired grants.
+pub fn permitted_values(
+    group: &PermissionGroup,
+    pattern: &PermissionItem,
+) -> Result<PermittedValues, PermissionParseError> {
+    let vars = pattern_vars(pattern);
+    if vars.len() != 1 {
+        return Err(format!(
+            "Expected exactly one variable in `{}`, found {}",
+            pattern,
+            vars.len()
+        )
+        .into());
+    }
+    let mut seen: HashSet<Field> = HashSet::new();
+    let mut values: Vec<Field> = vec![];
+    for permission in group.perms.iter().filter(|p| p.condition.is_none()) {
+        for assignment in permitted_assignments(pattern, &vars, permission)? {
+            match assignment.into_iter().next().flatten() {
+                None => return Ok(PermittedValues::All),
+                Some(value) => {
+                    if seen.insert(value.clone()) {
+                        values.push(value);
+                    }
+                }
+            }
+        }
+    }
+    Ok(PermittedValues::Values(values))
+}
+
+/// Whether some values for `count` placeholders make `holds` true. Values the grants of the group
+/// cannot tell apart give the same result, so one of each kind is tried: every name and ID the
+/// group spells out, one inside each distinct part of its prefixes and ranges, and a glob standing
+/// for the values no grant mentions. The placeholders take each combination in turn, so up to
+/// `representatives^count` calls of `holds`, where the representatives grow with the distinct
+/// names, IDs, prefixes and ranges of the group. The parsers and `ItemExpr::decode` bound `count`
+/// for the expressions they return with `ParseLimits::max_exists_vars`.
+pub fn exists(
+    group: &PermissionGroup,
+    count: usize,
+    mut holds: impl FnMut(&[Field]) -> bool,
+) -> bool {
+    let candidates = representatives(group);
+    let mut counter = vec![0; count];
+    loop {
+        let values: Vec<Field> = counter.iter().map(|idx| candidates[*idx].clone()).collect();
+        if holds(&values) {
+            return true;
+        }
+        let Some(pos) = counter.iter().rposition(|idx| idx + 1 < candidates.len()) else {
+            return false;
+        };
+        counter[pos] += 1;
+        counter[pos + 1..].fill(0);
+    }
+}
+
+// An ID is told apart by the ranges holding it and by being spelled out itself, so between two
+// consecutive bounds or IDs all are alike and the one after the lower bound stands for them. A
+// name is told apart by the prefixes it starts with, of which a prefix itself has the most, or
+// the prefix extended by a character no grant can spell when the prefix is a name of its own.
+fn representatives(group: &PermissionGroup) -> Vec<Field> {
+    let mut candidates = vec![Field::Glob];
+    let push = |candidates: &mut Vec<Field>, field: Field| {
+        if !candidates.contains(&field) {
+            candidates.push(field);
+        }
+    };
+    let mut prefixes: Vec<&String> = vec![];
+    let mut ranges: Vec<(u64, u64)> = vec![];
+    let mut points: BTreeSet<u64> = BTreeSet::new();
+    for field in group.perms.iter().flat_map(value_fields) {
+        match field {
+            Field::Prefix { prefix } => prefixes.push(prefix),
+            Field::Range { start, end } => {
+                ranges.push((*start, *end));
+                points.extend([*start, *end]);
+            }
+            Field::ID { id } => {
+                points.insert(*id);
+                push(&mut candidates, field.clone());
+            }
+            field => push(&mut candidates, field.clone()),
+        }
+    }
+    for prefix in prefixes {
+        let mut name = prefix.to_string();
+        if candidates.contains(&Field::Name { name: name.clone() }) {
+            name.push('\0');
+        }
+        push(&mut candidates, Field::Name { name });
+    }
+    let in_range = |id: u64| ranges.iter().any(|(start, end)| *start <= id && id < *end);
+    for point in points {
+        for id in [Some(point), point.checked_add(1)].into_iter().flatten() {
+            if in_range(id) {
+                push(&mut candidates, Field::ID { id });
+            }
+        }
+    }
+    candidates
+}
@@ -0,0 +1,79 @@
+use crate::query::{pattern_vars, permitted_assignments};
+use permission_parser::{PermissionGroup, PermissionItem, PermissionParseError, parse_pattern};
+use std::fmt::{self, Display, Formatter};
+
+// Used by `__register_permission!`, which the macros call for every permission
+#[doc(hidden)]
+pub use inventory;
+
+/// A permission written in `perm_parser!`, `try_perm_parser!`, `perm_expr!` or `perm_def!`,
+/// registered at build time from where the macro is used
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct RegisteredPermission {
+    /// The permission with its placeholders, e.g. `org.{org_id}.doc.read`
+    pub permission: &'static str,
+    /// The macro it is written in, e.g. `perm_expr!`
+    pub source: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    pub column: u32,
+}
+
+inventory::collect!(RegisteredPermission);
+
+impl RegisteredPermission {
+    /// The permission as parsed by `parse_pattern`, its placeholders standing for any value
+    pub fn pattern(&self) -> Result<PermissionItem, PermissionParseError> {
+        parse_pattern(self.permission)
+    }
+}
+
+impl Display for RegisteredPermission {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} at {}:{}:{})",
+            self.permission, self.source, self.file, self.line, self.column
+        )
+    }
+}
+
+/// Every permission the macros registered in this binary, ordered by source location. A
+/// permission used in several places is listed once for each.
+pub fn registered_permissions() -> Vec<&'static RegisteredPermission> {
+    let mut registered: Vec<&'static RegisteredPermission> =
+        inventory::iter::<RegisteredPermission>.into_iter().collect();
+    registered.sort_by_key(|p| (p.file, p.line, p.column, p.permission));
+    registered
+}
+
+/// The permissions of the group which grant none of `registered` for any value of their
+/// placeholders, i.e. grants no code of the binary checks. Pass `registered_permissions()`, or
+/// part of it to leave out e.g. the `perm_parser!` uses that build grants rather than check them.
+pub fn dead_permissions<'a>(
+    group: &'a PermissionGroup,
+    registered: &[&RegisteredPermission],
+) -> Result<Vec<&'a PermissionItem>, PermissionParseError> {
+    let patterns = registered
+        .iter()
+        .map(|registered| {
+            let pattern = registered.pattern()?;
+            Ok((pattern_vars(&pattern), pattern))
+        })
+        .collect::<Result<Vec<(Vec<String>, PermissionItem)>, PermissionParseError>>()?;
+    let mut dead = vec![];
+    for permission in &group.perms {
+        let mut live = false;
+        for (vars, pattern) in &patterns {
+            if !permitted_assignments(pattern, vars, permission)?.is_empty() {
+                live = true;
+                break;
+            }
+        }
+        if !live {
+            dead.push(permission);
+        }
+    }
+    Ok(dead)
+}
@@ -0,0 +1,259 @@
+use crate::query::{minimise, pattern_vars, permitted_assignments};
+use permission_parser::{PermissionGroup, PermissionItem, PermissionParseError, tokenizer::Field};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum SqlDialect {
+    /// Numbered `?1` parameters
+    Sqlite,
+    /// Numbered `$1` parameters
+    Postgres,
+}
+
+/// Whether a column holds IDs (integers) or names (text)
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum ColumnKind {
+    Id,
+    Name,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct SqlColumn {
+    pub name: String,
+    pub kind: ColumnKind,
+}
+
+impl SqlColumn {
+    pub fn id(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: ColumnKind::Id,
+        }
+    }
+
+    pub fn name(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: ColumnKind::Name,
+        }
+    }
+}
+
+/// A resource pattern such as `org.{org_id}.doc.{doc_id}.read` and the column each
+/// placeholder is stored in
+#[derive(Clone)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct ResourceMapping {
+    pub pattern: PermissionItem,
+    pub columns: HashMap<String, SqlColumn>,
+}
+
+impl ResourceMapping {
+    /// Maps every placeholder to an ID column of the same name
+    pub fn new(pattern: PermissionItem) -> Self {
+        let columns = pattern_vars(&pattern)
+            .into_iter()
+            .map(|var| {
+                let column = SqlColumn::id(&var);
+                (var, column)
+            })
+            .collect();
+        Self { pattern, columns }
+    }
+
+    pub fn column(mut self, var: &str, column: SqlColumn) -> Self {
+        self.columns.insert(var.to_string(), column);
+        self
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum SqlValue {
+    Integer(i64),
+    Text(String),
+}
+
+/// A parenthesised `WHERE` fragment with its parameters, numbered from 1
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct SqlPredicate {
+    pub sql: String,
+    pub params: Vec<SqlValue>,
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// What a granted value requires of its column
+#[derive(Clone, PartialEq, Eq)]
+enum SqlMatch {
+    Eq(SqlValue),
+    /// IDs from the start up to the end, if it fits into a column at all
+    Range(i64, Option<i64>),
+    Prefix(String),
+}
+
+// Values of the wrong kind for their column can never match a row
+fn to_sql_match(value: &Field, kind: ColumnKind) -> Option<SqlMatch> {
+    match (value, kind) {
+        (Field::ID { id }, ColumnKind::Id) => i64::try_from(*id)
+            .ok()
+            .map(|id| SqlMatch::Eq(SqlValue::Integer(id))),
+        (Field::Name { name }, ColumnKind::Name) => {
+            Some(SqlMatch::Eq(SqlValue::Text(name.clone())))
+        }
+        (Field::Range { start, end }, ColumnKind::Id) => i64::try_from(*start)
+            .ok()
+            .map(|start| SqlMatch::Range(start, i64::try_from(*end).ok())),
+        (Field::Prefix { prefix }, ColumnKind::Name) => Some(SqlMatch::Prefix(prefix.clone())),
+        _ => None,
+    }
+}
+
+struct Builder {
+    dialect: SqlDialect,
+    params: Vec<SqlValue>,
+}
+
+impl Builder {
+    fn param(&mut self, value: SqlValue) -> String {
+        self.params.push(value);
+        match self.dialect {
+            SqlDialect::Sqlite => format!("?{}", self.params.len()),
+            SqlDialect::Postgres => format!("${}", self.params.len()),
+        }
+    }
+
+    // Prefixes compare the leading characters, `LIKE` would treat `%` and `_` in them as
+    // wildcards and ignore case in SQLite
+    fn condition(&mut self, column: &str, value: SqlMatch) -> String {
+        match value {
+            SqlMatch::Eq(value) => format!("{} = {}", column, self.param(value)),
+            SqlMatch::Range(start, None) => {
+                format!("{} >= {}", column, self.param(SqlValue::Integer(start)))
+            }
+            SqlMatch::Range(start, Some(end)) => format!(
+                "{} >= {} AND {} < {}",
+                column,
+                self.param(SqlValue::Integer(start)),
+                column,
+                self.param(SqlValue::Integer(end))
+            ),
+            SqlMatch::Prefix(prefix) => format!(
+                "substr({}, 1, {}) = {}",
+                column,
+                prefix.chars().count(),
+                self.param(SqlValue::Text(prefix))
+            ),
+        }
+    }
+}
+
+/// Builds a `WHERE` fragment selecting exactly the rows whose bound pattern is granted by the
+/// group, i.e. the rows a post-filter with `check` would keep, skipping grants carrying a condition
+/// and ignoring validity
+pub fn sql_predicate(
+    group: &PermissionGroup,
+    mapping: &ResourceMapping,
+    dialect: SqlDialect,
+) -> Result<SqlPredicate, PermissionParseError> {
+    let vars = pattern_vars(&mapping.pattern);
+    if vars.is_empty() {
+        return Err(format!("No variable in `{}` to map to a column", mapping.pattern).into());
+    }
+    let columns = vars
+        .iter()
+        .map(|var| {
+            mapping
+                .columns
+                .get(var)
+                .ok_or_else(|| format!("No column mapped for variable `{{{}}}`", var).into())
+        })
+        .collect::<Result<Vec<&SqlColumn>, PermissionParseError>>()?;
+    let mut assignments = vec![];
+    for permission in group.perms.iter().filter(|p| p.condition.is_none()) {
+        assignments.extend(permitted_assignments(&mapping.pattern, &vars, permission)?);
+    }
+    let mut rows: Vec<Vec<Option<SqlMatch>>> = vec![];
+    for assignment in minimise(assignments) {
+        let converted = assignment
+            .iter()
+            .zip(columns.iter())
+            .map(|(value, column)| match value {
+                None => Some(None),
+                Some(value) => to_sql_match(value, column.kind).map(Some),
+            })
+            .collect::<Option<Vec<Option<SqlMatch>>>>();
+        if let Some(converted) = converted {
+            if converted.iter().all(|v| v.is_none()) {
+                return Ok(SqlPredicate {
+                    sql: "(1 = 1)".to_string(),
+                    params: vec![],
+                });
+            }
+            rows.push(converted);
+        }
+    }
+    if rows.is_empty() {
+        return Ok(SqlPredicate {
+            sql: "(1 = 0)".to_string(),
+            params: vec![],
+        });
+    }
+    // Rows only differing in the value of their last fixed column are merged into one `IN`
+    // list, ranges and prefixes keep a clause of their own
+    let mut merged: Vec<(Vec<Option<SqlMatch>>, usize, Vec<SqlMatch>)> = vec![];
+    for mut row in rows {
+        let last = row.iter().rposition(|v| v.is_some()).unwrap();
+        let value = row[last].take().unwrap();
+        match merged.iter_mut().find(|(key, column, values)| {
+            *column == last
+                && *key == row
+                && matches!((&values[0], &value), (SqlMatch::Eq(_), SqlMatch::Eq(_)))
+        }) {
+            Some((_, _, values)) => values.push(value),
+            None => merged.push((row, last, vec![value])),
+        }
+    }
+    let mut builder = Builder {
+        dialect,
+        params: vec![],
+    };
+    let clauses: Vec<String> = merged
+        .into_iter()
+        .map(|(key, last, values)| {
+            let mut conditions: Vec<String> = key
+                .into_iter()
+                .enumerate()
+                .filter_map(|(idx, value)| {
+                    value.map(|value| {
+                        builder.condition(&quote_identifier(&columns[idx].name), value)
+                    })
+                })
+                .collect();
+            let column = quote_identifier(&columns[last].name);
+            conditions.push(if values.len() == 1 {
+                builder.condition(&column, values.into_iter().next().unwrap())
+            } else {
+                let params: Vec<String> = values
+                    .into_iter()
+                    .filter_map(|value| match value {
+                        SqlMatch::Eq(value) => Some(builder.param(value)),
+                        _ => None,
+                    })
+                    .collect();
+                format!("{} IN ({})", column, params.join(", "))
+            });
+            format!("({})", conditions.join(" AND "))
+        })
+        .collect();
+    Ok(SqlPredicate {
+        sql: format!("({})", clauses.join(" OR ")),
+        params: builder.params,
+    })
+}
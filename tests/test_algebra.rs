@@ -0,0 +1,152 @@
+use permission_check::{PatternAlgebra, check, check_one};
+use permission_parser::{
+    PermissionItem, PermissionParseError, parse,
+    tokenizer::{Field, Specifier},
+};
+use proptest::prelude::*;
+
+fn field(segment: &str) -> Field {
+    match segment {
+        "*" => Field::Glob,
+        "**" => Field::DoubleGlob,
+        "***" => Field::TripleGlob,
+        "read" => Specifier::Read.into(),
+        "write" => Specifier::Write.into(),
+        "assign" => Specifier::Assign.into(),
+        segment if segment.ends_with('*') || segment.contains(['.', '{']) => {
+            parse(segment).unwrap().perm.remove(0)
+        }
+        segment => match segment.parse::<u64>() {
+            Ok(id) => Field::ID { id },
+            Err(_) => Field::Name {
+                name: segment.to_string(),
+            },
+        },
+    }
+}
+
+fn item(segments: Vec<&str>) -> PermissionItem {
+    segments
+        .into_iter()
+        .map(field)
+        .collect::<Vec<Field>>()
+        .into()
+}
+
+const NAMES: [&str; 7] = ["org", "doc", "1047", "1048", "read", "write", "assign"];
+
+// Segments rather than items, as `PermissionItem` only derives `Debug` in debug builds
+fn pattern() -> impl Strategy<Value = Vec<&'static str>> {
+    let mut segments = NAMES.to_vec();
+    segments.extend([
+        "*",
+        "**",
+        "***",
+        "do*",
+        "1047..1049",
+        "{doc,1048}",
+        "{read,write}",
+    ]);
+    prop::collection::vec(prop::sample::select(segments), 1..5)
+        .prop_filter("valid pattern", |p| item(p.clone()).validate().is_ok())
+}
+
+// Concrete permissions, including a name no pattern mentions
+fn path() -> impl Strategy<Value = Vec<&'static str>> {
+    let mut segments = NAMES.to_vec();
+    segments.push("other");
+    prop::collection::vec(prop::sample::select(segments), 1..8)
+        .prop_filter("valid path", |p| item(p.clone()).validate().is_ok())
+}
+
+fn ends_in_specifier(path: &PermissionItem) -> bool {
+    matches!(path.perm.last(), Some(Field::Specifier { specifier: _ }))
+}
+
+proptest! {
+    #[test]
+    fn subsumes_agrees_with_check_one(a in pattern(), b in pattern(), p in path()) {
+        let (a, b, p) = (item(a), item(b), item(p));
+        if a.subsumes(&b) && check_one(&p, &b) {
+            prop_assert!(check_one(&p, &a), "{} subsumes {} but not {}", a, b, p);
+        }
+        if ends_in_specifier(&p) {
+            prop_assert_eq!(a.subsumes(&p), check_one(&p, &a), "{} {}", a, p);
+        }
+    }
+
+    #[test]
+    fn overlaps_and_intersect_agree_with_check_one(a in pattern(), b in pattern(), p in path()) {
+        let (a, b, p) = (item(a), item(b), item(p));
+        let both = check_one(&p, &a) && check_one(&p, &b);
+        if both {
+            prop_assert!(a.overlaps(&b), "{} and {} share {}", a, b, p);
+        }
+        let intersection = a.intersect(&b);
+        let actual = check(&p, &intersection.clone().into());
+        prop_assert!(!actual || both, "{} ∩ {} grants {}", a, b, p);
+        // Only fixed lengths ending in a name or ID can be left out
+        if ends_in_specifier(&p) {
+            prop_assert_eq!(actual, both, "{} ∩ {} at {}", a, b, p);
+        }
+        if !intersection.is_empty() {
+            prop_assert!(a.overlaps(&b));
+        }
+    }
+
+    #[test]
+    fn difference_never_grants_other(a in pattern(), b in pattern(), p in path()) {
+        let (a, b, p) = (item(a), item(b), item(p));
+        let difference = a.difference(&b);
+        if check(&p, &difference) {
+            prop_assert!(check_one(&p, &a) && !check_one(&p, &b), "{} - {} grants {}", a, b, p);
+        }
+        if a.subsumes(&b) {
+            prop_assert!(b.difference(&a).perms.is_empty(), "{} - {}", b, a);
+        }
+        // Nothing is removed, so only fixed lengths ending in a name or ID can be left out
+        if !a.overlaps(&b) && ends_in_specifier(&p) {
+            prop_assert_eq!(check(&p, &difference), check_one(&p, &a), "{} - {} at {}", a, b, p);
+        }
+    }
+}
+
+#[test]
+fn algebra_examples() -> Result<(), PermissionParseError> {
+    let to_strings =
+        |items: &[PermissionItem]| -> Vec<String> { items.iter().map(|p| p.to_string()).collect() };
+    let org_doc = parse("org.*.doc")?;
+    let org_1047 = item(vec!["org", "1047", "***"]);
+    assert_eq!(
+        to_strings(&org_doc.intersect(&org_1047)),
+        vec!["org.1047.doc"]
+    );
+    assert_eq!(org_doc.overlaps(&parse("org.1047.user")?), false);
+    assert_eq!(org_doc.subsumes(&parse("org.1047.doc.read")?), true);
+    assert_eq!(parse("org.1047.doc.read")?.subsumes(&org_doc), false);
+    assert_eq!(
+        to_strings(
+            &parse("org.*.doc.**")?
+                .difference(&parse("org.*.doc.read")?)
+                .perms
+        ),
+        vec![
+            "org.*.doc.write",
+            "org.*.doc.assign",
+            "org.*.doc.enact",
+            "org.*.doc.add",
+            "org.*.doc.remove",
+            "org.*.doc.read_one",
+            "org.*.doc.list_all",
+            "org.*.doc.*",
+        ]
+    );
+    assert_eq!(
+        parse("org.*")?
+            .difference(&parse("org.1047")?)
+            .perms
+            .is_empty(),
+        true
+    );
+    Ok(())
+}
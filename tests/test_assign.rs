@@ -0,0 +1,68 @@
+use permission_check::{can_assign, validate_grant_change};
+use permission_parser::{PermissionGroup, PermissionParseError, parse};
+
+#[test]
+fn assign_covers_prefix() -> Result<(), PermissionParseError> {
+    let granter: PermissionGroup = vec![
+        parse("org.1047.user.assign")?,
+        parse("org.*.doc.assign")?,
+        parse("team.5")?,
+        parse("org.1047.admin.read")?,
+        parse("org.***.report")?,
+    ]
+    .into();
+    for (target, expected) in [
+        ("org.1047.user", true),
+        ("org.1047.user.5.read", true),
+        ("org.1047.user.**", true),
+        ("org.1047.user.assign", true),
+        ("org.1048.doc.write", true),
+        ("org.*.doc", true),
+        ("team.5.member.add", true),
+        ("org.1047", false),
+        ("org.*.user.read", false),
+        ("org.1047.admin.read", false),
+        ("org.1047.report", false),
+        ("org.**", false),
+        ("team.6", false),
+    ] {
+        assert_eq!(
+            can_assign(&granter, &parse(target)?),
+            expected,
+            "{}",
+            target
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn grant_change_reports_offending_items() -> Result<(), PermissionParseError> {
+    let granter: PermissionGroup = parse("org.1047.doc.assign")?.into();
+    let old: PermissionGroup = vec![parse("org.1047.doc.read")?, parse("org.1048.doc")?].into();
+    let allowed: PermissionGroup =
+        vec![parse("org.1047.doc.write")?, parse("org.1048.doc")?].into();
+    assert_eq!(
+        validate_grant_change(&granter, &old, &allowed).is_ok(),
+        true
+    );
+    assert_eq!(validate_grant_change(&granter, &old, &old).is_ok(), true);
+
+    let new: PermissionGroup = vec![parse("org.1047.doc.read")?, parse("org.1047.user")?].into();
+    let err = validate_grant_change(&granter, &old, &new).unwrap_err();
+    assert_eq!(
+        err.granted
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>(),
+        vec!["org.1047.user"]
+    );
+    assert_eq!(
+        err.revoked
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>(),
+        vec!["org.1048.doc"]
+    );
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use permission_check::{ComplexCheck, GroupCheck, check};
+use permission_parser::{
+    Bindings, PermissionGroup, PermissionItem, PermissionParseError, expr_parse,
+    expr_parse_pattern, parse, tokenizer::Field,
+};
+
+fn docs(ids: impl Iterator<Item = u64>) -> Result<Vec<PermissionItem>, PermissionParseError> {
+    ids.map(|id| parse(&format!("org.1047.doc.{}.read", id)))
+        .collect()
+}
+
+#[test]
+fn check_many_matches_check() -> Result<(), PermissionParseError> {
+    let mut requires = docs(0..50)?;
+    requires.push(parse("org.1047.doc.*.read")?);
+    requires.push(parse("org.1047.doc")?);
+    requires.push(parse("org.1047.user.3.read")?);
+    requires.push(parse("org.1048.doc.3.read")?);
+    requires.push(parse("org.1047.doc.3.write")?);
+    let groups: Vec<PermissionGroup> = vec![
+        vec![].into(),
+        vec![parse("org.1047.doc.3.read")?, parse("org.1047.doc.7")?].into(),
+        vec![parse("org.1047.doc.*.read")?].into(),
+        vec![parse("org.*.doc.**")?].into(),
+        vec![parse("org.*.***.read")?].into(),
+        vec![parse("***.read")?, parse("org.1048")?].into(),
+        vec![parse("org.1047.doc")?, parse("org.1047.doc.12.write")?].into(),
+        vec![parse("org.1047")?].into(),
+        vec![parse("org.1047.user.3")?, parse("org.1047.doc.**.read")?].into(),
+    ];
+    for group in &groups {
+        let result = group.check_many(&requires);
+        for (idx, require) in requires.iter().enumerate() {
+            assert_eq!(result[idx], check(require, group));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn filter_keeps_order() -> Result<(), PermissionParseError> {
+    let requires = docs(0..10)?;
+    let group: PermissionGroup = vec![
+        parse("org.1047.doc.7.read")?,
+        parse("org.1047.doc.2")?,
+        parse("org.1047.doc.5.write")?,
+    ]
+    .into();
+    assert_eq!(
+        group
+            .filter(&requires)
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>(),
+        vec!["org.1047.doc.2.read", "org.1047.doc.7.read"]
+    );
+    assert_eq!(group.filter(&[]).is_empty(), true);
+    Ok(())
+}
+
+#[test]
+fn with_perm_many_binds_each_value() -> Result<(), PermissionParseError> {
+    let checker = ComplexCheck::from(&expr_parse_pattern(
+        "(org.1047.doc.{id}.read & !org.1047.doc.{id}.locked) | org.1047.role.admin.enact",
+    )?);
+    let group: PermissionGroup = vec![
+        parse("org.1047.doc.1")?,
+        parse("org.1047.doc.2.read")?,
+        parse("org.1047.doc.3.read")?,
+        parse("org.1047.doc.3.locked")?,
+    ]
+    .into();
+    let bindings: Vec<_> = (0..5)
+        .map(|id| Bindings::from([("id".to_string(), Field::ID { id })]))
+        .collect();
+    let result = checker.with_perm_many(&group, &bindings)?;
+    assert_eq!(
+        result.iter().map(|b| *b).collect::<Vec<_>>(),
+        vec![false, false, true, false, false]
+    );
+    let mut admin = group.clone();
+    admin.add(parse("org.1047.role.admin.enact")?);
+    assert_eq!(checker.with_perm_many(&admin, &bindings)?.all(), true);
+    Ok(())
+}
+
+#[test]
+fn with_perm_many_errors() -> Result<(), PermissionParseError> {
+    let checker = ComplexCheck::from(&expr_parse_pattern("org.{org}.doc.{id}.read")?);
+    let group = parse("org")?;
+    assert_eq!(
+        checker
+            .with_perm_many(
+                &group,
+                &[Bindings::from([("id".to_string(), Field::ID { id: 1 })])]
+            )
+            .is_err(),
+        true
+    );
+    let closure_check = ComplexCheck::new(Box::new(|_| true));
+    assert_eq!(closure_check.with_perm_many(&group, &[]).is_err(), true);
+    let plain = ComplexCheck::from(&expr_parse("org.1.doc")?);
+    assert_eq!(plain.with_perm_many(&group, &[]).map(|r| r.len())?, 0);
+    Ok(())
+}
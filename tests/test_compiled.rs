@@ -0,0 +1,63 @@
+use permission_check::{CompiledExpr, check_expr};
+use permission_parser::{PermissionGroup, PermissionParseError, expr_parse, parse};
+
+fn groups() -> Result<Vec<PermissionGroup>, PermissionParseError> {
+    Ok(vec![
+        parse("org.1047.user.243")?.into(),
+        parse("org.1048.user.243")?.into(),
+        parse("org.1047.role.owner")?.into(),
+        parse("org")?.into(),
+        parse("*")?.into(),
+        vec![parse("org.1047.user.243.read")?].into(),
+        vec![
+            parse("org.1047.user.243.read")?,
+            parse("org.1047.user.243.write")?,
+        ]
+        .into(),
+        vec![
+            parse("org.1047.user.write")?,
+            parse("org.1047.user.read_one")?,
+        ]
+        .into(),
+        vec![
+            parse("org.1047.role.owner")?,
+            parse("user.blacklist.enact")?,
+        ]
+        .into(),
+        vec![parse("org.1047.role.owner")?, parse("user.blacklist.***")?].into(),
+        vec![].into(),
+    ])
+}
+
+#[test]
+fn compiled_matches_tree_walk() -> Result<(), PermissionParseError> {
+    for expr in [
+        "((org.1047.role.admin.enact | org.1047.role.owner.enact) | (org.1047.user.write && (org.1047.user.read | org.1047.user.read_one)) | (org.1047.user.243.read && org.1047.user.243.write)) & !(user.blacklist.enact & !user.blacklist.*)",
+        "org.1047.user.read ^ org.1047.user.write ^ org.1047.user.read_one",
+        "!(org.1047.user.read | !org.1047.user.write) & !!org.1047",
+        "(org.1047.user.read) ^ (org.1047.user.read & org.1048)",
+    ] {
+        let expr = expr_parse(expr)?;
+        let program = CompiledExpr::compile(&expr);
+        let groups = groups()?;
+        let expected: Vec<bool> = groups.iter().map(|g| check_expr(&expr, g)).collect();
+        assert_eq!(
+            groups.iter().map(|g| program.eval(g)).collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(program.eval_many(&groups), expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn compiled_deduplicates_leaves() -> Result<(), PermissionParseError> {
+    let expr = expr_parse(
+        "(org.1047.user.read & org.1047.user.write) | (org.1047.user.read & !org.1047.user.write) | org.1047.user.read",
+    )?;
+    let program = CompiledExpr::compile(&expr);
+    assert_eq!(program.leaves().len(), 2);
+    assert_eq!(program.eval(&parse("org.1047.user.read")?.into()), true);
+    assert_eq!(program.eval(&parse("org.1047.user.write")?.into()), false);
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use permission_check::{
+    CompiledExpr, ComplexCheck, GroupCheck, PermittedValues, can_assign, check, check_expr,
+    check_expr_with_context, check_with_context, permitted_values,
+};
+use permission_parser::{
+    Condition, ItemExpr, PermissionGroup, PermissionParseError, Value, expr_parse, parse,
+    parse_condition, parse_pattern, tokenizer::Field,
+};
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+fn context(attrs: &[(&str, Value)]) -> HashMap<String, Value> {
+    attrs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect()
+}
+
+#[test]
+fn condition_eval() -> Result<(), PermissionParseError> {
+    let ctx = context(&[
+        ("network", "corp".into()),
+        ("mfa", true.into()),
+        ("mfa_age", 300.into()),
+        ("request.region", "eu".into()),
+    ]);
+    for (condition, expected) in [
+        (r#"network == "corp""#, true),
+        (r#"network != "corp""#, false),
+        ("mfa && mfa_age <= 900", true),
+        ("mfa_age > 900 || !mfa", false),
+        ("mfa_age >= 300 && mfa_age < 301", true),
+        (r#"request.region in ["eu", "uk"]"#, true),
+        (r#"request.region in ["us"]"#, false),
+        ("mfa_age == -5 || (mfa && !(mfa_age > 600))", true),
+        // Missing attributes and mismatched types never compare
+        (r#"device != "laptop""#, false),
+        (r#"mfa_age != "300""#, false),
+        ("missing", false),
+        ("!missing", true),
+    ] {
+        assert_eq!(
+            parse_condition(condition)?.eval(&ctx),
+            expected,
+            "{}",
+            condition
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn condition_display_roundtrip() -> Result<(), PermissionParseError> {
+    for condition in [
+        r#"network == "corp""#,
+        "a == 1 || b == 2 && c == 3",
+        "(a == 1 || b == 2) && c == 3",
+        "a == 1 && (b == 2 && c == 3)",
+        r#"!(region in ["eu", "uk"]) || mfa == true"#,
+        r#"name == "quote \" and \\ backslash""#,
+        "level >= -9223372036854775808",
+    ] {
+        let parsed = parse_condition(condition)?;
+        assert_eq!(
+            parse_condition(&parsed.to_string())? == parsed,
+            true,
+            "{}",
+            condition
+        );
+    }
+    assert_eq!(
+        parse_condition("a == 1 || b == 2 && c == 3")?.to_string(),
+        "a == 1 || b == 2 && c == 3"
+    );
+    assert_eq!(
+        parse_condition("(a == 1 || b == 2) && c")?.to_string(),
+        "(a == 1 || b == 2) && c == true"
+    );
+    assert_eq!(
+        parse_condition("!!a == 1 || !(b && !c in [1])")?.to_string(),
+        "!!a == 1 || !(b == true && !c in [1])"
+    );
+    for invalid in [
+        "",
+        "a ==",
+        "1",
+        "a == 1.5",
+        "a in 1",
+        "a == 1 b == 2",
+        "a == 99999999999999999999",
+    ] {
+        assert_eq!(parse_condition(invalid).is_err(), true, "{}", invalid);
+    }
+    let deep = format!("{}a{}", "(".repeat(100), ")".repeat(100));
+    assert_eq!(parse_condition(&deep).is_err(), true);
+    Ok(())
+}
+
+#[test]
+fn check_with_context_applies_conditions() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = vec![
+        parse("org.1047.doc.*.read")?,
+        parse("org.1047.doc.**")?.when(parse_condition(
+            r#"network == "corp" && mfa && mfa_age <= 900"#,
+        )?),
+    ]
+    .into();
+    let write = parse("org.1047.doc.3.write")?;
+    let read = parse("org.1047.doc.3.read")?;
+    let office = context(&[
+        ("network", "corp".into()),
+        ("mfa", true.into()),
+        ("mfa_age", 60.into()),
+    ]);
+    let stale = context(&[
+        ("network", "corp".into()),
+        ("mfa", true.into()),
+        ("mfa_age", 3600.into()),
+    ]);
+    assert_eq!(check_with_context(&write, &group, &office), true);
+    assert_eq!(check_with_context(&write, &group, &stale), false);
+    assert_eq!(check_with_context(&write, &group, &HashMap::new()), false);
+    assert_eq!(check_with_context(&read, &group, &HashMap::new()), true);
+    // Without a context conditional grants are skipped
+    assert_eq!(check(&write, &group), false);
+    assert_eq!(check(&read, &group), true);
+    Ok(())
+}
+
+#[test]
+fn every_path_applies_conditions() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = vec![
+        parse("org.1047.doc.1.read")?,
+        parse("org.1047.doc.**")?.when(parse_condition("mfa && mfa_age <= 900")?),
+        // Holds for an empty context, but there is none without one
+        parse("org.1048.assign")?.when(parse_condition("!mfa")?),
+    ]
+    .into();
+    let office = context(&[("mfa", true.into()), ("mfa_age", 60.into())]);
+    let stale = context(&[("mfa", true.into()), ("mfa_age", 3600.into())]);
+    let expr = expr_parse("org.1047.doc.3.write & ctx(mfa)")?;
+    let compiled = CompiledExpr::compile(&expr);
+    let checker = ComplexCheck::from(&expr);
+    assert_eq!(check_expr_with_context(&expr, &group, &office), true);
+    assert_eq!(check_expr_with_context(&expr, &group, &stale), false);
+    assert_eq!(check_expr(&expr, &group), false);
+    assert_eq!(compiled.eval_with_context(&group, &office), true);
+    assert_eq!(compiled.eval_with_context(&group, &stale), false);
+    assert_eq!(compiled.eval(&group), false);
+    assert_eq!(
+        compiled.eval_many(std::slice::from_ref(&group)),
+        vec![false]
+    );
+    assert_eq!(checker.with_perm_and_context(&group, &office), true);
+    assert_eq!(checker.with_perm_and_context(&group, &stale), false);
+    assert_eq!(checker.with_perm(&group), false);
+    let exists = expr_parse("exists d: org.1047.doc.{d}.write")?;
+    assert_eq!(check_expr_with_context(&exists, &group, &office), true);
+    assert_eq!(check_expr_with_context(&exists, &group, &stale), false);
+    let requires = vec![
+        parse("org.1047.doc.1.read")?,
+        parse("org.1047.doc.3.write")?,
+    ];
+    assert_eq!(
+        group
+            .check_many(&requires)
+            .iter()
+            .map(|b| *b)
+            .collect::<Vec<bool>>(),
+        vec![true, false]
+    );
+    assert_eq!(
+        permitted_values(&group, &parse_pattern("org.1047.doc.{d}.read")?)?
+            == PermittedValues::Values(vec![Field::ID { id: 1 }]),
+        true
+    );
+    let empty = context(&[]);
+    assert_eq!(
+        check_with_context(&parse("org.1048.assign")?, &group, &empty),
+        true
+    );
+    assert_eq!(check(&parse("org.1048.assign")?, &group), false);
+    assert_eq!(can_assign(&group, &parse("org.1048.user.1")?), false);
+    Ok(())
+}
+
+// Source text with `layers` of `!`, `&&` and `||` around a comparison, near the depth limit
+fn nested_condition(layers: &[u8]) -> String {
+    layers
+        .iter()
+        .fold("a == 1".to_string(), |inner, layer| match layer {
+            0 => format!("!{}", inner),
+            1 => format!("b == 2 && ({})", inner),
+            _ => format!("({}) || c", inner),
+        })
+}
+
+proptest! {
+    // Whatever parses also decodes, however its string form is nested
+    #[test]
+    fn nested_conditions_survive_encoding(layers in prop::collection::vec(0u8..3, 0..80)) {
+        let source = nested_condition(&layers);
+        if let Ok(condition) = parse_condition(&source) {
+            let group: PermissionGroup = parse("org.1047").unwrap().when(condition).into();
+            let decoded = PermissionGroup::decode(&group.encode().unwrap());
+            prop_assert!(decoded.is_ok_and(|decoded| decoded.perms == group.perms), "{}", source);
+        }
+    }
+}
+
+#[test]
+fn condition_survives_encoding() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = vec![
+        parse("org.1047.doc.**")?.when(parse_condition(r#"network in ["corp", "vpn"]"#)?),
+        parse("org.1048")?,
+    ]
+    .into();
+    let decoded = PermissionGroup::decode(&group.encode().unwrap()).unwrap();
+    assert_eq!(decoded.perms == group.perms, true);
+    Ok(())
+}
+
+#[test]
+fn long_condition_chains() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = parse("org.1047")?.into();
+    let mfa = context(&[("a", true.into())]);
+    for terms in [12_000, 21_000] {
+        let chain = vec!["a"; terms].join("&&");
+        let expr = expr_parse(&format!("ctx({}) & org.1047", chain))?;
+        match &expr {
+            ItemExpr::And(operands) => match &operands[0] {
+                ItemExpr::Predicate(Condition::And(operands)) => {
+                    assert_eq!(operands.len(), terms)
+                }
+                _ => panic!("not a chain of conditions"),
+            },
+            _ => panic!("not an and"),
+        }
+        assert_eq!(check_expr_with_context(&expr, &group, &mfa), true);
+        // Decoded though its spaced out string form is longer than the parser's limit
+        let decoded = ItemExpr::decode(&expr.encode().unwrap()).unwrap();
+        assert_eq!(decoded == expr, true);
+        let chain = vec!["a"; terms].join("||");
+        let condition = parse_condition(&format!("b || {}", chain))?;
+        assert_eq!(condition.eval(&mfa), true);
+    }
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use permission_check::{Delegate, DelegationError, check};
+use permission_parser::{
+    PermissionGroup, PermissionItem, PermissionParseError,
+    tokenizer::{Field, Specifier},
+};
+
+// Builds the item field by field, `parse` lexes `1047.***` as a float
+fn parse(permission: &str) -> Result<PermissionItem, PermissionParseError> {
+    let item: PermissionItem = permission
+        .split('.')
+        .map(|segment| match segment {
+            "*" => Field::Glob,
+            "**" => Field::DoubleGlob,
+            "***" => Field::TripleGlob,
+            "read" => Specifier::Read.into(),
+            "assign" => Specifier::Assign.into(),
+            segment => match segment.parse::<u64>() {
+                Ok(id) => Field::ID { id },
+                Err(_) => Field::Name {
+                    name: segment.to_string(),
+                },
+            },
+        })
+        .collect::<Vec<Field>>()
+        .into();
+    item.validate()?;
+    Ok(item)
+}
+
+const PATTERNS: [&str; 16] = [
+    "org",
+    "org.1047",
+    "org.*",
+    "org.**",
+    "org.***",
+    "org.*.doc.read",
+    "org.1047.***",
+    "org.***.read",
+    "org.***.doc",
+    "***.read",
+    "org.1047.doc.**",
+    "org.**.doc",
+    "org.1047.doc.read",
+    "org.*.assign",
+    "*.1047.***.doc.read",
+    "org.1048.*.*",
+];
+
+// Every valid permission up to four segments over a small alphabet
+fn paths() -> Result<Vec<PermissionItem>, PermissionParseError> {
+    let segments = ["org", "doc", "1047", "1048"];
+    let mut paths: Vec<String> = vec![];
+    let mut current: Vec<String> = vec![String::new()];
+    for _ in 0..4 {
+        current = current
+            .iter()
+            .flat_map(|prefix| {
+                segments.iter().map(move |segment| match prefix.is_empty() {
+                    true => segment.to_string(),
+                    false => format!("{}.{}", prefix, segment),
+                })
+            })
+            .collect();
+        for path in &current {
+            paths.push(path.clone());
+            if path.matches('.').count() < 3 {
+                paths.push(format!("{}.read", path));
+                paths.push(format!("{}.assign", path));
+            }
+        }
+    }
+    paths.iter().map(|p| parse(p)).collect()
+}
+
+#[test]
+fn delegate_is_intersection() -> Result<(), PermissionParseError> {
+    let paths = paths()?;
+    // `***` followed by a name or ID can intersect to a fixed-length part ending in a name or
+    // ID, which no grant can express and is left out
+    let lossy = "org.***.doc";
+    for requested in PATTERNS {
+        for held in PATTERNS {
+            let holder: PermissionGroup = parse(held)?.into();
+            let request: PermissionGroup = parse(requested)?.into();
+            let Ok(delegated) = holder.delegate(&request) else {
+                continue;
+            };
+            let exact = requested != lossy && held != lossy;
+            for path in &paths {
+                let expected = check(path, &holder) && check(path, &request);
+                let actual = check(path, &delegated);
+                assert_eq!(
+                    actual && !expected,
+                    false,
+                    "{} ∩ {} grants {}",
+                    requested,
+                    held,
+                    path
+                );
+                let ends_in_specifier =
+                    matches!(path.perm.last(), Some(Field::Specifier { specifier: _ }));
+                if exact || ends_in_specifier {
+                    assert_eq!(actual, expected, "{} ∩ {} at {}", requested, held, path);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn delegate_examples() -> Result<(), PermissionParseError> {
+    let holder: PermissionGroup = vec![parse("org.1047.***")?, parse("user.5.read")?].into();
+    let requested: PermissionGroup = vec![
+        parse("org.*.doc.read")?,
+        parse("user.*.read")?,
+        parse("org.1048.doc")?,
+    ]
+    .into();
+    let delegated = holder.delegate(&requested).unwrap();
+    assert_eq!(
+        delegated
+            .perms
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>(),
+        vec!["org.1047.doc.read", "user.5.read"]
+    );
+    assert_eq!(
+        holder.delegate(&vec![].into()).unwrap().perms.is_empty(),
+        true
+    );
+    Ok(())
+}
+
+#[test]
+fn delegate_requires_held_assign() -> Result<(), PermissionParseError> {
+    let holder: PermissionGroup =
+        vec![parse("org.1047.doc")?, parse("org.1047.user.*.assign")?].into();
+    let requested: PermissionGroup = parse("org.1047.doc.*.assign")?.into();
+    assert_eq!(
+        holder
+            .delegate(&requested)
+            .unwrap()
+            .perms
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>(),
+        vec!["org.1047.doc.*.assign"]
+    );
+    assert_eq!(
+        holder
+            .delegate(&parse("org.1047.user.3.assign")?.into())
+            .is_ok(),
+        true
+    );
+    let too_broad: PermissionGroup =
+        vec![parse("org.1047.doc.read")?, parse("org.*.user.*.assign")?].into();
+    assert_eq!(
+        matches!(
+            holder.delegate(&too_broad),
+            Err(DelegationError::AssignNotHeld(item)) if item.to_string() == "org.*.user.*.assign"
+        ),
+        true
+    );
+    Ok(())
+}
+
+#[test]
+fn delegate_keeps_restrictions() -> Result<(), PermissionParseError> {
+    use permission_parser::parse_condition;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let holder: PermissionGroup = parse("org.1047")?
+        .expires_at(UNIX_EPOCH + Duration::from_secs(100))
+        .when(parse_condition("mfa")?)
+        .into();
+    let requested: PermissionGroup = parse("org.1047.doc")?
+        .not_before(UNIX_EPOCH + Duration::from_secs(10))
+        .expires_at(UNIX_EPOCH + Duration::from_secs(200))
+        .into();
+    let delegated = holder.delegate(&requested).unwrap();
+    assert_eq!(delegated.perms.len(), 1);
+    let item = &delegated.perms[0];
+    assert_eq!(
+        item.validity.not_before,
+        Some(UNIX_EPOCH + Duration::from_secs(10))
+    );
+    assert_eq!(
+        item.validity.expires_at,
+        Some(UNIX_EPOCH + Duration::from_secs(100))
+    );
+    assert_eq!(item.condition == Some(parse_condition("mfa")?), true);
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use permission_check::ComplexCheck;
+use permission_parser::{ItemExpr, PermissionParseError, expr_parse, parse, parse_condition};
+use proptest::prelude::*;
+use std::fmt::{self, Debug, Display, Formatter};
+
+const PERMISSIONS: [&str; 6] = [
+    "org",
+    "org.1047.read",
+    "org.*.user.write",
+    "org.1048.user.243",
+    "user.blacklist.***",
+    "**.read_one",
+];
+
+const CONDITIONS: [&str; 3] = ["mfa", "owner_id == subject_id", r#"region in ["eu", "uk"]"#];
+
+// Shows generated values with `Display`, as the parser's types only derive `Debug` in debug builds
+#[derive(Clone)]
+struct Shown<T>(T);
+
+impl<T: Display> Debug for Shown<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn unshown(operands: Vec<Shown<ItemExpr>>) -> Vec<ItemExpr> {
+    operands.into_iter().map(|Shown(e)| e).collect()
+}
+
+fn leaf() -> impl Strategy<Value = Shown<ItemExpr>> {
+    prop_oneof![
+        prop::sample::select(PERMISSIONS.to_vec())
+            .prop_map(|p| Shown(ItemExpr::Permission(parse(p).unwrap()))),
+        prop::sample::select(CONDITIONS.to_vec())
+            .prop_map(|c| Shown(ItemExpr::Predicate(parse_condition(c).unwrap()))),
+    ]
+}
+
+// Any tree, including shapes the parser never builds such as an unbracketed `!` on the left
+fn expr() -> impl Strategy<Value = Shown<ItemExpr>> {
+    leaf().prop_recursive(6, 48, 2, |inner| {
+        prop_oneof![
+            inner
+                .clone()
+                .prop_map(|Shown(e)| Shown(ItemExpr::Not(Box::new(e)))),
+            inner
+                .clone()
+                .prop_map(|Shown(e)| Shown(ItemExpr::Bracketed(Box::new(e)))),
+            prop::collection::vec(inner.clone(), 2..4)
+                .prop_map(|operands| Shown(ItemExpr::And(unshown(operands)))),
+            prop::collection::vec(inner.clone(), 2..4)
+                .prop_map(|operands| Shown(ItemExpr::Or(unshown(operands)))),
+            prop::collection::vec(inner.clone(), 2..4)
+                .prop_map(|operands| Shown(ItemExpr::Xor(unshown(operands)))),
+            prop::collection::vec(inner, 1..5)
+                .prop_flat_map(|operands| (1..=operands.len(), Just(operands)))
+                .prop_map(|(count, operands)| Shown(ItemExpr::AtLeast(count, unshown(operands)))),
+        ]
+    })
+}
+
+fn unbracketed(expr: &ItemExpr) -> ItemExpr {
+    let operands = |operands: &[ItemExpr]| operands.iter().map(unbracketed).collect();
+    match expr {
+        ItemExpr::Bracketed(e) => unbracketed(e),
+        ItemExpr::Not(e) => ItemExpr::Not(Box::new(unbracketed(e))),
+        ItemExpr::And(chain) => ItemExpr::And(operands(chain)),
+        ItemExpr::Or(chain) => ItemExpr::Or(operands(chain)),
+        ItemExpr::Xor(chain) => ItemExpr::Xor(operands(chain)),
+        ItemExpr::AtLeast(count, chain) => ItemExpr::AtLeast(*count, operands(chain)),
+        leaf => leaf.clone(),
+    }
+}
+
+proptest! {
+    #[test]
+    fn display_round_trips(Shown(expr) in expr()) {
+        let text = expr.to_string();
+        let parsed = expr_parse(&text).unwrap();
+        // Brackets are only added where the tree needs them, and kept where it has them
+        prop_assert!(unbracketed(&parsed) == unbracketed(&expr), "{}", text);
+        prop_assert_eq!(parsed.to_string(), text.clone());
+        prop_assert!(expr_parse(&text).unwrap() == parsed);
+    }
+}
+
+#[test]
+fn display_parsed_expr() -> Result<(), PermissionParseError> {
+    for expr in [
+        "org.1047.user.read ^ org.1047.user.write ^ org.1047.user.read_one",
+        "!(org.1047.user.read | !org.1047.user.write) & !!org.1047",
+        "(org.1047.doc.3.write & ctx(owner_id == subject_id)) | org.1047.role.admin.enact",
+        "org.a & !org.b | org.c",
+    ] {
+        let parsed = expr_parse(expr)?;
+        assert_eq!(parsed.to_string(), expr);
+        assert_eq!(expr_parse(&parsed.to_string())? == parsed, true);
+    }
+    assert_eq!(
+        expr_parse("org.a&&(org.b||org.c)")?.to_string(),
+        "org.a & (org.b | org.c)"
+    );
+    let permission =
+        |p: &str| -> Result<ItemExpr, PermissionParseError> { Ok(ItemExpr::Permission(parse(p)?)) };
+    let not_on_left = ItemExpr::And(vec![
+        ItemExpr::Not(Box::new(permission("org.a")?)),
+        permission("org.b")?,
+    ]);
+    assert_eq!(not_on_left.to_string(), "(!org.a) & org.b");
+    // A chain of the same operator last would join the outer one
+    let nested = ItemExpr::And(vec![
+        permission("org.a")?,
+        ItemExpr::And(vec![permission("org.b")?, permission("org.c")?]),
+    ]);
+    assert_eq!(nested.to_string(), "org.a & (org.b & org.c)");
+    assert_eq!(
+        matches!(expr_parse("org.a & org.b & org.c")?, ItemExpr::And(operands) if operands.len() == 3),
+        true
+    );
+    Ok(())
+}
+
+#[test]
+fn complex_check_keeps_source() -> Result<(), PermissionParseError> {
+    let expr = expr_parse("org.1047.doc.read & !user.blacklist.enact")?;
+    let checker = ComplexCheck::from(&expr);
+    assert_eq!(checker.source() == Some(&expr), true);
+    assert_eq!(
+        checker.source().map(|e| e.to_string()),
+        Some("org.1047.doc.read & !user.blacklist.enact".to_string())
+    );
+    let closure = ComplexCheck::new(Box::new(|_| true));
+    assert_eq!(closure.source().is_none(), true);
+    Ok(())
+}
@@ -0,0 +1,261 @@
+use permission_parser::{
+    EncodingError, ItemExpr, PermissionGroup, PermissionItem, encoding::FORMAT_VERSION, expr_parse,
+    expr_parse_pattern, parse, parse_condition, tokenizer::Field,
+};
+
+#[test]
+fn item_round_trip() {
+    for perm in [
+        "org.1047.user.243.read",
+        "a.b.cd.***.b.1974.add",
+        "perm.**.*.list_all",
+        "x.read_one",
+        "***",
+    ] {
+        let item = parse(perm).unwrap();
+        let decoded = PermissionItem::decode(&item.encode().unwrap()).unwrap();
+        assert_eq!(decoded.to_string(), perm);
+    }
+    let item = PermissionItem::from(vec![
+        Field::Name {
+            name: "org".to_string(),
+        },
+        Field::ID { id: u64::MAX },
+    ]);
+    let decoded = PermissionItem::decode(&item.encode().unwrap()).unwrap();
+    assert_eq!(decoded.to_string(), "org.18446744073709551615");
+}
+
+#[test]
+fn group_round_trip_interns_names() {
+    let group: PermissionGroup = vec![
+        parse("organisation.1047.member.1.read").unwrap(),
+        parse("organisation.1047.member.2.read").unwrap(),
+        parse("organisation.1047.member.3.write").unwrap(),
+    ]
+    .into();
+    let encoded = group.encode().unwrap();
+    assert_eq!(
+        encoded
+            .windows("organisation".len())
+            .filter(|w| *w == b"organisation")
+            .count(),
+        1
+    );
+    let decoded = PermissionGroup::decode(&encoded).unwrap();
+    assert_eq!(
+        decoded
+            .perms
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>(),
+        group
+            .perms
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn expr_round_trip() {
+    let expr = expr_parse(
+        "((org.1047.role.admin.enact | org.1047.role.owner.enact) ^ org.*.user.read) & !(user.blacklist.enact & !user.blacklist.*)",
+    )
+    .unwrap();
+    let encoded = expr.encode().unwrap();
+    let decoded = ItemExpr::decode(&encoded).unwrap();
+    assert_eq!(decoded.encode().unwrap(), encoded);
+}
+
+#[test]
+fn condition_round_trip() {
+    let condition = r#"!(mfa_age > -900 && request.ip != "10.0.0.1") || network in ["corp", "vpn", 3, false] && !!r#type == true"#;
+    let group: PermissionGroup = vec![
+        parse("org.1047.doc.read")
+            .unwrap()
+            .when(parse_condition(condition).unwrap()),
+        parse("org.1048.doc.read")
+            .unwrap()
+            .when(parse_condition("network == \"corp\"").unwrap()),
+    ]
+    .into();
+    let encoded = group.encode().unwrap();
+    // Conditions are encoded as a tree, with their strings in the name table
+    assert_eq!(encoded.windows(4).filter(|w| *w == b"corp").count(), 1);
+    assert_eq!(encoded.windows(2).any(|w| w == b"||"), false);
+    let decoded = PermissionGroup::decode(&encoded).unwrap();
+    assert_eq!(decoded.perms == group.perms, true);
+    let expr = expr_parse(&format!("org.1047.doc.read & ctx({})", condition)).unwrap();
+    assert_eq!(
+        ItemExpr::decode(&expr.encode().unwrap()).unwrap() == expr,
+        true
+    );
+}
+
+#[test]
+fn placeholder_names_round_trip() {
+    for source in [
+        "exists u: org.{u}.read",
+        "exists r#type: org.{r#type:id}.read",
+        "exists ünïcode, _v: org.{ünïcode}.{_v:name}",
+    ] {
+        let expr = expr_parse(source).unwrap();
+        let decoded = ItemExpr::decode(&expr.encode().unwrap()).unwrap();
+        assert_eq!(decoded == expr, true, "{}", source);
+        assert_eq!(decoded.to_string(), expr.to_string());
+    }
+    assert_eq!(
+        expr_parse_pattern("exists u: org.{u}.read")
+            .unwrap()
+            .encode()
+            .is_ok(),
+        true
+    );
+}
+
+// Swaps a name of the table, which is short enough for its length to be a single byte
+fn rename(encoded: &[u8], from: &str, to: &str) -> Vec<u8> {
+    let at = encoded
+        .windows(from.len())
+        .position(|w| w == from.as_bytes())
+        .unwrap();
+    let mut input = encoded[..at - 1].to_vec();
+    input.push(to.len() as u8);
+    input.extend_from_slice(to.as_bytes());
+    input.extend_from_slice(&encoded[at + from.len()..]);
+    input
+}
+
+#[test]
+fn decode_rejects_invalid_names() {
+    let var = expr_parse("exists vv: org.{vv}.read")
+        .unwrap()
+        .encode()
+        .unwrap();
+    let attr = expr_parse("ctx(vv.x > 1)").unwrap().encode().unwrap();
+    assert_eq!(ItemExpr::decode(&rename(&var, "vv", "ok")).is_ok(), true);
+    assert_eq!(
+        ItemExpr::decode(&rename(&attr, "vv.x", "r#in.fn")).is_ok(),
+        true
+    );
+    for (encoded, from, invalid) in [
+        (&var, "vv", ""),
+        (&var, "vv", "1v"),
+        (&var, "vv", "a-b"),
+        (&var, "vv", "fn"),
+        (&var, "vv", "self"),
+        (&var, "vv", "r#self"),
+        (&var, "vv", "r#"),
+        (&attr, "vv.x", ""),
+        (&attr, "vv.x", "true"),
+        (&attr, "vv.x", "in.x"),
+        (&attr, "vv.x", "a..b"),
+        (&attr, "vv.x", "a.1"),
+        (&attr, "vv.x", "a ||"),
+    ] {
+        assert_eq!(
+            matches!(
+                ItemExpr::decode(&rename(encoded, from, invalid)),
+                Err(EncodingError::Invalid(_))
+            ),
+            true,
+            "{}",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn decode_rejects_invalid_input() {
+    let encoded = parse("org.1047.user.read").unwrap().encode().unwrap();
+    assert_eq!(
+        matches!(
+            PermissionItem::decode(&encoded[..encoded.len() - 1]),
+            Err(EncodingError::UnexpectedEnd)
+        ),
+        true
+    );
+    let mut trailing = encoded.clone();
+    trailing.push(0);
+    assert_eq!(
+        matches!(
+            PermissionItem::decode(&trailing),
+            Err(EncodingError::TrailingBytes(1))
+        ),
+        true
+    );
+    let mut version = encoded.clone();
+    version[0] = FORMAT_VERSION + 1;
+    assert_eq!(
+        matches!(
+            PermissionItem::decode(&version),
+            Err(EncodingError::UnsupportedVersion(_))
+        ),
+        true
+    );
+    assert_eq!(
+        matches!(
+            PermissionGroup::decode(&encoded),
+            Err(EncodingError::UnexpectedKind { .. })
+        ),
+        true
+    );
+}
+
+#[test]
+fn decode_enforces_parse_invariants() {
+    // version, kind, no names, 2 fields
+    let triple_twice = [FORMAT_VERSION, 0x01, 0, 2, 0x05, 0x05];
+    assert_eq!(
+        matches!(
+            PermissionItem::decode(&triple_twice),
+            Err(EncodingError::Invalid(_))
+        ),
+        true
+    );
+    let specifier_in_middle = [FORMAT_VERSION, 0x01, 0, 2, 0x02, 0x00, 0x03];
+    assert_eq!(
+        matches!(
+            PermissionItem::decode(&specifier_in_middle),
+            Err(EncodingError::Invalid(_))
+        ),
+        true
+    );
+    let empty = [FORMAT_VERSION, 0x01, 0, 0];
+    assert_eq!(PermissionItem::decode(&empty).is_err(), true);
+    let bad_name_index = [FORMAT_VERSION, 0x01, 0, 1, 0x00, 0x00];
+    assert_eq!(
+        matches!(
+            PermissionItem::decode(&bad_name_index),
+            Err(EncodingError::InvalidNameIndex(0))
+        ),
+        true
+    );
+    let mut deep = vec![FORMAT_VERSION, 0x03, 0];
+    deep.extend(std::iter::repeat_n(0x01, 100_000));
+    assert_eq!(
+        matches!(ItemExpr::decode(&deep), Err(EncodingError::TooDeep)),
+        true
+    );
+}
+
+#[test]
+fn decode_arbitrary_bytes_does_not_panic() {
+    let mut state: u64 = 0x2545f4914f6cdd1d;
+    for _ in 0..20_000 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let mut input = vec![FORMAT_VERSION, (state % 4) as u8];
+        for _ in 0..(state % 32) {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            input.push((state >> 24) as u8);
+        }
+        let _ = PermissionItem::decode(&input);
+        let _ = PermissionGroup::decode(&input);
+        let _ = ItemExpr::decode(&input);
+    }
+}
@@ -0,0 +1,273 @@
+use permission_check::{CompiledExpr, ComplexCheck, check_expr, exists};
+use permission_parser::{
+    Bindings, EncodingError, ExprLibrary, ItemExpr, Limit, ParseLimits, PermissionGroup,
+    PermissionItem, PermissionParseError, encoding::FORMAT_VERSION, expr_parse, expr_parse_pattern,
+    expr_parse_with_limits, parse, tokenizer::Field,
+};
+
+fn group(perms: &[&str]) -> Result<PermissionGroup, PermissionParseError> {
+    Ok(perms
+        .iter()
+        .map(|p| parse(p))
+        .collect::<Result<Vec<PermissionItem>, PermissionParseError>>()?
+        .into())
+}
+
+#[test]
+fn exists_parse() -> Result<(), PermissionParseError> {
+    match expr_parse("exists u, v: org.{u}.user.{v}.read & org.{u}.user.{v}.write")? {
+        ItemExpr::Exists(vars, body) => {
+            assert_eq!(vars, vec!["u".to_string(), "v".to_string()]);
+            assert_eq!(matches!(*body, ItemExpr::And(_)), true);
+        }
+        _ => panic!("not an exists"),
+    }
+    assert_eq!(
+        matches!(expr_parse("exists.read & org.exists")?, ItemExpr::And(_)),
+        true
+    );
+    for invalid in [
+        "exists u: org.{v}.read",
+        "exists u org.{u}.read",
+        "exists: org.a",
+        "(exists u: org.{u}.read) & org.{u}.write",
+    ] {
+        assert_eq!(expr_parse(invalid).is_err(), true, "{}", invalid);
+    }
+    // Placeholders outside the quantifier are left to the pattern bindings
+    let pattern = expr_parse_pattern("exists u: org.{o}.user.{u}.read")?;
+    assert_eq!(pattern.vars(), vec!["o".to_string()]);
+    Ok(())
+}
+
+#[test]
+fn exists_check() -> Result<(), PermissionParseError> {
+    let expr = expr_parse("exists u: org.1.user.{u}.read & org.1.user.{u}.write")?;
+    let program = CompiledExpr::compile(&expr);
+    let checker = ComplexCheck::from(&expr);
+    for (perms, expected) in [
+        (vec!["org.1.user.5.read", "org.1.user.5.write"], true),
+        (vec!["org.1.user.5.read", "org.1.user.6.write"], false),
+        (vec!["org.1.user.*.read", "org.1.user.6.write"], true),
+        (vec!["org.1.user.*.read", "org.1.user.*.write"], true),
+        (vec!["org.1.user.5.read", "org.2.user.5.write"], false),
+        (vec!["org.1"], true),
+        (vec![], false),
+    ] {
+        let group = group(&perms)?;
+        assert_eq!(check_expr(&expr, &group), expected, "{:?}", perms);
+        assert_eq!(program.eval(&group), expected, "{:?}", perms);
+        assert_eq!(checker.with_perm(group.perms), expected, "{:?}", perms);
+    }
+    // Some user the group has no say about, which only a glob can grant
+    let expr = expr_parse("exists u: !org.1.user.{u}.read")?;
+    assert_eq!(check_expr(&expr, &group(&["org.1.user.5.read"])?), true);
+    assert_eq!(check_expr(&expr, &group(&["org.1.user.*.read"])?), false);
+    let nested = expr_parse("exists o: org.{o}.billing & exists u: org.{o}.user.{u}.write")?;
+    assert_eq!(
+        check_expr(&nested, &group(&["org.1.billing", "org.1.user.2.write"])?),
+        true
+    );
+    assert_eq!(
+        check_expr(&nested, &group(&["org.1.billing", "org.2.user.2.write"])?),
+        false
+    );
+    Ok(())
+}
+
+#[test]
+fn exists_with_bindings() -> Result<(), PermissionParseError> {
+    let expr = expr_parse_pattern("exists u: org.{o}.user.{u}.read & org.{o}.user.{u}.write")?;
+    let checker = ComplexCheck::from(&expr);
+    let group = group(&[
+        "org.1.user.5.read",
+        "org.1.user.5.write",
+        "org.2.user.5.read",
+    ])?;
+    let bindings: Vec<Bindings> = [1, 2]
+        .iter()
+        .map(|id| Bindings::from([("o".to_string(), Field::ID { id: *id })]))
+        .collect();
+    let result = checker.with_perm_many(group.clone(), &bindings)?;
+    assert_eq!(result[0], true);
+    assert_eq!(result[1], false);
+    assert_eq!(
+        checker.with_perm_many(group, &[Bindings::new()]).is_err(),
+        true
+    );
+    Ok(())
+}
+
+#[test]
+fn exists_in_library() -> Result<(), PermissionParseError> {
+    let library = ExprLibrary::parse("let has_editor(o) = exists u: org.{o}.user.{u}.write;")?;
+    let expr = library.expr_parse("has_editor(1047)")?;
+    assert_eq!(expr.to_string(), "(exists u: org.1047.user.{u}.write)");
+    assert_eq!(check_expr(&expr, &group(&["org.1047.user.5"])?), true);
+    assert_eq!(check_expr(&expr, &group(&["org.1048.user.5"])?), false);
+    assert_eq!(
+        ExprLibrary::parse("let a() = exists u: org.{v}.read;").is_err(),
+        true
+    );
+    Ok(())
+}
+
+#[test]
+fn exists_candidates() -> Result<(), PermissionParseError> {
+    let group = group(&["org.1047.user.5", "org.report.*"])?;
+    let mut tried: Vec<Vec<String>> = vec![];
+    let found = exists(&group, 2, |values| {
+        tried.push(values.iter().map(|v| v.to_string()).collect());
+        false
+    });
+    assert_eq!(found, false);
+    // A glob for any other value, then every name and ID of the group
+    assert_eq!(tried.len(), 36);
+    assert_eq!(tried[0], vec!["*", "*"]);
+    assert_eq!(tried[1], vec!["*", "org"]);
+    assert_eq!(tried[35], vec!["report", "report"]);
+    Ok(())
+}
+
+#[test]
+fn exists_limit() -> Result<(), PermissionParseError> {
+    let limit = |result: Result<ItemExpr, PermissionParseError>| match result {
+        Err(PermissionParseError::LimitExceeded(limit)) => Some(limit),
+        _ => None,
+    };
+    let four = "exists a, b, c, d: org.{a}.{b}.{c}.{d}";
+    assert_eq!(limit(expr_parse(four)), Some(Limit::ExistsVars(3)));
+    assert_eq!(
+        limit(expr_parse(
+            "exists a, b: exists c: org.{a}.{b} & any(exists d: org.{c}.{d})"
+        )),
+        Some(Limit::ExistsVars(3))
+    );
+    // Placeholders quantified side by side are tried one after the other
+    expr_parse("(exists a, b: org.{a}.{b}) & (exists c, d: org.{c}.{d}) & exists e: org.{e}")?;
+    let library = ExprLibrary::parse("let has_user(o) = exists u, v: org.{o}.user.{u}.{v};")?;
+    library.expr_parse("exists o: has_user({o})")?;
+    assert_eq!(
+        limit(library.expr_parse("exists o, p: has_user({o}) & org.{p}")),
+        Some(Limit::ExistsVars(3))
+    );
+    // Decoding applies the default, so what is parsed above it cannot be encoded
+    let limits = ParseLimits {
+        max_exists_vars: 4,
+        ..Default::default()
+    };
+    let exceeded = |result: Result<(), EncodingError>| {
+        matches!(
+            result,
+            Err(EncodingError::Invalid(PermissionParseError::LimitExceeded(
+                Limit::ExistsVars(3)
+            )))
+        )
+    };
+    assert_eq!(
+        exceeded(expr_parse_with_limits(four, &limits)?.encode().map(|_| ())),
+        true
+    );
+    // version, kind, the names `a` to `d` and `org`, then `exists <vars>: org`
+    let encoded = |vars: &[u8]| {
+        let mut input = vec![FORMAT_VERSION, 0x03, 5];
+        for name in ["a", "b", "c", "d", "org"] {
+            input.push(name.len() as u8);
+            input.extend(name.as_bytes());
+        }
+        input.extend([0x08, vars.len() as u8]);
+        input.extend(vars);
+        input.extend([0x00, 1, 0x00, 4, 0]);
+        input
+    };
+    assert_eq!(
+        exceeded(ItemExpr::decode(&encoded(&[0, 1, 2, 3])).map(|_| ())),
+        true
+    );
+    assert_eq!(
+        ItemExpr::decode(&encoded(&[0, 1, 2])).unwrap().to_string(),
+        "exists a, b, c: org"
+    );
+    Ok(())
+}
+
+#[test]
+fn exists_split() -> Result<(), PermissionParseError> {
+    let limits = ParseLimits {
+        max_exists_vars: 5,
+        ..Default::default()
+    };
+    let expr = expr_parse_with_limits(
+        "exists a, b, c, d, e: org.{a}.read & org.{b}.write & org.{c}.{d} | org.{e} | org.x",
+        &limits,
+    )?;
+    assert_eq!(
+        expr.simplify().to_string(),
+        "(exists a: org.{a}.read) & (exists b: org.{b}.write) \
+         & ((exists c, d: org.{c}.{d}) | (exists e: org.{e}) | org.x)"
+    );
+    // Trying every combination of 31 values for 5 placeholders would not finish
+    let perms: Vec<String> = (0..30).map(|id| format!("org.{}.audit", id)).collect();
+    let mut perms: Vec<&str> = perms.iter().map(String::as_str).collect();
+    assert_eq!(check_expr(&expr, &group(&perms)?), false);
+    assert_eq!(CompiledExpr::compile(&expr).eval(&group(&perms)?), false);
+    perms.extend(["org.3.read", "org.4.write", "org.5.doc"]);
+    assert_eq!(check_expr(&expr, &group(&perms)?), true);
+    assert_eq!(ComplexCheck::from(&expr).with_perm(group(&perms)?), true);
+    // Unused placeholders are dropped
+    assert_eq!(
+        expr_parse("exists u, v: org.{u}.read")?
+            .simplify()
+            .to_string(),
+        "exists u: org.{u}.read"
+    );
+    Ok(())
+}
+
+#[test]
+fn exists_display_and_encode() -> Result<(), PermissionParseError> {
+    for (expr, displayed, simplified) in [
+        (
+            "exists u: org.{u}.read & org.{u}.write",
+            "exists u: org.{u}.read & org.{u}.write",
+            "exists u: org.{u}.read & org.{u}.write",
+        ),
+        (
+            "(exists u, v: org.{u}.user.{v}) & org.a",
+            "(exists u, v: org.{u}.user.{v}) & org.a",
+            "(exists u, v: org.{u}.user.{v}) & org.a",
+        ),
+        (
+            "!(exists u: org.{u}.read | org.a)",
+            "!(exists u: org.{u}.read | org.a)",
+            "(!exists u: org.{u}.read) & !org.a",
+        ),
+        (
+            "any(exists u: org.{u}, org.a)",
+            "any(exists u: org.{u}, org.a)",
+            "(exists u: org.{u}) | org.a",
+        ),
+        (
+            "exists u: org.a | !org.a",
+            "exists u: org.a | !org.a",
+            "all()",
+        ),
+    ] {
+        let parsed = expr_parse(expr)?;
+        assert_eq!(parsed.to_string(), displayed);
+        assert_eq!(expr_parse(displayed)? == parsed, true);
+        assert_eq!(parsed.simplify().to_string(), simplified);
+        assert_eq!(
+            ItemExpr::decode(&parsed.encode().unwrap()).unwrap() == parsed,
+            true
+        );
+    }
+    // Placeholders are only encoded where an `exists` binds them
+    assert_eq!(
+        expr_parse_pattern("exists u: org.{o}.{u}")?
+            .encode()
+            .is_err(),
+        true
+    );
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use permission_check::{check, check_one};
+use permission_parser::{
+    PermissionGroup, PermissionItem, PermissionParseError, parse,
+    tokenizer::{Field, parse_uuid},
+};
+
+const UUID: &str = "550e8400-e29b-41d4-a716-446655440000";
+
+#[test]
+fn uuid_parse_and_display() -> Result<(), PermissionParseError> {
+    let uuid = 0x550e8400_e29b_41d4_a716_446655440000;
+    assert_eq!(parse_uuid(UUID), Some(uuid));
+    assert_eq!(parse_uuid(&UUID.to_uppercase()), Some(uuid));
+    for invalid in [
+        "550e8400e29b41d4a716446655440000",
+        "550e8400-e29b-41d4-a716-44665544000",
+        "550e8400-e29b-41d4-a716-44665544000g",
+        "{550e8400-e29b-41d4-a716-446655440000}",
+        "+50e8400-e29b-41d4-a716-446655440000",
+    ] {
+        assert_eq!(parse_uuid(invalid), None, "{}", invalid);
+    }
+    let item = parse(&format!("org.1047.project.\"{}\"uuid.read", UUID))?;
+    assert_eq!(item.perm[3] == Field::Uuid { uuid }, true);
+    assert_eq!(
+        item.to_string(),
+        format!("org.1047.project.\"{}\"uuid.read", UUID)
+    );
+    // Upper case is read, lower case is written
+    let upper = parse(&format!("org.\"{}\"uuid", UUID.to_uppercase()))?;
+    assert_eq!(upper.to_string(), format!("org.\"{}\"uuid", UUID));
+    // Without the suffix it is a name
+    assert_eq!(
+        parse(&format!("org.\"{}\"", UUID))?.perm[1]
+            == Field::Name {
+                name: UUID.to_string()
+            },
+        true
+    );
+    for invalid in [
+        "org.\"550e8400\"uuid",
+        "org.\"not-a-uuid\"uuid",
+        "org.\"550e8400-e29b-41d4-a716-446655440000\"id",
+    ] {
+        assert_eq!(parse(invalid).is_err(), true, "{}", invalid);
+    }
+    Ok(())
+}
+
+#[test]
+fn large_ids() -> Result<(), PermissionParseError> {
+    for (text, field) in [
+        ("org.9223372036854775808", Field::ID { id: 1 << 63 }),
+        ("org.18446744073709551615", Field::ID { id: u64::MAX }),
+        (
+            "org.18446744073709551616",
+            Field::LargeID {
+                id: u64::MAX as u128 + 1,
+            },
+        ),
+        (
+            "org.340282366920938463463374607431768211455",
+            Field::LargeID { id: u128::MAX },
+        ),
+    ] {
+        let item = parse(text)?;
+        assert_eq!(item.perm[1] == field, true, "{}", text);
+        assert_eq!(item.to_string(), text);
+    }
+    assert_eq!(
+        parse("org.340282366920938463463374607431768211456").is_err(),
+        true
+    );
+    assert_eq!(Field::id(5) == Field::ID { id: 5 }, true);
+    assert_eq!(
+        Field::id(u128::MAX) == Field::LargeID { id: u128::MAX },
+        true
+    );
+    // Every ID has one form, so a large one that fits is rejected
+    let item: PermissionItem = vec![
+        Field::Name {
+            name: "org".to_string(),
+        },
+        Field::LargeID { id: 5 },
+    ]
+    .into();
+    assert_eq!(item.validate().is_err(), true);
+    Ok(())
+}
+
+#[test]
+fn ids_match() -> Result<(), PermissionParseError> {
+    let snowflake = "org.340282366920938463463374607431768211455.doc.read";
+    let uuid = format!("org.1047.project.\"{}\"uuid.read", UUID);
+    for (require, granted, expected) in [
+        (snowflake, "org.*.doc.read".to_string(), true),
+        (
+            snowflake,
+            "org.340282366920938463463374607431768211455".to_string(),
+            true,
+        ),
+        (
+            snowflake,
+            "org.340282366920938463463374607431768211454".to_string(),
+            false,
+        ),
+        (snowflake, "org.1047".to_string(), false),
+        (&uuid, "org.1047.project.*".to_string(), true),
+        (&uuid, format!("org.1047.project.\"{}\"uuid", UUID), true),
+        (&uuid, format!("org.1047.project.\"{}\"", UUID), false),
+        (
+            &uuid,
+            "org.1047.project.\"550e8400-e29b-41d4-a716-446655440001\"uuid".to_string(),
+            false,
+        ),
+        (
+            &uuid,
+            format!("org.1047.project.{{\"{}\"uuid,report}}", UUID),
+            true,
+        ),
+    ] {
+        assert_eq!(
+            check_one(&parse(require)?, &parse(&granted)?),
+            expected,
+            "{} by {}",
+            require,
+            granted
+        );
+    }
+    let group: PermissionGroup = vec![parse(&format!("org.\"{}\"uuid.*", UUID))?].into();
+    assert_eq!(check(&parse("org.*.1.read")?, &group), false);
+    Ok(())
+}
+
+#[test]
+fn ids_encode() -> Result<(), PermissionParseError> {
+    for text in [
+        format!("org.\"{}\"uuid.read", UUID),
+        "org.340282366920938463463374607431768211455.*".to_string(),
+        format!("org.{{\"{}\"uuid,18446744073709551616}}", UUID),
+    ] {
+        let item = parse(&text)?;
+        assert_eq!(
+            PermissionItem::decode(&item.encode().unwrap()).unwrap() == item,
+            true,
+            "{}",
+            text
+        );
+    }
+    Ok(())
+}
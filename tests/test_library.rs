@@ -0,0 +1,121 @@
+use permission_check::check_expr;
+use permission_parser::{
+    ExprLibrary, Limit, PermissionParseError, expr_parse, parse, parse_pattern,
+};
+
+const GUARDS: &str = "
+    let can_edit(o, d) = is_org_admin({o}) | org.{o}.doc.{d}.write;
+    let is_org_admin(o) = org.{o}.role.admin.enact | org.{o}.role.owner.enact;
+";
+
+#[test]
+fn library_resolves_calls() -> Result<(), PermissionParseError> {
+    let library = ExprLibrary::parse(GUARDS)?;
+    assert_eq!(library.contains("is_org_admin"), true);
+    let expr = library.expr_parse("can_edit(1047, 3) & !user.blacklist.enact")?;
+    assert_eq!(
+        expr.to_string(),
+        "((org.1047.role.admin.enact | org.1047.role.owner.enact) | org.1047.doc.3.write) & !user.blacklist.enact"
+    );
+    assert_eq!(
+        check_expr(&expr, &parse("org.1047.role.owner")?.into()),
+        true
+    );
+    assert_eq!(check_expr(&expr, &parse("org.1047.doc.3")?.into()), true);
+    assert_eq!(check_expr(&expr, &parse("org.1048.doc.3")?.into()), false);
+
+    let pattern = library.expr_parse_pattern("is_org_admin({org})")?;
+    let bound = match &pattern {
+        permission_parser::ItemExpr::Bracketed(body) => match body.as_ref() {
+            permission_parser::ItemExpr::Or(operands) => match &operands[0] {
+                permission_parser::ItemExpr::Permission(item) => item.clone(),
+                _ => panic!("{}", pattern),
+            },
+            _ => panic!("{}", pattern),
+        },
+        _ => panic!("{}", pattern),
+    };
+    assert_eq!(bound == parse_pattern("org.{org}.role.admin.enact")?, true);
+    Ok(())
+}
+
+#[test]
+fn library_errors() {
+    for (source, message) in [
+        (
+            "let a(x) = b({x}); let b(y) = c({y}) & org; let c(z) = a({z});",
+            "Recursive expression: a -> b -> c -> a",
+        ),
+        ("let a() = a();", "Recursive expression: a -> a"),
+        (
+            "let a(x) = org.{x}; let a(y) = org;",
+            "Expression `a` is defined twice",
+        ),
+        (
+            "let a(x) = org.{y};",
+            "`{y}` is not a parameter of expression `a`",
+        ),
+        ("let a(x) = b({x});", "Unknown expression `b`"),
+        (
+            "let a(x) = org.{x}; let b() = a(1, 2);",
+            "Expression `a` takes 1 arguments but 2 were given",
+        ),
+    ] {
+        match ExprLibrary::parse(source) {
+            Err(PermissionParseError::InvalidOutput(err)) => assert_eq!(err, message),
+            other => panic!("{} gave {:?}", source, other.map(|_| ())),
+        }
+    }
+    assert_eq!(ExprLibrary::parse("let a(x) = org.{x}").is_err(), true);
+    assert_eq!(expr_parse("is_org_admin(1047)").is_err(), true);
+    let library = ExprLibrary::parse(GUARDS).unwrap();
+    assert_eq!(library.expr_parse("is_org_admin({org})").is_err(), true);
+    assert_eq!(library.expr_parse("is_org_admin(***)").is_err(), false);
+    assert_eq!(library.expr_parse("is_org_admin(read)").is_err(), true);
+}
+
+#[test]
+fn library_bounds_expansion() -> Result<(), PermissionParseError> {
+    // Every level doubles the leaves of the one it calls
+    let mut source = "let l0() = org.a | org.b;".to_string();
+    for level in 1..12 {
+        source.push_str(&format!(
+            "let l{}() = l{}() & l{}();",
+            level,
+            level - 1,
+            level - 1
+        ));
+    }
+    assert_eq!(
+        matches!(
+            ExprLibrary::parse(&source),
+            Err(PermissionParseError::LimitExceeded(Limit::Leaves(1024)))
+        ),
+        true
+    );
+    let library = ExprLibrary::parse("let l0() = org.a | org.b; let l1() = l0() & l0();")?;
+    let calls = format!("any({})", vec!["l1()"; 300].join(", "));
+    assert_eq!(
+        matches!(
+            library.expr_parse(&calls),
+            Err(PermissionParseError::LimitExceeded(Limit::Leaves(1024)))
+        ),
+        true
+    );
+    Ok(())
+}
+
+#[test]
+fn library_load() -> Result<(), PermissionParseError> {
+    let path = std::env::temp_dir().join(format!("permission_library_{}.perm", std::process::id()));
+    std::fs::write(&path, GUARDS).unwrap();
+    let library = ExprLibrary::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    let expr = library?.expr_parse("is_org_admin(1047)")?;
+    assert_eq!(
+        check_expr(&expr, &parse("org.1047.role.admin")?.into()),
+        true
+    );
+    assert_eq!(ExprLibrary::load(&path).is_err(), true);
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use permission_check::{ComplexCheck, check_expr};
+use permission_parser::{
+    ItemExpr, Limit, ParseLimits, PermissionGroup, PermissionParseError, expr_parse,
+    expr_parse_with_limits, parse, parse_with_limits,
+};
+
+fn limit(result: Result<impl Sized, PermissionParseError>) -> Option<Limit> {
+    match result {
+        Err(PermissionParseError::LimitExceeded(limit)) => Some(limit),
+        _ => None,
+    }
+}
+
+#[test]
+fn deep_nesting_is_an_error() {
+    let nested = format!("{}org.1047{}", "(".repeat(10_000), ")".repeat(10_000));
+    assert_eq!(limit(expr_parse(&nested)), Some(Limit::Depth(256)));
+    let nots = format!("{}org.1047", "!".repeat(10_000));
+    assert_eq!(limit(expr_parse(&nots)), Some(Limit::Depth(256)));
+    // Each change of operator nests the rest of the chain
+    let alternating = (0..1000)
+        .map(|idx| format!("org.a{} {}", idx, ["&", "|"][idx % 2]))
+        .collect::<Vec<String>>()
+        .join(" ");
+    let alternating = format!("{} org.b", alternating);
+    let limits = ParseLimits {
+        max_len: usize::MAX,
+        max_leaves: usize::MAX,
+        ..Default::default()
+    };
+    assert_eq!(
+        limit(expr_parse_with_limits(&alternating, &limits)),
+        Some(Limit::Depth(256))
+    );
+}
+
+#[test]
+fn expressions_within_limits() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = parse("org.1047")?.into();
+    // 255 nested brackets around a leaf are 256 levels
+    let nested = format!("{}org.1047{}", "(".repeat(255), ")".repeat(255));
+    assert_eq!(check_expr(&expr_parse(&nested)?, &group), true);
+    let nested = format!("({})", nested);
+    assert_eq!(limit(expr_parse(&nested)), Some(Limit::Depth(256)));
+    // Balanced brackets keep many leaves shallow
+    let mut balanced = "org.1048".to_string();
+    for _ in 0..9 {
+        balanced = format!("({} | {})", balanced, balanced);
+    }
+    let expr = expr_parse(&format!("{} | org.1047", balanced))?;
+    assert_eq!(ComplexCheck::from(&expr).with_perm(&group), true);
+    assert_eq!(ItemExpr::decode(&expr.encode().unwrap()).is_ok(), true);
+    Ok(())
+}
+
+#[test]
+fn leaf_and_field_limits() {
+    let limits = ParseLimits {
+        max_leaves: 3,
+        max_fields: 4,
+        ..Default::default()
+    };
+    assert_eq!(
+        limit(expr_parse_with_limits("a | (b & c) | ctx(mfa)", &limits)),
+        Some(Limit::Leaves(3))
+    );
+    assert_eq!(expr_parse_with_limits("a | (b & c)", &limits).is_ok(), true);
+    assert_eq!(
+        limit(expr_parse_with_limits("a.b.c.d.e | b", &limits)),
+        Some(Limit::Fields(4))
+    );
+    assert_eq!(
+        limit(parse_with_limits("a.b.c.d.e", &limits)),
+        Some(Limit::Fields(4))
+    );
+    assert_eq!(parse_with_limits("a.b.c.d", &limits).is_ok(), true);
+    let long = vec!["a"; 65].join(".");
+    assert_eq!(limit(parse(&long)), Some(Limit::Fields(64)));
+}
+
+#[test]
+fn chains_are_one_level_and_conditions_count_towards_depth() -> Result<(), PermissionParseError> {
+    // A chain of the same operator is a single node however long
+    let chain = (0..10_000)
+        .map(|idx| format!("org.a{}", idx))
+        .collect::<Vec<String>>()
+        .join(" | ");
+    assert_eq!(limit(expr_parse(&chain)), Some(Limit::Len(64 * 1024)));
+    let limits = ParseLimits {
+        max_len: usize::MAX,
+        max_leaves: usize::MAX,
+        ..Default::default()
+    };
+    let expr = expr_parse_with_limits(&chain, &limits)?;
+    assert_eq!(
+        matches!(&expr, ItemExpr::Or(operands) if operands.len() == 10_000),
+        true
+    );
+    assert_eq!(check_expr(&expr, &parse("org.a9999")?.into()), true);
+    assert_eq!(check_expr(&expr, &parse("org.b")?.into()), false);
+    assert_eq!(ComplexCheck::from(&expr).with_perm(parse("org.a0")?), true);
+    assert_eq!(
+        expr_parse_with_limits(&expr.to_string(), &limits)? == expr,
+        true
+    );
+    assert_eq!(
+        ItemExpr::decode(&expr.encode().unwrap()).unwrap() == expr,
+        true
+    );
+    let chain = vec!["org.a"; 257].join(" & ");
+    assert_eq!(expr_parse(&chain).is_ok(), true);
+    // The levels of a condition are below its predicate
+    let limits = ParseLimits {
+        max_depth: 4,
+        ..Default::default()
+    };
+    assert_eq!(expr_parse_with_limits("ctx(!a) & b", &limits).is_ok(), true);
+    assert_eq!(
+        limit(expr_parse_with_limits("ctx(!!a) & b", &limits)),
+        Some(Limit::Depth(4))
+    );
+    assert_eq!(
+        limit(expr_parse_with_limits("ctx(a && (b || !c))", &limits)),
+        Some(Limit::Depth(4))
+    );
+    // A chain of conditions is one level however long
+    let chain = vec!["a"; 1000].join(" && ");
+    assert_eq!(
+        expr_parse_with_limits(&format!("ctx({}) & b", chain), &limits).is_ok(),
+        true
+    );
+    Ok(())
+}
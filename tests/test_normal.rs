@@ -140,3 +140,22 @@ fn both_triple_glob_match() {
     let perm2 = parse("perm.***.test.1").unwrap();
     assert_eq!(check_one(&perm1, &perm2), true);
 }
+
+#[test]
+fn triple_glob_shorter_than_permission() {
+    // `***.read` includes `org.read`, which `***.**.**` needs at least three segments for
+    let perm1 = parse("***.read").unwrap();
+    let perm2 = parse("***.**.**").unwrap();
+    assert_eq!(check_one(&perm1, &perm2), false);
+    let perm1 = parse("***.abc").unwrap();
+    let perm2 = parse("**.abc").unwrap();
+    assert_eq!(check_one(&perm1, &perm2), false);
+}
+
+#[test]
+fn double_glob_before_last_is_glob() {
+    // Only the last segment can be a specifier
+    let perm1 = parse("perm.**.abc").unwrap();
+    let perm2 = parse("perm.*.abc").unwrap();
+    assert_eq!(check_one(&perm1, &perm2), true);
+}
@@ -0,0 +1,191 @@
+use permission_check::{CompiledExpr, PermittedValues, check_expr, permitted_values};
+use permission_parser::{
+    Bindings, ItemExpr, PermissionGroup, PermissionParseError, expr_parse, parse, parse_pattern,
+    tokenizer::{Field, IntoField, IntoId, IntoName, ListSpecifier, Specifier},
+};
+
+#[test]
+fn typed_pattern_parse() -> Result<(), PermissionParseError> {
+    for pattern in [
+        "org.{o:id}.doc.{d:name}.read",
+        "org.{o}.doc.{d:id}",
+        "org.{o:name}.*",
+    ] {
+        assert_eq!(parse_pattern(pattern)?.to_string(), pattern);
+    }
+    assert_eq!(
+        parse_pattern("org.{o:id}")? == parse_pattern("org.{o}")?,
+        false
+    );
+    for invalid in [
+        "org.{o:ID}",
+        "org.{o:uuid}",
+        "org.{o:}",
+        "org.{o:id:id}",
+        "org.{o:id,p}",
+    ] {
+        assert_eq!(parse_pattern(invalid).is_err(), true, "{}", invalid);
+    }
+    // Typed or not, placeholders stay out of the string form
+    assert_eq!(parse("org.{o:id}").is_err(), true);
+    Ok(())
+}
+
+#[test]
+fn typed_bind() -> Result<(), PermissionParseError> {
+    let pattern = parse_pattern("org.{o:id}.doc.{d:name}.read")?;
+    let bind = |o: Field, d: Field| {
+        let bindings: Bindings = [("o".to_string(), o), ("d".to_string(), d)].into();
+        pattern.bind(&bindings)
+    };
+    let report = || Field::Name {
+        name: "report".to_string(),
+    };
+    assert_eq!(
+        bind(Field::ID { id: 1047 }, report())?.to_string(),
+        "org.1047.doc.report.read"
+    );
+    assert_eq!(
+        bind(Field::Range { start: 1, end: 5 }, Field::Glob)?.to_string(),
+        "org.1..5.doc.*.read"
+    );
+    assert_eq!(
+        bind(
+            Field::LargeID { id: u128::MAX },
+            Field::Prefix {
+                prefix: "rep".to_string()
+            }
+        )?
+        .to_string(),
+        "org.340282366920938463463374607431768211455.doc.rep*.read"
+    );
+    // "42" as a name is not the ID 42
+    let forty_two = Field::Name {
+        name: "42".to_string(),
+    };
+    assert_eq!(bind(forty_two.clone(), report()).is_err(), true);
+    assert_eq!(
+        bind(Field::ID { id: 1047 }, Field::ID { id: 3 }).is_err(),
+        true
+    );
+    assert_eq!(
+        bind(Field::ID { id: 1047 }, Specifier::Read.into()).is_err(),
+        true
+    );
+    let bindings: Bindings = [("o".to_string(), forty_two)].into();
+    assert_eq!(
+        pattern.bind_known(&bindings).to_string(),
+        "org.{o:id}.doc.{d:name}.read"
+    );
+    Ok(())
+}
+
+#[test]
+fn typed_exists() -> Result<(), PermissionParseError> {
+    let expr = expr_parse("exists u: org.1.user.{u:id}.read & org.1.user.{u:id}.write")?;
+    let program = CompiledExpr::compile(&expr);
+    for (perms, expected) in [
+        (vec!["org.1.user.5.read", "org.1.user.5.write"], true),
+        (
+            vec!["org.1.user.alice.read", "org.1.user.alice.write"],
+            false,
+        ),
+        (vec!["org.1.user.*.read", "org.1.user.*.write"], true),
+        (vec!["org.1.user.1..9.read", "org.1.user.5.write"], true),
+        (vec!["org.1.user.team_*"], false),
+    ] {
+        let group: PermissionGroup = perms
+            .iter()
+            .map(|p| parse(p))
+            .collect::<Result<Vec<_>, _>>()?
+            .into();
+        assert_eq!(check_expr(&expr, &group), expected, "{:?}", perms);
+        assert_eq!(program.eval(&group), expected, "{:?}", perms);
+    }
+    let decoded = ItemExpr::decode(&expr.encode().unwrap()).unwrap();
+    assert_eq!(decoded == expr, true);
+    assert_eq!(decoded.to_string(), expr.to_string());
+    Ok(())
+}
+
+struct OrgId(u64);
+
+impl IntoField for OrgId {
+    fn to_field(&self) -> Result<Field, PermissionParseError> {
+        Ok(Field::ID { id: self.0 })
+    }
+}
+
+#[test]
+fn into_field() -> Result<(), PermissionParseError> {
+    assert_eq!(42u8.to_field()? == Field::ID { id: 42 }, true);
+    assert_eq!(42i32.to_field()? == Field::ID { id: 42 }, true);
+    assert_eq!(
+        u128::MAX.to_field()? == Field::LargeID { id: u128::MAX },
+        true
+    );
+    assert_eq!(
+        "42".to_field()?
+            == Field::Name {
+                name: "42".to_string()
+            },
+        true
+    );
+    assert_eq!(
+        "read".to_string().to_field()?
+            == Field::Name {
+                name: "read".to_string()
+            },
+        true
+    );
+    assert_eq!(Specifier::Read.to_field()? == Specifier::Read.into(), true);
+    assert_eq!(
+        ListSpecifier::Add.to_field()? == ListSpecifier::Add.into(),
+        true
+    );
+    assert_eq!(Field::Glob.to_field()? == Field::Glob, true);
+    assert_eq!((&&OrgId(7)).to_field()? == Field::ID { id: 7 }, true);
+    assert_eq!(7usize.to_id()?, 7);
+    assert_eq!(7usize.to_name(), "7");
+    assert_eq!((&"doc").to_name(), "doc");
+    let uuid = uuid::Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
+    assert_eq!(
+        uuid.to_field()?.to_string(),
+        "\"550e8400-e29b-41d4-a716-446655440000\"uuid"
+    );
+    Ok(())
+}
+
+#[test]
+fn negative_id() {
+    assert_eq!((-1i64).to_field().is_err(), true);
+    assert_eq!((-1i8).to_id().is_err(), true);
+    assert_eq!((-7isize).to_id().is_err(), true);
+    assert_eq!(0i32.to_id().is_ok(), true);
+}
+
+#[test]
+fn typed_permitted_values() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = vec![
+        parse("org.1047.doc.read")?,
+        parse("org.report.doc.read")?,
+        parse("org.1..5.doc")?,
+    ]
+    .into();
+    assert_eq!(
+        permitted_values(&group, &parse_pattern("org.{o:id}.doc.read")?)?
+            == PermittedValues::Values(vec![
+                Field::ID { id: 1047 },
+                Field::Range { start: 1, end: 5 },
+            ]),
+        true
+    );
+    assert_eq!(
+        permitted_values(&group, &parse_pattern("org.{o:name}.doc.read")?)?
+            == PermittedValues::Values(vec![Field::Name {
+                name: "report".to_string()
+            }]),
+        true
+    );
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use permission_check::{ComplexCheck, check_expr, check_expr_with_context};
+use permission_parser::{
+    ItemExpr, PermissionGroup, PermissionParseError, Value, expr_parse, parse,
+};
+use std::collections::HashMap;
+
+fn context(owner_id: i64, subject_id: i64) -> HashMap<String, Value> {
+    HashMap::from([
+        ("owner_id".to_string(), Value::Int(owner_id)),
+        ("subject_id".to_string(), Value::Int(subject_id)),
+    ])
+}
+
+#[test]
+fn predicate_mixes_with_permissions() -> Result<(), PermissionParseError> {
+    let expr = expr_parse(
+        "(org.1047.doc.3.write & ctx(owner_id == subject_id)) | org.1047.role.admin.enact",
+    )?;
+    let checker = ComplexCheck::from(&expr);
+    let writer: PermissionGroup = parse("org.1047.doc.3.write")?.into();
+    let admin: PermissionGroup = parse("org.1047.role.admin")?.into();
+    for (group, ctx, expected) in [
+        (&writer, context(7, 7), true),
+        (&writer, context(7, 8), false),
+        (&admin, context(7, 8), true),
+    ] {
+        assert_eq!(checker.with_perm_and_context(group, &ctx), expected);
+        assert_eq!(check_expr_with_context(&expr, group, &ctx), expected);
+    }
+    // Without a context the predicate has no attributes to compare
+    assert_eq!(checker.with_perm(&writer), false);
+    assert_eq!(check_expr(&expr, &writer), false);
+    Ok(())
+}
+
+#[test]
+fn predicate_operators() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = parse("org.1047")?.into();
+    let ctx = HashMap::from([
+        ("mfa".to_string(), Value::Bool(true)),
+        ("region".to_string(), Value::Str("eu".to_string())),
+    ]);
+    for (expr, expected) in [
+        ("ctx(mfa)", true),
+        ("!ctx(mfa)", false),
+        ("org.1047.doc ^ ctx(mfa)", false),
+        (r#"(ctx(region in ["eu", "uk"]) & org.1047.doc.read)"#, true),
+        (r#"ctx(region == "us" || !mfa) | org.1048"#, false),
+        // A permission named `ctx` is still a permission
+        ("org.1047.ctx.read & ctx(mfa)", true),
+        ("ctx.read", false),
+    ] {
+        assert_eq!(
+            ComplexCheck::from(&expr_parse(expr)?).with_perm_and_context(&group, &ctx),
+            expected,
+            "{}",
+            expr
+        );
+    }
+    assert_eq!(expr_parse("ctx(mfa ==)").is_err(), true);
+    assert_eq!(expr_parse("ctx(mfa) extra").is_err(), true);
+    Ok(())
+}
+
+#[test]
+fn predicate_survives_encoding() -> Result<(), PermissionParseError> {
+    let expr = expr_parse(r#"org.1047.doc.write & ctx(owner_id == subject_id && tier >= 2)"#)?;
+    let decoded = ItemExpr::decode(&expr.encode().unwrap()).unwrap();
+    let group: PermissionGroup = parse("org.1047.doc")?.into();
+    let mut ctx = context(1, 1);
+    for (tier, expected) in [(1, false), (2, true)] {
+        ctx.insert("tier".to_string(), Value::Int(tier));
+        assert_eq!(check_expr_with_context(&decoded, &group, &ctx), expected);
+    }
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use permission_check::{CompiledExpr, ComplexCheck, check_expr};
+use permission_parser::{
+    ItemExpr, PermissionGroup, PermissionItem, PermissionParseError, expr_parse, parse,
+};
+
+fn group(perms: &[&str]) -> Result<PermissionGroup, PermissionParseError> {
+    Ok(perms
+        .iter()
+        .map(|p| parse(p))
+        .collect::<Result<Vec<PermissionItem>, PermissionParseError>>()?
+        .into())
+}
+
+#[test]
+fn quantifiers_parse() -> Result<(), PermissionParseError> {
+    for (expr, count, len) in [
+        ("any(org.a, org.b | org.c)", 1, 2),
+        ("all(org.a, org.b, !org.c)", 3, 3),
+        ("atleast(2, org.a, org.b, org.c, org.d)", 2, 4),
+        ("atleast(1, org.a, (org.b & org.c),)", 1, 2),
+    ] {
+        match expr_parse(expr)? {
+            ItemExpr::AtLeast(c, operands) => {
+                assert_eq!(c, count);
+                assert_eq!(operands.len(), len);
+            }
+            _ => panic!("{} is not a quantifier", expr),
+        }
+    }
+    assert_eq!(
+        matches!(expr_parse("org.any.read & any.read")?, ItemExpr::And(_)),
+        true
+    );
+    // Without operands the quantifiers are constants
+    let org_a = group(&["org.a"])?;
+    for (expr, expected) in [("any()", false), ("all()", true), ("all() & org.a", true)] {
+        let parsed = expr_parse(expr)?;
+        assert_eq!(parsed.to_string(), expr);
+        assert_eq!(check_expr(&parsed, &org_a), expected, "{}", expr);
+        assert_eq!(
+            ItemExpr::decode(&parsed.encode().unwrap()).unwrap() == parsed,
+            true
+        );
+    }
+    for invalid in [
+        "atleast(0)",
+        "atleast(1,)",
+        "atleast(0, org.a)",
+        "atleast(3, org.a, org.b)",
+        "atleast(org.a, org.b)",
+        "all(org.a org.b)",
+    ] {
+        assert_eq!(expr_parse(invalid).is_err(), true, "{}", invalid);
+    }
+    Ok(())
+}
+
+#[test]
+fn quantifiers_check() -> Result<(), PermissionParseError> {
+    let expr = expr_parse(
+        "atleast(2, org.1047.role.admin, org.1047.role.owner, org.1047.role.auditor) & !any(user.blacklist.enact, user.suspended)",
+    )?;
+    let program = CompiledExpr::compile(&expr);
+    let checker = ComplexCheck::from(&expr);
+    for (perms, expected) in [
+        (vec!["org.1047.role.admin", "org.1047.role.auditor"], true),
+        (vec!["org.1047.role"], true),
+        (vec!["org.1047.role.admin"], false),
+        (vec!["org.1047.role", "user.suspended"], false),
+        (vec![], false),
+    ] {
+        let group = group(&perms)?;
+        assert_eq!(check_expr(&expr, &group), expected, "{:?}", perms);
+        assert_eq!(program.eval(&group), expected, "{:?}", perms);
+        assert_eq!(checker.with_perm(group.perms), expected, "{:?}", perms);
+    }
+    Ok(())
+}
+
+#[test]
+fn quantifiers_display_and_encode() -> Result<(), PermissionParseError> {
+    for (expr, displayed) in [
+        ("any(org.a, org.b)", "any(org.a, org.b)"),
+        ("atleast(2, org.a, org.b)", "all(org.a, org.b)"),
+        (
+            "atleast(2, org.a, org.b | org.c, org.d)",
+            "atleast(2, org.a, org.b | org.c, org.d)",
+        ),
+        ("all(org.a) & !org.b", "any(org.a) & !org.b"),
+    ] {
+        let parsed = expr_parse(expr)?;
+        assert_eq!(parsed.to_string(), displayed);
+        assert_eq!(
+            ItemExpr::decode(&parsed.encode().unwrap()).unwrap() == parsed,
+            true
+        );
+    }
+    assert_eq!(
+        expr_parse("!atleast(2, org.a, org.b, org.c, org.d)")?
+            .simplify()
+            .to_string(),
+        "atleast(3, !org.a, !org.b, !org.c, !org.d)"
+    );
+    Ok(())
+}
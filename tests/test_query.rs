@@ -0,0 +1,90 @@
+use permission_check::{PermittedValues, check, permitted_values};
+use permission_parser::{
+    Bindings, PermissionGroup, PermissionParseError, parse, parse_pattern, tokenizer::Field,
+};
+
+#[test]
+fn explicit_values() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = vec![
+        parse("org.1047.doc.3.read")?,
+        parse("org.1047.doc.5")?,
+        parse("org.1047.doc.7.write")?,
+        parse("org.1048.doc.9.read")?,
+        parse("org.1047.doc.report.read")?,
+        parse("org.1047.doc.3.read")?,
+    ]
+    .into();
+    let pattern = parse_pattern("org.1047.doc.{id}.read")?;
+    assert_eq!(
+        permitted_values(&group, &pattern)?
+            == PermittedValues::Values(vec![
+                Field::ID { id: 3 },
+                Field::ID { id: 5 },
+                Field::Name {
+                    name: "report".to_string()
+                },
+            ]),
+        true
+    );
+    assert_eq!(
+        permitted_values(&vec![].into(), &pattern)? == PermittedValues::Values(vec![]),
+        true
+    );
+    Ok(())
+}
+
+#[test]
+fn glob_permits_all() -> Result<(), PermissionParseError> {
+    let pattern = parse_pattern("org.1047.doc.{id}.read")?;
+    for perm in [
+        "org.1047.doc.*.read",
+        "org.1047.doc",
+        "org.1047",
+        "org.*.doc.**",
+        "org.*.***.read",
+    ] {
+        let group: PermissionGroup = vec![parse("org.1047.doc.3.read")?, parse(perm)?].into();
+        assert_eq!(
+            permitted_values(&group, &pattern)? == PermittedValues::All,
+            true
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn agrees_with_check() -> Result<(), PermissionParseError> {
+    let pattern = parse_pattern("org.{org}.doc.read")?;
+    let group: PermissionGroup = vec![
+        parse("org.3.doc")?,
+        parse("org.4.doc.write")?,
+        parse("org.5.doc.**")?,
+        parse("org.6.user.read")?,
+    ]
+    .into();
+    let permitted = permitted_values(&group, &pattern)?;
+    for org in 0..10 {
+        let value = Field::ID { id: org };
+        let bound = pattern.bind(&Bindings::from([("org".to_string(), value.clone())]))?;
+        assert_eq!(permitted.contains(&value), check(&bound, &group));
+    }
+    Ok(())
+}
+
+#[test]
+fn requires_single_variable() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = parse("org")?.into();
+    assert_eq!(
+        permitted_values(&group, &parse_pattern("org.1.doc")?).is_err(),
+        true
+    );
+    assert_eq!(
+        permitted_values(&group, &parse_pattern("org.{org}.doc.{id}")?).is_err(),
+        true
+    );
+    assert_eq!(
+        permitted_values(&group, &parse_pattern("org.{id}.doc.{id}")?)? == PermittedValues::All,
+        true
+    );
+    Ok(())
+}
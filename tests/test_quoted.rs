@@ -0,0 +1,118 @@
+use permission_check::check_one;
+use permission_parser::{
+    PermissionItem, PermissionParseError, expr_parse, parse,
+    tokenizer::{Field, Specifier},
+};
+use proptest::prelude::*;
+
+fn name(name: &str) -> Field {
+    Field::Name {
+        name: name.to_string(),
+    }
+}
+
+proptest! {
+    #[test]
+    fn any_name_round_trips(segment in any::<String>(), prefix in "\\PC+") {
+        let item: PermissionItem = vec![
+            name("org"),
+            name(&segment),
+            Field::Prefix { prefix },
+            Specifier::Read.into(),
+        ]
+        .into();
+        let text = item.to_string();
+        let parsed = parse(&text).unwrap();
+        prop_assert!(parsed == item, "{}", text);
+        prop_assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+fn quoted_names() -> Result<(), PermissionParseError> {
+    let item = parse(r#"org.1047.project."my-project".read"#)?;
+    assert_eq!(
+        item.perm
+            == vec![
+                name("org"),
+                Field::ID { id: 1047 },
+                name("project"),
+                name("my-project"),
+                Specifier::Read.into(),
+            ],
+        true
+    );
+    assert_eq!(item.to_string(), r#"org.1047.project."my-project".read"#);
+    for (text, displayed) in [
+        (r#"org."alice@example.com""#, r#"org."alice@example.com""#),
+        (r#"org."a.b.c".write"#, r#"org."a.b.c".write"#),
+        (r#"org."zürich""#, r#"org."zürich""#),
+        (r#"org."say \"hi\"\n""#, r#"org."say \"hi\"\n""#),
+        (r#"org."read""#, r#"org."read""#),
+        (r#"org."1047""#, r#"org."1047""#),
+        (r#"org."""#, r#"org."""#),
+        (r#"org."plain""#, "org.plain"),
+        (r#"org.r"raw\path""#, r#"org."raw\\path""#),
+        (r#"org."team-"*"#, r#"org."team-"*"#),
+        (r#"org."team_"*"#, "org.team_*"),
+        (r#"org.{"my-project",report}"#, r#"org.{"my-project",report}"#),
+    ] {
+        let parsed = parse(text)?;
+        assert_eq!(parsed.to_string(), displayed, "{}", text);
+        assert_eq!(parse(displayed)? == parsed, true, "{}", text);
+    }
+    // A quoted specifier keyword is a name, not the specifier
+    assert_eq!(
+        parse(r#"org."read""#)?.perm[1] == Specifier::Read.into(),
+        false
+    );
+    assert_eq!(parse(r#"org."x"suffix"#).is_err(), true);
+    assert_eq!(parse(r#"org.""*.read"#).is_err(), true);
+    assert_eq!(parse(r#"org." "*.read"#).is_ok(), true);
+    Ok(())
+}
+
+#[test]
+fn quoted_names_match() -> Result<(), PermissionParseError> {
+    let require = parse(r#"org.1047.project."my-project".read"#)?;
+    for (granted, expected) in [
+        (r#"org.1047.project."my-project""#, true),
+        (r#"org.1047.project."my-"*"#, true),
+        ("org.1047.project.*", true),
+        (r#"org.1047.project."my-project-2""#, false),
+        (r#"org.1047.project."my""#, false),
+    ] {
+        assert_eq!(check_one(&require, &parse(granted)?), expected, "{}", granted);
+    }
+    let expr = expr_parse(r#"org."a-b".read & !org."a-b"."c d""#)?;
+    assert_eq!(expr.to_string(), r#"org."a-b".read & !org."a-b"."c d""#);
+    assert_eq!(expr_parse(&expr.to_string())? == expr, true);
+    Ok(())
+}
+
+#[test]
+fn id_before_symbol() -> Result<(), PermissionParseError> {
+    // `1047.` followed by a symbol or a quote lexes as a float, which is split back
+    for text in [
+        "org.1047.*",
+        "org.1047.**",
+        "org.1047.***.read",
+        r#"org.1047."my-project".read"#,
+        "org.1047.{doc,report}",
+        "org.1047.{read,write}",
+        "org.100..200.*",
+        "org.100..200.{read,write}",
+    ] {
+        let parsed = parse(text)?;
+        assert_eq!(parsed.to_string(), text);
+    }
+    assert_eq!(parse("org.1047.*")?.perm[1] == Field::ID { id: 1047 }, true);
+    assert_eq!(
+        expr_parse("org.1047.* & !org.1048.**")?.to_string(),
+        "org.1047.* & !org.1048.**"
+    );
+    for invalid in ["org.1047.", "org.01.*", "org.1047.5", "org.100..2.5"] {
+        assert_eq!(parse(invalid).is_err(), true, "{}", invalid);
+    }
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use permission_check::{PatternAlgebra, check_one};
+use permission_parser::{
+    PermissionItem,
+    tokenizer::{Field, ListSpecifier, Specifier},
+};
+use proptest::prelude::*;
+use std::fmt::{self, Debug, Display, Formatter};
+
+// Reference semantics of a grant: `*` is one name or ID, `**` one segment of any kind, `***` one
+// or more segments, a prefix, range or alternatives the names and IDs they spell out. A grant with `***` matches up to the end of the permission, one without
+// also matches any segments after its own.
+fn reference(path: &[Field], grant: &[Field]) -> bool {
+    reference_anchored(path, grant, grant.contains(&Field::TripleGlob))
+}
+
+// A requirement pattern stands for the permissions it matches without any segments after it
+fn reference_anchored(path: &[Field], grant: &[Field], anchored: bool) -> bool {
+    fn segment(path: &Field, grant: &Field) -> bool {
+        match grant {
+            Field::Glob => matches!(path, Field::Name { name: _ } | Field::ID { id: _ }),
+            Field::DoubleGlob => true,
+            Field::Prefix { prefix } => {
+                matches!(path, Field::Name { name } if name.starts_with(prefix.as_str()))
+            }
+            Field::Range { start, end } => {
+                matches!(path, Field::ID { id } if start <= id && id < end)
+            }
+            Field::Alternatives { fields } => fields.contains(path),
+            grant => path == grant,
+        }
+    }
+    fn go(path: &[Field], grant: &[Field], anchored: bool) -> bool {
+        match grant.split_first() {
+            None => path.is_empty() || !anchored,
+            Some((Field::TripleGlob, rest)) => {
+                (1..=path.len()).any(|taken| go(&path[taken..], rest, anchored))
+            }
+            Some((field, rest)) => path
+                .split_first()
+                .is_some_and(|(first, path)| segment(first, field) && go(path, rest, anchored)),
+        }
+    }
+    go(path, grant, anchored)
+}
+
+// Shows generated values with `Display`, as the parser's types only derive `Debug` in debug builds
+#[derive(Clone)]
+struct Shown<T>(T);
+
+impl<T: Display> Debug for Shown<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn specifier() -> impl Strategy<Value = Shown<Field>> {
+    let specifiers = [
+        Specifier::Read,
+        Specifier::Write,
+        Specifier::Assign,
+        Specifier::Enact,
+        Specifier::ListSpecifier {
+            specifier: ListSpecifier::Add,
+        },
+        Specifier::ListSpecifier {
+            specifier: ListSpecifier::ListAll,
+        },
+    ];
+    prop::sample::select(
+        specifiers
+            .into_iter()
+            .map(|specifier| Shown(specifier.into()))
+            .collect::<Vec<Shown<Field>>>(),
+    )
+}
+
+fn segment() -> impl Strategy<Value = Shown<Field>> {
+    prop_oneof![
+        prop::sample::select(vec!["org", "doc", "user"]).prop_map(|name| Shown(Field::Name {
+            name: name.to_string()
+        })),
+        (1047u64..1050).prop_map(|id| Shown(Field::ID { id })),
+    ]
+}
+
+// Prefixes, ranges and alternatives of the names and IDs `segment` picks from
+fn segment_pattern() -> impl Strategy<Value = Shown<Field>> {
+    prop_oneof![
+        prop::sample::select(vec!["o", "do", "us", "user"]).prop_map(|prefix| Shown(
+            Field::Prefix {
+                prefix: prefix.to_string()
+            }
+        )),
+        (1046u64..1050, 1u64..4).prop_map(|(start, len)| Shown(Field::Range {
+            start,
+            end: start + len
+        })),
+        prop::collection::vec(segment(), 2..4).prop_map(alternatives),
+    ]
+}
+
+fn specifier_alternatives() -> impl Strategy<Value = Shown<Field>> {
+    prop::collection::vec(specifier(), 2..4).prop_map(alternatives)
+}
+
+fn alternatives(fields: Vec<Shown<Field>>) -> Shown<Field> {
+    let fields = fields.into_iter().map(|Shown(field)| field).collect();
+    Shown(Field::Alternatives { fields })
+}
+
+fn valid(fields: Vec<Shown<Field>>) -> Option<Shown<PermissionItem>> {
+    let item: PermissionItem = fields
+        .into_iter()
+        .map(|Shown(field)| field)
+        .collect::<Vec<Field>>()
+        .into();
+    item.validate().ok().map(|_| Shown(item))
+}
+
+// Names and IDs with an optional specifier at the end
+fn path() -> impl Strategy<Value = Shown<PermissionItem>> {
+    (
+        prop::collection::vec(segment(), 1..7),
+        prop::option::of(specifier()),
+    )
+        .prop_filter_map("valid path", |(mut fields, specifier)| {
+            fields.extend(specifier);
+            valid(fields)
+        })
+}
+
+fn pattern() -> impl Strategy<Value = Shown<PermissionItem>> {
+    (
+        prop::collection::vec(
+            prop_oneof![
+                3 => segment(),
+                1 => segment_pattern(),
+                1 => Just(Shown(Field::Glob)),
+                1 => Just(Shown(Field::DoubleGlob)),
+                1 => Just(Shown(Field::TripleGlob)),
+            ],
+            1..6,
+        ),
+        prop::option::of(prop_oneof![
+            specifier(),
+            specifier_alternatives(),
+            Just(Shown(Field::DoubleGlob))
+        ]),
+    )
+        .prop_filter_map("valid pattern", |(mut fields, last)| {
+            fields.extend(last);
+            valid(fields)
+        })
+}
+
+proptest! {
+    #[test]
+    fn check_one_matches_reference(Shown(path) in path(), Shown(grant) in pattern()) {
+        prop_assert_eq!(
+            check_one(&path, &grant),
+            reference(&path.perm, &grant.perm),
+            "{} by {}",
+            path,
+            grant
+        );
+    }
+
+    #[test]
+    fn subsumption_is_reflexive(Shown(grant) in pattern()) {
+        prop_assert!(check_one(&grant, &grant), "{}", grant);
+        prop_assert!(grant.subsumes(&grant), "{}", grant);
+    }
+
+    // A requirement matches nothing after its last field and a grant does, so `check_one` itself
+    // is not transitive over patterns (`org.1047.read` by `*.1047` by `***.*`), but a grant
+    // subsuming another grants everything it does
+    #[test]
+    fn subsumption_is_transitive(Shown(path) in path(), Shown(a) in pattern(), Shown(b) in pattern(), Shown(c) in pattern()) {
+        if b.subsumes(&a) && c.subsumes(&b) {
+            prop_assert!(c.subsumes(&a), "{} by {} by {}", a, b, c);
+        }
+        if check_one(&path, &a) && b.subsumes(&a) {
+            prop_assert!(check_one(&path, &b), "{} by {} by {}", path, a, b);
+        }
+    }
+
+    // A pattern is granted when everything it matches is granted
+    #[test]
+    fn pattern_requirement_is_sound(Shown(require) in pattern(), Shown(grant) in pattern(), Shown(path) in path()) {
+        if check_one(&require, &grant) && reference_anchored(&path.perm, &require.perm, true) {
+            prop_assert!(
+                reference(&path.perm, &grant.perm),
+                "{} by {} but not {}",
+                require,
+                grant,
+                path
+            );
+        }
+    }
+}
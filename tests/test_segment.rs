@@ -0,0 +1,192 @@
+use permission_check::{PermittedValues, check, check_one, exists, permitted_values};
+use permission_parser::{
+    PermissionGroup, PermissionItem, PermissionParseError, parse, parse_pattern,
+    tokenizer::{Field, Specifier},
+};
+
+#[test]
+fn segment_parse_and_display() -> Result<(), PermissionParseError> {
+    let item = parse("org.team_*.100..200.user.{read,write}")?;
+    assert_eq!(
+        item.perm
+            == vec![
+                Field::Name {
+                    name: "org".to_string()
+                },
+                Field::Prefix {
+                    prefix: "team_".to_string()
+                },
+                Field::Range {
+                    start: 100,
+                    end: 200
+                },
+                Field::Name {
+                    name: "user".to_string()
+                },
+                Field::Alternatives {
+                    fields: vec![Specifier::Read.into(), Specifier::Write.into()]
+                },
+            ],
+        true
+    );
+    for perm in [
+        "org.team_*.read",
+        "org.100..200.read",
+        "org.{1047,1048}.doc.{read,write}",
+        "org.1047.doc.{read,write}",
+        "org.{doc,1048}",
+    ] {
+        let parsed = parse(perm)?;
+        assert_eq!(parsed.to_string(), perm);
+        assert_eq!(parse(&parsed.to_string())? == parsed, true);
+        assert_eq!(
+            PermissionItem::decode(&parsed.encode().unwrap()).unwrap() == parsed,
+            true
+        );
+    }
+    // A single name in braces is still a placeholder
+    assert_eq!(parse_pattern("org.{o}.read")?.has_var(), true);
+    Ok(())
+}
+
+#[test]
+fn segment_parse_errors() {
+    for invalid in [
+        "org.200..100.read",
+        "org.100..100.read",
+        "org.100..=200.read",
+        "org.{read,1047.doc}",
+        "org.{read,*}",
+        "org.{read,{a,b}}",
+        "org.{read,write}.doc",
+        "org.{}",
+    ] {
+        assert_eq!(parse(invalid).is_err(), true, "{}", invalid);
+    }
+}
+
+#[test]
+fn segment_check() -> Result<(), PermissionParseError> {
+    for (require, granted, expected) in [
+        ("org.team_a.read", "org.team_*.read", true),
+        ("org.team_.read", "org.team_*.read", true),
+        ("org.team.read", "org.team_*.read", false),
+        ("org.team_a_b.read", "org.team_a*", true),
+        ("org.1047.read", "org.team_*", false),
+        ("org.150.read", "org.100..200", true),
+        ("org.200.read", "org.100..200", false),
+        ("org.99.read", "org.100..200", false),
+        ("org.report.read", "org.100..200", false),
+        ("org.1047.doc.read", "org.1047.doc.{read,write}", true),
+        ("org.1047.doc.write", "org.1047.doc.{read,write}", true),
+        ("org.1047.doc.read_one", "org.1047.doc.{read,write}", false),
+        ("org.1048.doc", "org.{1047,1048}", true),
+        // Subsumption of patterns in the requirement
+        ("org.120..150.read", "org.100..200", true),
+        ("org.150..250.read", "org.100..200", false),
+        ("org.team_ab*.read", "org.team_*", true),
+        ("org.team_*.read", "org.team_ab*", false),
+        ("org.{team_a,team_b}.read", "org.team_*", true),
+        ("org.{team_a,1047}.read", "org.team_*", false),
+        ("org.3..5.read", "org.{3,4}", true),
+        ("org.3..6.read", "org.{3,4}", false),
+        ("org.100..200.read", "org.*", true),
+        ("org.team_*.read", "org.*.read", true),
+        ("org.{1047,1048}.read", "org.1047", false),
+        ("org.1047.read", "org.1047..1048", true),
+        ("org.1047..1048.read", "org.1047", true),
+    ] {
+        assert_eq!(
+            check_one(&parse(require)?, &parse(granted)?),
+            expected,
+            "{} by {}",
+            require,
+            granted
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn specifier_alternatives() -> Result<(), PermissionParseError> {
+    let granted: PermissionGroup = vec![parse("org.1047.doc.{read,write}")?].into();
+    assert_eq!(check(&parse("org.1047.doc.read")?, &granted), true);
+    assert_eq!(check(&parse("org.1047.doc.enact")?, &granted), false);
+    assert_eq!(check(&parse("org.1047.doc.{read,write}")?, &granted), true);
+    assert_eq!(check(&parse("org.1047.doc.{read,enact}")?, &granted), false);
+    // Like a single specifier, a glob does not cover them
+    let glob: PermissionGroup = vec![parse("org.1047.doc.*")?].into();
+    assert_eq!(check(&parse("org.1047.doc.{read,write}")?, &glob), false);
+    Ok(())
+}
+
+#[test]
+fn segment_permitted_values() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = vec![
+        parse("org.team_*.read")?,
+        parse("org.100..200.read")?,
+        parse("org.{5,report}")?,
+    ]
+    .into();
+    let values = permitted_values(&group, &parse_pattern("org.{o}.read")?)?;
+    assert_eq!(
+        values
+            == PermittedValues::Values(vec![
+                Field::Prefix {
+                    prefix: "team_".to_string()
+                },
+                Field::Range {
+                    start: 100,
+                    end: 200
+                },
+                Field::ID { id: 5 },
+                Field::Name {
+                    name: "report".to_string()
+                },
+            ]),
+        true
+    );
+    assert_eq!(
+        values.contains(&Field::Name {
+            name: "team_a".to_string()
+        }),
+        true
+    );
+    assert_eq!(values.contains(&Field::ID { id: 150 }), true);
+    assert_eq!(values.contains(&Field::ID { id: 250 }), false);
+    Ok(())
+}
+
+#[test]
+fn segment_exists() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = vec![
+        parse("org.100..200.doc.read")?,
+        parse("org.150..300.doc.write")?,
+        parse("org.team_*.user.read")?,
+        parse("org.team_a.user.write")?,
+    ]
+    .into();
+    let both = |values: &[Field], kind: &str| -> Result<bool, PermissionParseError> {
+        let read = parse_pattern(&format!("org.{{v}}.{}.read", kind))?;
+        let write = parse_pattern(&format!("org.{{v}}.{}.write", kind))?;
+        let bindings = [("v".to_string(), values[0].clone())].into();
+        Ok(check(&read.bind(&bindings)?, &group) && check(&write.bind(&bindings)?, &group))
+    };
+    assert_eq!(exists(&group, 1, |v| both(v, "doc").unwrap()), true);
+    assert_eq!(exists(&group, 1, |v| both(v, "user").unwrap()), true);
+    let disjoint: PermissionGroup = vec![
+        parse("org.100..200.doc.read")?,
+        parse("org.200..300.doc.write")?,
+    ]
+    .into();
+    assert_eq!(
+        exists(&disjoint, 1, |v| {
+            let bindings = [("v".to_string(), v[0].clone())].into();
+            let read = parse_pattern("org.{v}.doc.read").unwrap().bind(&bindings);
+            let write = parse_pattern("org.{v}.doc.write").unwrap().bind(&bindings);
+            check(&read.unwrap(), &disjoint) && check(&write.unwrap(), &disjoint)
+        }),
+        false
+    );
+    Ok(())
+}
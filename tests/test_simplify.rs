@@ -0,0 +1,103 @@
+use permission_check::{CompiledExpr, ComplexCheck, check_expr};
+use permission_parser::{
+    ItemExpr, PermissionGroup, PermissionItem, PermissionParseError, expr_parse, parse,
+};
+use proptest::prelude::*;
+use std::fmt::{self, Debug, Display, Formatter};
+
+const LEAVES: [&str; 4] = ["org.a", "org.b", "org.c", "org.d"];
+
+// Every combination of the leaves, which decides the value of any expression over them
+fn groups() -> Vec<PermissionGroup> {
+    (0..1 << LEAVES.len())
+        .map(|mask: usize| {
+            LEAVES
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| mask & (1 << idx) != 0)
+                .map(|(_, leaf)| parse(leaf).unwrap())
+                .collect::<Vec<PermissionItem>>()
+                .into()
+        })
+        .collect()
+}
+
+// Shows generated values with `Display`, as the parser's types only derive `Debug` in debug builds
+#[derive(Clone)]
+struct Shown<T>(T);
+
+impl<T: Display> Debug for Shown<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn unshown(operands: Vec<Shown<ItemExpr>>) -> Vec<ItemExpr> {
+    operands.into_iter().map(|Shown(e)| e).collect()
+}
+
+fn expr() -> impl Strategy<Value = Shown<ItemExpr>> {
+    let leaf = prop::sample::select(LEAVES.to_vec())
+        .prop_map(|leaf| Shown(ItemExpr::Permission(parse(leaf).unwrap())));
+    leaf.prop_recursive(5, 32, 2, |inner| {
+        prop_oneof![
+            inner
+                .clone()
+                .prop_map(|Shown(e)| Shown(ItemExpr::Not(Box::new(e)))),
+            inner
+                .clone()
+                .prop_map(|Shown(e)| Shown(ItemExpr::Bracketed(Box::new(e)))),
+            prop::collection::vec(inner.clone(), 2..4)
+                .prop_map(|operands| Shown(ItemExpr::And(unshown(operands)))),
+            prop::collection::vec(inner.clone(), 2..4)
+                .prop_map(|operands| Shown(ItemExpr::Or(unshown(operands)))),
+            prop::collection::vec(inner.clone(), 2..4)
+                .prop_map(|operands| Shown(ItemExpr::Xor(unshown(operands)))),
+            prop::collection::vec(inner, 1..5)
+                .prop_flat_map(|operands| (1..=operands.len(), Just(operands)))
+                .prop_map(|(count, operands)| Shown(ItemExpr::AtLeast(count, unshown(operands)))),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn simplify_keeps_meaning(Shown(expr) in expr()) {
+        let simplified = expr.simplify();
+        let program = CompiledExpr::compile_normal(&simplified);
+        let check = ComplexCheck::from(&expr);
+        for group in groups() {
+            let expected = check_expr(&expr, &group);
+            prop_assert_eq!(program.eval(&group), expected, "{}", simplified);
+            prop_assert_eq!(check.with_perm(group.perms.clone()), expected, "{}", simplified);
+        }
+    }
+
+    #[test]
+    fn simplified_display_parses_back(Shown(expr) in expr()) {
+        let simplified = expr.simplify();
+        let reparsed = expr_parse(&simplified.to_string()).unwrap();
+        prop_assert!(reparsed.simplify() == simplified, "{}", simplified);
+    }
+}
+
+#[test]
+fn simplify_examples() -> Result<(), PermissionParseError> {
+    for (expr, expected) in [
+        ("(org.a & (org.b & org.c)) & org.a", "org.a & org.b & org.c"),
+        ("!(org.a | !org.b)", "(!org.a) & org.b"),
+        ("(!(org.a & org.b)) | org.c", "(!org.a) | (!org.b) | org.c"),
+        (
+            "org.a & (org.b | org.c) & org.d",
+            "org.a & (org.b | org.c) & org.d",
+        ),
+        ("!!org.a ^ org.b ^ org.a", "org.b"),
+        ("org.a ^ !org.a ^ org.b", "!org.b"),
+        ("org.a & (org.b | !org.b)", "org.a"),
+        ("org.a & org.b & !org.a", "any()"),
+        ("(org.a & org.b) ^ !(org.a & org.b)", "all()"),
+    ] {
+        assert_eq!(expr_parse(expr)?.simplify().to_string(), expected);
+    }
+    Ok(())
+}
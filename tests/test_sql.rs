@@ -0,0 +1,229 @@
+use permission_check::{
+    check,
+    sql::{ResourceMapping, SqlColumn, SqlDialect, SqlValue, sql_predicate},
+};
+use permission_parser::{
+    Bindings, PermissionGroup, PermissionParseError, parse, parse_condition, parse_pattern,
+    tokenizer::Field,
+};
+use rusqlite::{Connection, params_from_iter, types::Value};
+
+const SLUGS: [&str; 4] = ["intro", "faq", "changelog", "roadmap"];
+
+fn database() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute(
+        "CREATE TABLE docs (org_id INTEGER, doc_id INTEGER, slug TEXT)",
+        (),
+    )
+    .unwrap();
+    for org_id in 1046..1050 {
+        for doc_id in 0..12 {
+            conn.execute(
+                "INSERT INTO docs (org_id, doc_id, slug) VALUES (?1, ?2, ?3)",
+                (org_id, doc_id, SLUGS[doc_id % SLUGS.len()]),
+            )
+            .unwrap();
+        }
+    }
+    conn
+}
+
+fn groups() -> Result<Vec<PermissionGroup>, PermissionParseError> {
+    Ok(vec![
+        vec![].into(),
+        vec![parse("org")?].into(),
+        vec![parse("org.1047.doc.3.read")?, parse("org.1047.doc.5")?].into(),
+        vec![
+            parse("org.1047.doc.*.read")?,
+            parse("org.1048.doc.1.read")?,
+            parse("org.1048.doc.2.write")?,
+        ]
+        .into(),
+        vec![parse("org.*.doc.4.read")?, parse("org.1049.doc.**")?].into(),
+        vec![parse("org.*.***.read")?].into(),
+        vec![
+            parse("org.1046.doc.1.read")?,
+            parse("org.1046.doc.2.read")?,
+            parse("org.1046.doc.3.read")?,
+            parse("org.1048.doc.faq.read")?,
+        ]
+        .into(),
+        vec![parse("org.1047.user.3.read")?, parse("user.1047")?].into(),
+        // Conditional grants are skipped, like in `check`
+        vec![
+            parse("org.1047.doc.3.read")?,
+            parse("org.1048")?.when(parse_condition("mfa")?),
+        ]
+        .into(),
+        vec![
+            parse("org.1047.doc.3..6.read")?,
+            parse("org.1048..1050.doc.1")?,
+            parse("org.{1046,1047}.doc.{7,9}.read")?,
+        ]
+        .into(),
+        vec![
+            parse("org.1046.doc.ch*.read")?,
+            parse("org.*.doc.{faq,intro}.read")?,
+            parse("org.1048..1049.doc.road*")?,
+        ]
+        .into(),
+    ])
+}
+
+fn to_value(value: &SqlValue) -> Value {
+    match value {
+        SqlValue::Integer(i) => Value::Integer(*i),
+        SqlValue::Text(t) => Value::Text(t.clone()),
+    }
+}
+
+fn query(conn: &Connection, sql: &str, params: &[SqlValue]) -> Vec<(i64, i64)> {
+    let mut statement = conn
+        .prepare(&format!(
+            "SELECT org_id, doc_id FROM docs WHERE {} ORDER BY org_id, doc_id",
+            sql
+        ))
+        .unwrap();
+    statement
+        .query_map(params_from_iter(params.iter().map(to_value)), |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn post_filter(
+    conn: &Connection,
+    group: &PermissionGroup,
+    bind: impl Fn(i64, i64, String) -> Bindings,
+    pattern: &str,
+) -> Result<Vec<(i64, i64)>, PermissionParseError> {
+    let pattern = parse_pattern(pattern)?;
+    let rows = query(conn, "1 = 1", &[]);
+    let mut statement = conn
+        .prepare("SELECT slug FROM docs WHERE org_id = ?1 AND doc_id = ?2")
+        .unwrap();
+    let mut result = vec![];
+    for (org_id, doc_id) in rows {
+        let slug: String = statement
+            .query_row((org_id, doc_id), |row| row.get(0))
+            .unwrap();
+        if check(&pattern.bind(&bind(org_id, doc_id, slug))?, group) {
+            result.push((org_id, doc_id));
+        }
+    }
+    Ok(result)
+}
+
+#[test]
+fn id_columns_match_post_filter() -> Result<(), PermissionParseError> {
+    let conn = database();
+    let pattern = "org.{org_id}.doc.{doc_id}.read";
+    let mapping = ResourceMapping::new(parse_pattern(pattern)?);
+    for group in groups()? {
+        let predicate = sql_predicate(&group, &mapping, SqlDialect::Sqlite)?;
+        let expected = post_filter(
+            &conn,
+            &group,
+            |org_id, doc_id, _| {
+                Bindings::from([
+                    ("org_id".to_string(), Field::ID { id: org_id as u64 }),
+                    ("doc_id".to_string(), Field::ID { id: doc_id as u64 }),
+                ])
+            },
+            pattern,
+        )?;
+        assert_eq!(query(&conn, &predicate.sql, &predicate.params), expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn name_column_matches_post_filter() -> Result<(), PermissionParseError> {
+    let conn = database();
+    let pattern = "org.{org_id}.doc.{slug}.read";
+    let mapping =
+        ResourceMapping::new(parse_pattern(pattern)?).column("slug", SqlColumn::name("slug"));
+    for group in groups()? {
+        let predicate = sql_predicate(&group, &mapping, SqlDialect::Sqlite)?;
+        let expected = post_filter(
+            &conn,
+            &group,
+            |org_id, _, slug| {
+                Bindings::from([
+                    ("org_id".to_string(), Field::ID { id: org_id as u64 }),
+                    ("slug".to_string(), Field::Name { name: slug }),
+                ])
+            },
+            pattern,
+        )?;
+        assert_eq!(query(&conn, &predicate.sql, &predicate.params), expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn generated_sql() -> Result<(), PermissionParseError> {
+    let mapping = ResourceMapping::new(parse_pattern("org.{org_id}.doc.{doc_id}.read")?);
+    let group: PermissionGroup = vec![
+        parse("org.1046.doc.1.read")?,
+        parse("org.1046.doc.2.read")?,
+        parse("org.1047.doc")?,
+    ]
+    .into();
+    let predicate = sql_predicate(&group, &mapping, SqlDialect::Postgres)?;
+    assert_eq!(
+        predicate.sql,
+        "((\"org_id\" = $1 AND \"doc_id\" IN ($2, $3)) OR (\"org_id\" = $4))"
+    );
+    assert_eq!(
+        predicate.params
+            == vec![
+                SqlValue::Integer(1046),
+                SqlValue::Integer(1),
+                SqlValue::Integer(2),
+                SqlValue::Integer(1047),
+            ],
+        true
+    );
+    assert_eq!(
+        sql_predicate(&parse("org")?.into(), &mapping, SqlDialect::Postgres)?.sql,
+        "(1 = 1)"
+    );
+    assert_eq!(
+        sql_predicate(&vec![].into(), &mapping, SqlDialect::Postgres)?.sql,
+        "(1 = 0)"
+    );
+    let patterns: PermissionGroup = vec![
+        parse("org.1046..1048.doc.1..3.read")?,
+        parse("org.1049.doc.{4,5}")?,
+    ]
+    .into();
+    let predicate = sql_predicate(&patterns, &mapping, SqlDialect::Postgres)?;
+    assert_eq!(
+        predicate.sql,
+        "((\"org_id\" >= $1 AND \"org_id\" < $2 AND \"doc_id\" >= $3 AND \"doc_id\" < $4) OR (\"org_id\" = $5 AND \"doc_id\" IN ($6, $7)))"
+    );
+    let slugs = ResourceMapping::new(parse_pattern("org.{org_id}.doc.{slug}.read")?)
+        .column("slug", SqlColumn::name("slug"));
+    let predicate = sql_predicate(
+        &parse("org.1046.doc.ch*")?.into(),
+        &slugs,
+        SqlDialect::Sqlite,
+    )?;
+    assert_eq!(
+        predicate.sql,
+        "((\"org_id\" = ?1 AND substr(\"slug\", 1, 2) = ?2))"
+    );
+    let unmapped = ResourceMapping {
+        pattern: mapping.pattern.clone(),
+        columns: Default::default(),
+    };
+    assert_eq!(
+        sql_predicate(&group, &unmapped, SqlDialect::Sqlite).is_err(),
+        true
+    );
+    Ok(())
+}
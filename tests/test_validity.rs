@@ -0,0 +1,131 @@
+use permission_check::{
+    CompiledExpr, ComplexCheck, check, check_at, check_expr_at, check_with_context,
+    check_with_context_at,
+};
+use permission_parser::{
+    PermissionGroup, PermissionItem, PermissionParseError, Value, expr_parse, parse,
+    parse_condition,
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn on_call() -> Result<PermissionGroup, PermissionParseError> {
+    Ok(vec![
+        parse("org.1047.doc.*.read")?,
+        parse("org.1047.incident.**")?
+            .not_before(at(1_000))
+            .expires_at(at(2_000)),
+        parse("org.1047.billing.read")?.expires_at(at(500)),
+    ]
+    .into())
+}
+
+#[test]
+fn check_at_respects_window() -> Result<(), PermissionParseError> {
+    let group = on_call()?;
+    let incident = parse("org.1047.incident.7.write")?;
+    let billing = parse("org.1047.billing.read")?;
+    let doc = parse("org.1047.doc.3.read")?;
+    assert_eq!(check_at(&incident, &group, &at(999)), false);
+    assert_eq!(check_at(&incident, &group, &at(1_000)), true);
+    assert_eq!(check_at(&incident, &group, &at(1_999)), true);
+    assert_eq!(check_at(&incident, &group, &at(2_000)), false);
+    assert_eq!(check_at(&billing, &group, &at(499)), true);
+    assert_eq!(check_at(&billing, &group, &at(500)), false);
+    assert_eq!(check_at(&doc, &group, &at(u32::MAX as u64)), true);
+    // Plain checks ignore the window
+    assert_eq!(check(&incident, &group), true);
+    Ok(())
+}
+
+#[test]
+fn active_group_for_expressions() -> Result<(), PermissionParseError> {
+    let checker = ComplexCheck::from(&expr_parse(
+        "org.1047.incident.7.write & !org.1047.billing.read",
+    )?);
+    let group = on_call()?;
+    assert_eq!(checker.with_perm(group.active(&at(100))), false);
+    assert_eq!(checker.with_perm(group.active(&at(1_500))), true);
+    assert_eq!(checker.with_perm(group.active(&at(2_500))), false);
+    Ok(())
+}
+
+#[test]
+fn window_and_condition_together() -> Result<(), PermissionParseError> {
+    let group: PermissionGroup = vec![
+        parse("org.1047.incident.**")?
+            .expires_at(at(2_000))
+            .when(parse_condition("mfa")?),
+    ]
+    .into();
+    let mfa: HashMap<String, Value> = HashMap::from([("mfa".to_string(), true.into())]);
+    let incident = parse("org.1047.incident.7.write")?;
+    assert_eq!(
+        check_with_context_at(&incident, &group, &mfa, &at(1_500)),
+        true
+    );
+    assert_eq!(
+        check_with_context_at(&incident, &group, &mfa, &at(2_500)),
+        false
+    );
+    assert_eq!(
+        check_with_context_at(&incident, &group, &HashMap::new(), &at(1_500)),
+        false
+    );
+    // Without a clock the window is ignored, without a context the grant is skipped
+    assert_eq!(check_with_context(&incident, &group, &mfa), true);
+    assert_eq!(check_at(&incident, &group, &at(1_500)), false);
+    let expr = expr_parse("org.1047.incident.7.write & ctx(mfa)")?;
+    let compiled = CompiledExpr::compile(&expr);
+    let checker = ComplexCheck::from(&expr);
+    for (time, expected) in [(1_500, true), (2_500, false)] {
+        assert_eq!(check_expr_at(&expr, &group, &mfa, &at(time)), expected);
+        assert_eq!(compiled.eval_at(&group, &mfa, &at(time)), expected);
+        assert_eq!(checker.with_perm_at(&group, &mfa, &at(time)), expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn prune_expired_keeps_pending() -> Result<(), PermissionParseError> {
+    let mut group = on_call()?;
+    assert_eq!(group.prune_expired(&at(600)), 1);
+    assert_eq!(
+        group
+            .perms
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>(),
+        vec!["org.1047.doc.*.read", "org.1047.incident.**"]
+    );
+    assert_eq!(group.prune_expired(&at(600)), 0);
+    assert_eq!(group.prune_expired(&at(2_000)), 1);
+    assert_eq!(group.perms.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn validity_survives_encoding() -> Result<(), PermissionParseError> {
+    let group = on_call()?;
+    let decoded = PermissionGroup::decode(&group.encode().unwrap()).unwrap();
+    assert_eq!(decoded.perms == group.perms, true);
+    let item = parse("org.1047.doc")?.expires_at(UNIX_EPOCH + Duration::new(5, 123));
+    assert_eq!(
+        PermissionItem::decode(&item.encode().unwrap()).unwrap() == item,
+        true
+    );
+    assert_eq!(
+        parse("org.1047.doc")?
+            .not_before(UNIX_EPOCH - Duration::from_secs(1))
+            .encode()
+            .is_err(),
+        true
+    );
+    Ok(())
+}